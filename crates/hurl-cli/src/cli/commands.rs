@@ -1,9 +1,28 @@
 use crate::cli::config::Config;
-use crate::cli::parser::RequestConfig;
-use anyhow::Result;
-use reqwest::Client;
-use std::path::PathBuf;
-use std::time::Duration;
+use crate::cli::options::{open_disk_cache, open_history_log, HistoryFilter, TestOptions};
+use crate::cli::parser::{FormField, RequestConfig};
+use anyhow::{anyhow, Context, Result};
+use colored::Colorize;
+use hurl_lib::batch::{self, BatchExecutor, BatchRequest, BatchStats};
+use hurl_lib::config::Config as Settings;
+use hurl_lib::http::client::{ClientConfig, HttpClient};
+use hurl_lib::http::multipart::{Multipart, Part};
+use hurl_lib::http::oauth2::OAuth2Provider;
+use hurl_lib::http::request::RequestBuilder;
+use hurl_lib::http::response::HttpResponse;
+use hurl_lib::http::security::{ProxyConfig, TlsConfig};
+use hurl_lib::storage::diff::{BodyDiff, HeaderChange, JsonChange, LineChange, ResponseDiff};
+use hurl_lib::storage::history::{HistoryEntry, SearchQuery};
+use hurl_lib::test::hml_parser::HmlParser;
+use hurl_lib::test::runner::TestRunner;
+use hurl_lib::test::{TestCase, TestReport, TestResult};
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::Semaphore;
 
 pub async fn handle_get(
     url: &str,
@@ -19,90 +38,64 @@ pub async fn handle_get(
         eprintln!("[VERBOSE] GET {}", request_config.url);
     }
 
-    let client = build_client(request_config.timeout)?;
-    let mut request = client.get(&request_config.url);
-
-    request = apply_headers(request, request_config.headers);
-    request = apply_auth(request, request_config.auth);
-
-    let response = request.send().await?;
+    let response = send_request("GET", request_config, output.as_deref(), config).await?;
 
     if config.should_output() {
-        print_response(response, output, config).await?;
+        print_response(&response, output, config)?;
     }
 
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn handle_post(
     url: &str,
     headers: Vec<String>,
     data: Option<String>,
+    form: Vec<String>,
     auth: Option<String>,
     timeout: Option<u64>,
     output: Option<PathBuf>,
     config: &Config,
 ) -> Result<()> {
-    let request_config = RequestConfig::new(url, headers, auth, timeout, data)?;
+    let request_config = RequestConfig::new(url, headers, auth, timeout, data)?.with_form(form)?;
 
     if config.verbose {
         eprintln!("[VERBOSE] POST {}", request_config.url);
-        if let Some(body) = &request_config.body {
-            eprintln!("[VERBOSE] Body: {}", body);
-        }
+        print_verbose_body(&request_config);
     }
 
-    let client = build_client(request_config.timeout)?;
-    let mut request = client.post(&request_config.url);
-
-    request = apply_headers(request, request_config.headers);
-    request = apply_auth(request, request_config.auth);
-
-    if let Some(body) = request_config.body {
-        request = request.body(body);
-    }
-
-    let response = request.send().await?;
+    let response = send_request("POST", request_config, output.as_deref(), config).await?;
 
     if config.should_output() {
-        print_response(response, output, config).await?;
+        print_response(&response, output, config)?;
     }
 
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn handle_put(
     url: &str,
     headers: Vec<String>,
     data: Option<String>,
+    form: Vec<String>,
     auth: Option<String>,
     timeout: Option<u64>,
     output: Option<PathBuf>,
     config: &Config,
 ) -> Result<()> {
-    let request_config = RequestConfig::new(url, headers, auth, timeout, data)?;
+    let request_config = RequestConfig::new(url, headers, auth, timeout, data)?.with_form(form)?;
 
     if config.verbose {
         eprintln!("[VERBOSE] PUT {}", request_config.url);
-        if let Some(body) = &request_config.body {
-            eprintln!("[VERBOSE] Body: {}", body);
-        }
+        print_verbose_body(&request_config);
     }
 
-    let client = build_client(request_config.timeout)?;
-    let mut request = client.put(&request_config.url);
-
-    request = apply_headers(request, request_config.headers);
-    request = apply_auth(request, request_config.auth);
-
-    if let Some(body) = request_config.body {
-        request = request.body(body);
-    }
-
-    let response = request.send().await?;
+    let response = send_request("PUT", request_config, output.as_deref(), config).await?;
 
     if config.should_output() {
-        print_response(response, output, config).await?;
+        print_response(&response, output, config)?;
     }
 
     Ok(())
@@ -122,53 +115,37 @@ pub async fn handle_delete(
         eprintln!("[VERBOSE] DELETE {}", request_config.url);
     }
 
-    let client = build_client(request_config.timeout)?;
-    let mut request = client.delete(&request_config.url);
-
-    request = apply_headers(request, request_config.headers);
-    request = apply_auth(request, request_config.auth);
-
-    let response = request.send().await?;
+    let response = send_request("DELETE", request_config, output.as_deref(), config).await?;
 
     if config.should_output() {
-        print_response(response, output, config).await?;
+        print_response(&response, output, config)?;
     }
 
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn handle_patch(
     url: &str,
     headers: Vec<String>,
     data: Option<String>,
+    form: Vec<String>,
     auth: Option<String>,
     timeout: Option<u64>,
     output: Option<PathBuf>,
     config: &Config,
 ) -> Result<()> {
-    let request_config = RequestConfig::new(url, headers, auth, timeout, data)?;
+    let request_config = RequestConfig::new(url, headers, auth, timeout, data)?.with_form(form)?;
 
     if config.verbose {
         eprintln!("[VERBOSE] PATCH {}", request_config.url);
-        if let Some(body) = &request_config.body {
-            eprintln!("[VERBOSE] Body: {}", body);
-        }
+        print_verbose_body(&request_config);
     }
 
-    let client = build_client(request_config.timeout)?;
-    let mut request = client.patch(&request_config.url);
-
-    request = apply_headers(request, request_config.headers);
-    request = apply_auth(request, request_config.auth);
-
-    if let Some(body) = request_config.body {
-        request = request.body(body);
-    }
-
-    let response = request.send().await?;
+    let response = send_request("PATCH", request_config, output.as_deref(), config).await?;
 
     if config.should_output() {
-        print_response(response, output, config).await?;
+        print_response(&response, output, config)?;
     }
 
     Ok(())
@@ -187,16 +164,10 @@ pub async fn handle_head(
         eprintln!("[VERBOSE] HEAD {}", request_config.url);
     }
 
-    let client = build_client(request_config.timeout)?;
-    let mut request = client.head(&request_config.url);
-
-    request = apply_headers(request, request_config.headers);
-    request = apply_auth(request, request_config.auth);
-
-    let response = request.send().await?;
+    let response = send_request("HEAD", request_config, None, config).await?;
 
     if config.should_output() {
-        println_response_headers(&response, config);
+        print_response_headers(&response);
     }
 
     Ok(())
@@ -216,142 +187,1018 @@ pub async fn handle_options(
         eprintln!("[VERBOSE] OPTIONS {}", request_config.url);
     }
 
-    let client = build_client(request_config.timeout)?;
-    let mut request = client.request(reqwest::Method::OPTIONS, &request_config.url);
+    let response = send_request("OPTIONS", request_config, output.as_deref(), config).await?;
+
+    if config.should_output() {
+        print_response(&response, output, config)?;
+    }
+
+    Ok(())
+}
+
+pub fn handle_history_list(limit: usize, config: &Config) -> Result<()> {
+    let entries = open_history_log()?.recent(limit)?;
+
+    if config.should_output() {
+        for entry in &entries {
+            print_history_line(entry);
+        }
+    }
+
+    Ok(())
+}
+
+pub fn handle_history_show(id: &str, config: &Config) -> Result<()> {
+    let entry = open_history_log()?.resolve(id)?;
 
-    request = apply_headers(request, request_config.headers);
-    request = apply_auth(request, request_config.auth);
+    if config.should_output() {
+        println!("Id:       {}", entry.id);
+        println!("Date:     {}", format_timestamp(entry.timestamp));
+        println!("Duration: {}ms", entry.duration_ms);
+        if !entry.tags.is_empty() {
+            println!("Tags:     {}", entry.tags.join(", "));
+        }
+        println!();
+        println!("> {} {}", entry.method, entry.url);
+        for (name, value) in &entry.headers {
+            println!("> {}: {}", name, value);
+        }
+        if let Some(body) = &entry.body {
+            println!();
+            println!("{}", body);
+        } else if let Some(size) = entry.body_size {
+            println!();
+            println!("[{} byte body not recorded]", size);
+            for part in &entry.body_parts {
+                let filename = part
+                    .filename
+                    .as_ref()
+                    .map(|f| format!(" filename={}", f))
+                    .unwrap_or_default();
+                println!("  part {}{} ({} bytes)", part.name, filename, part.size);
+            }
+        }
+        println!();
+        println!("< HTTP/1.1 {}", status_line(entry.status));
+        for (name, value) in &entry.response_headers {
+            println!("< {}: {}", name, value);
+        }
+        println!();
+        match entry.response_body_size {
+            Some(size) => println!("[{} byte body not recorded]", size),
+            None => println!("{}", entry.response_body),
+        }
+    }
+
+    Ok(())
+}
 
-    let response = request.send().await?;
+pub fn handle_history_search(filter: &HistoryFilter, config: &Config) -> Result<()> {
+    let query = filter.to_query()?;
+    let page = open_history_log()?.search(&query)?;
 
     if config.should_output() {
-        print_response(response, output, config).await?;
+        for entry in &page.entries {
+            print_history_line(entry);
+        }
+        if page.entries.len() < page.total {
+            eprintln!(
+                "Showing {} of {} matching entries",
+                page.entries.len(),
+                page.total
+            );
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn handle_history_replay(id: &str, diff: bool, config: &Config) -> Result<()> {
+    let entry = open_history_log()?.resolve(id)?;
+    if !entry.is_replayable() {
+        return Err(anyhow!(
+            "Entry {} cannot be replayed: its request body was not recorded",
+            entry.id
+        ));
+    }
+    let settings = config.load_settings()?;
+    let request = entry
+        .to_request()
+        .set_timeout(Duration::from_secs(settings.timeout));
+
+    if config.verbose {
+        eprintln!(
+            "[VERBOSE] Replaying {} {} {}",
+            entry.id, entry.method, entry.url
+        );
+    }
+
+    let response = execute_request(request, None, &settings, config).await?;
+
+    if config.should_output() {
+        if diff {
+            print_response_diff(&ResponseDiff::between(&entry, &response));
+        } else {
+            print_response(&response, None, config)?;
+        }
     }
 
     Ok(())
 }
 
-fn build_client(timeout: Option<u64>) -> Result<Client> {
-    let mut builder = Client::builder();
+pub fn handle_history_delete(ids: Vec<String>, all: bool, config: &Config) -> Result<()> {
+    let log = open_history_log()?;
+
+    let removed = if all {
+        let count = log.search(&SearchQuery::new().limit(0))?.total;
+        log.clear()?;
+        count
+    } else {
+        let ids = ids
+            .iter()
+            .map(|id| Ok(log.resolve(id)?.id))
+            .collect::<Result<Vec<_>>>()?;
+        let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+        log.delete(&ids)?
+    };
 
-    if let Some(secs) = timeout {
-        builder = builder.timeout(Duration::from_secs(secs));
+    if config.should_output() {
+        println!("Deleted {} history entries", removed);
     }
 
-    builder
-        .build()
-        .map_err(|e| anyhow::anyhow!("Failed to build HTTP client: {}", e))
+    Ok(())
 }
 
-fn apply_headers(
-    mut request: reqwest::RequestBuilder,
-    headers: std::collections::HashMap<String, String>,
-) -> reqwest::RequestBuilder {
-    for (key, value) in headers {
-        if let Ok(header_name) = key.parse::<reqwest::header::HeaderName>() {
-            if let Ok(header_value) = value.parse::<reqwest::header::HeaderValue>() {
-                request = request.header(header_name, header_value);
+pub fn handle_history_export(output: Option<PathBuf>, config: &Config) -> Result<()> {
+    let json = open_history_log()?.load()?.export_to_json()?;
+
+    match output {
+        Some(path) => {
+            std::fs::write(&path, json)?;
+            if config.verbose {
+                eprintln!("[VERBOSE] History written to {:?}", path);
             }
         }
+        None => println!("{}", json),
     }
-    request
+
+    Ok(())
 }
 
-fn apply_auth(
-    request: reqwest::RequestBuilder,
-    auth: Option<(String, String)>,
-) -> reqwest::RequestBuilder {
-    match auth {
-        Some((username, password)) => request.basic_auth(username, Some(password)),
-        None => request,
+fn print_history_line(entry: &HistoryEntry) {
+    let id = entry.id.get(..8).unwrap_or(&entry.id);
+    let mut line = format!(
+        "{} {} {} {} {} {}ms",
+        id,
+        format_timestamp(entry.timestamp),
+        entry.status,
+        entry.method,
+        entry.url,
+        entry.duration_ms
+    );
+    if !entry.tags.is_empty() {
+        line.push_str(&format!(" [{}]", entry.tags.join(", ")));
     }
+    println!("{}", line);
 }
 
-async fn print_response(
-    response: reqwest::Response,
-    output: Option<PathBuf>,
+fn format_timestamp(timestamp: u64) -> String {
+    chrono::DateTime::from_timestamp(timestamp as i64, 0)
+        .map(|at| {
+            at.with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string()
+        })
+        .unwrap_or_else(|| timestamp.to_string())
+}
+
+pub fn handle_cache_stats(config: &Config) -> Result<()> {
+    let cache = open_disk_cache()?;
+    let stats = cache.stats()?;
+
+    if config.should_output() {
+        println!("Location:     {}", cache.root().display());
+        println!("Entries:      {}", stats.size);
+        println!("Size:         {} bytes", stats.bytes);
+        println!("Hits:         {}", stats.hits);
+        println!("Misses:       {}", stats.misses);
+        println!("Revalidated:  {}", stats.revalidated);
+        println!("Hit rate:     {:.1}%", stats.hit_rate);
+    }
+
+    Ok(())
+}
+
+pub fn handle_cache_clear(config: &Config) -> Result<()> {
+    let cache = open_disk_cache()?;
+    let removed = cache.stats()?.size;
+    cache.clear()?;
+
+    if config.should_output() {
+        println!("Removed {} cached responses", removed);
+    }
+
+    Ok(())
+}
+
+pub fn handle_cache_ls(config: &Config) -> Result<()> {
+    let mut entries = open_disk_cache()?.entries()?;
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    if config.should_output() {
+        for (key, entry) in entries {
+            let (method, url) = key.split_once("::").unwrap_or(("", &key));
+            let age = entry.current_age(now);
+            let lifetime = entry.freshness_lifetime(false);
+            println!(
+                "{} {} {} {} bytes, {} (age {}s, max-age {}s)",
+                entry.response.status,
+                method,
+                url,
+                entry.size,
+                if age < lifetime { "fresh" } else { "stale" },
+                age,
+                lifetime
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Sends the request. With `output`, the body is streamed straight into that
+/// file and the returned response has an empty body.
+async fn send_request(
+    method: &str,
+    request_config: RequestConfig,
+    output: Option<&Path>,
+    config: &Config,
+) -> Result<HttpResponse> {
+    let settings = config.load_settings()?;
+    let request = build_request(method, request_config, &settings);
+    execute_request(request, output, &settings, config).await
+}
+
+/// Runs every request in the JSONL `file`, `concurrency` at a time, and
+/// writes one result line per request to `out` or stdout, followed by a
+/// summary of the batch. Requests get the same defaults from the settings
+/// file as single requests, and each response is recorded in the history.
+pub async fn handle_batch(
+    file: &Path,
+    concurrency: usize,
+    out: Option<PathBuf>,
+    include_body: bool,
+    stop_on_error: bool,
     config: &Config,
 ) -> Result<()> {
-    let status = response.status();
-    let headers = response.headers().clone();
-    let body = response.text().await?;
+    let input = std::fs::read_to_string(file)
+        .with_context(|| format!("Failed to read {}", file.display()))?;
+    let settings = config.load_settings()?;
+    let timeout = Duration::from_secs(settings.timeout);
+    let requests: Vec<BatchRequest> = batch::parse_jsonl(&input)
+        .with_context(|| format!("Invalid batch file {}", file.display()))?
+        .into_iter()
+        .map(|mut batch_req| {
+            let request = apply_default_headers(batch_req.request.set_timeout(timeout), &settings);
+            batch_req.request = config.client_options.apply_auth(request);
+            batch_req
+        })
+        .collect();
+    let count = requests.len();
+    let sent: Vec<RequestBuilder> = requests.iter().map(|r| r.request.clone()).collect();
+
+    if config.verbose {
+        eprintln!(
+            "[VERBOSE] Running {} requests, {} at a time",
+            count, concurrency
+        );
+    }
+
+    let client = build_client(&settings, config)?;
+    let executor = BatchExecutor::new(concurrency)
+        .add_requests(requests)
+        .with_stop_on_error(stop_on_error);
+    let result = executor.execute(&client).await;
+    config.client_options.save_cookies(&client)?;
+    let result = result?;
+    record_all_history(
+        result
+            .responses
+            .iter()
+            .filter(|r| r.error.is_none())
+            .map(|r| (&sent[r.index], &r.response)),
+        config,
+    );
+
+    let mut lines = String::new();
+    for response in &result.responses {
+        lines.push_str(&response.to_json(include_body).to_string());
+        lines.push('\n');
+    }
+    if let Some(path) = &out {
+        std::fs::write(path, lines)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    } else if config.should_output() {
+        print!("{}", lines);
+    }
+
+    if config.should_output() {
+        // Keep stdout to result lines when they are written there.
+        let summary = format_batch_stats(&result.stats);
+        if out.is_some() {
+            print!("{}", summary);
+        } else {
+            eprint!("{}", summary);
+        }
+    }
+
+    if result.stats.failed > 0 {
+        return Err(anyhow!(
+            "{} of {} requests failed",
+            result.stats.failed,
+            count
+        ));
+    }
+    Ok(())
+}
+
+fn format_batch_stats(stats: &BatchStats) -> String {
+    let ms = |d: Duration| d.as_millis();
+    let mut summary = String::new();
+    summary.push_str(&format!("Requests:  {}\n", stats.total));
+    summary.push_str(&format!(
+        "Succeeded: {} ({:.1}%)\n",
+        stats.succeeded,
+        stats.success_rate()
+    ));
+    summary.push_str(&format!("Failed:    {}\n", stats.failed));
+    if stats.cancelled > 0 {
+        summary.push_str(&format!("Cancelled: {}\n", stats.cancelled));
+    }
+    summary.push_str(&format!(
+        "Duration:  {:.2}s\n",
+        stats.duration.as_secs_f64()
+    ));
+    summary.push_str(&format!(
+        "Latency:   mean {}ms, p50 {}ms, p95 {}ms, p99 {}ms, max {}ms\n",
+        ms(stats.mean_latency()),
+        ms(stats.percentile(50.0)),
+        ms(stats.percentile(95.0)),
+        ms(stats.percentile(99.0)),
+        ms(stats.percentile(100.0))
+    ));
+    summary
+}
+
+pub async fn handle_test(options: &TestOptions, config: &Config) -> Result<()> {
+    let files = discover_test_files(&options.paths)?;
+    let variables = options.variables()?;
+    let reports = options.reports()?;
+    let settings = config.load_settings()?;
+    let client = build_client(&settings, config)?;
+    let runner = Arc::new(TestRunner::with_client(client.clone()).with_variables(variables));
+
+    let mut suites = Vec::new();
+    for file in files {
+        let content = std::fs::read_to_string(&file)
+            .with_context(|| format!("Failed to read {}", file.display()))?;
+        let base_dir = file.parent().unwrap_or(Path::new(".")).to_path_buf();
+        let mut tests = HmlParser::new(&content)
+            .with_base_dir(base_dir)
+            .parse()
+            .map_err(|e| anyhow!("{}: {}", file.display(), e))?;
+        if !options.tags.is_empty() {
+            tests = runner.filter_by_tags(&tests, &options.tags);
+        }
+        if let Some(name) = &options.name {
+            tests = runner.filter_by_name(&tests, name);
+        }
+        if !tests.is_empty() {
+            suites.push((file, tests));
+        }
+    }
+    let total: usize = suites.iter().map(|(_, tests)| tests.len()).sum();
+    if total == 0 {
+        return Err(anyhow!("No tests to run"));
+    }
+    if config.verbose {
+        eprintln!(
+            "[VERBOSE] Running {} tests from {} files, {} at a time",
+            total,
+            suites.len(),
+            options.parallel.max(1)
+        );
+    }
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let limit = Arc::new(Semaphore::new(options.parallel.max(1)));
+    let start = Instant::now();
+
+    let mut handles = Vec::new();
+    for (file, tests) in suites {
+        let permit = limit.clone().acquire_owned().await?;
+        let runner = runner.clone();
+        let stop = stop.clone();
+        let fail_fast = options.fail_fast;
+        let show = config.should_output();
+        handles.push(tokio::spawn(async move {
+            let _permit = permit;
+            run_test_file(&runner, &file, &tests, &stop, fail_fast, show).await
+        }));
+    }
+    let mut results = Vec::new();
+    for handle in handles {
+        results.extend(handle.await??);
+    }
+    config.client_options.save_cookies(&client)?;
+
+    let name = match options.paths.as_slice() {
+        [file] if file.is_file() => file
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        _ => "hurl test".to_string(),
+    };
+    let mut report = TestReport::new(name);
+    for result in results {
+        report.add_result(result);
+    }
+    report.total_duration = start.elapsed();
+    report.finalize();
+
+    for (format, path) in &reports {
+        std::fs::write(path, format.reporter().render(&report))
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
 
+    let skipped = report.skipped_tests();
+    if config.should_output() {
+        let mut summary = format!(
+            "{} passed, {} failed",
+            report.passed_tests(),
+            report.failed_tests()
+        );
+        if skipped > 0 {
+            summary.push_str(&format!(", {} skipped", skipped));
+        }
+        summary.push_str(&format!(" in {:.2}s", report.total_duration.as_secs_f64()));
+        if report.failed_tests() > 0 {
+            println!("\n{}", summary.red().bold());
+        } else {
+            println!("\n{}", summary.green().bold());
+        }
+    }
+
+    if report.failed_tests() > 0 {
+        return Err(anyhow!(
+            "{} of {} tests failed",
+            report.failed_tests(),
+            report.run_tests()
+        ));
+    }
+    Ok(())
+}
+
+/// Runs one file's tests in order, so captured values reach the tests
+/// after them, and prints the file's results as one block. Once `stop` is
+/// set the remaining tests are recorded as skipped.
+async fn run_test_file(
+    runner: &TestRunner,
+    file: &Path,
+    tests: &[TestCase],
+    stop: &AtomicBool,
+    fail_fast: bool,
+    show: bool,
+) -> Result<Vec<TestResult>> {
+    let mut variables = runner.variables().clone();
+    let mut results = Vec::new();
+    let mut output = format!("{}\n", file.display().to_string().bold());
+    for test in tests {
+        if stop.load(Ordering::Relaxed) {
+            results.push(TestResult::skipped(test.name.clone()));
+            continue;
+        }
+        let result = runner.execute_with(test, &mut variables).await?;
+        output.push_str(&format_test_result(&result));
+        if !result.passed && fail_fast {
+            stop.store(true, Ordering::Relaxed);
+        }
+        results.push(result);
+    }
+    if show && results.iter().any(|r| !r.skipped) {
+        print!("{}", output);
+    }
+    Ok(results)
+}
+
+fn format_test_result(result: &TestResult) -> String {
+    let ms = result.duration.as_millis();
+    if result.passed {
+        return format!("  {} {} ({}ms)\n", "✓".green(), result.test_name, ms);
+    }
+    let mut output = format!("  {} {} ({}ms)\n", "✗".red(), result.test_name.bold(), ms);
+    if let Some(error) = &result.error {
+        output.push_str(&format!("      {}\n", error.red()));
+    }
+    for assertion in result.assertion_results.iter().filter(|a| !a.passed) {
+        for line in assertion.format_failure().lines() {
+            output.push_str(&format!("      {}\n", line.red()));
+        }
+    }
+    output
+}
+
+/// The `.hml` files named by `paths`: files as given, directories searched
+/// recursively, and glob patterns expanded. Defaults to the current
+/// directory.
+fn discover_test_files(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let default = [PathBuf::from(".")];
+    let paths = if paths.is_empty() {
+        &default[..]
+    } else {
+        paths
+    };
+
+    let mut files = Vec::new();
+    for path in paths {
+        let pattern = path.to_string_lossy();
+        if pattern.contains(['*', '?', '[']) {
+            let matches =
+                glob::glob(&pattern).with_context(|| format!("Invalid pattern {}", pattern))?;
+            let before = files.len();
+            for entry in matches {
+                let entry = entry?;
+                if entry.is_dir() {
+                    collect_hml_files(&entry, &mut files)?;
+                } else if is_hml_file(&entry) {
+                    files.push(entry);
+                }
+            }
+            if files.len() == before {
+                return Err(anyhow!("No .hml files match {}", pattern));
+            }
+        } else if path.is_dir() {
+            let before = files.len();
+            collect_hml_files(path, &mut files)?;
+            if files.len() == before {
+                return Err(anyhow!("No .hml files found in {}", path.display()));
+            }
+        } else if path.is_file() {
+            files.push(path.clone());
+        } else {
+            return Err(anyhow!("No such file or directory: {}", path.display()));
+        }
+    }
+
+    let mut seen = HashSet::new();
+    files.retain(|file| seen.insert(file.clone()));
+    Ok(files)
+}
+
+fn collect_hml_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let mut entries = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read directory {}", dir.display()))?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort();
+    for entry in entries {
+        let hidden = entry
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+        if entry.is_dir() && !hidden {
+            collect_hml_files(&entry, files)?;
+        } else if is_hml_file(&entry) {
+            files.push(entry);
+        }
+    }
+    Ok(())
+}
+
+fn is_hml_file(path: &Path) -> bool {
+    path.is_file() && path.extension().is_some_and(|ext| ext == "hml")
+}
+
+/// Builds the client from the settings and flags, sends `request`, then
+/// saves cookies and records the exchange in the history.
+async fn execute_request(
+    request: RequestBuilder,
+    output: Option<&Path>,
+    settings: &Settings,
+    config: &Config,
+) -> Result<HttpResponse> {
+    let client = build_client(settings, config)?;
+    let request = config.client_options.apply_auth(request);
+    let response = match output {
+        Some(path) => {
+            client
+                .execute_to(request.clone(), |_| tokio::fs::File::create(path))
+                .await
+        }
+        None => client.execute(request.clone()).await,
+    };
+    config.client_options.save_cookies(&client)?;
+    let response = response?;
+    record_history(&request, &response, config);
+
+    if config.verbose {
+        for hop in &response.redirects {
+            eprintln!(
+                "[VERBOSE] {} {} -> {} {}",
+                hop.method, hop.url, hop.status, hop.location
+            );
+        }
+    }
+
+    if config.verbose && response.attempts > 1 {
+        eprintln!("[VERBOSE] Completed after {} attempts", response.attempts);
+    }
+
+    Ok(response)
+}
+
+/// Appends the exchange to the history. Failing to do so only warns, since
+/// the request itself went through.
+fn record_history(request: &RequestBuilder, response: &HttpResponse, config: &Config) {
+    record_all_history([(request, response)], config);
+}
+
+/// Appends one history entry per exchange, opening the log once.
+fn record_all_history<'a>(
+    exchanges: impl IntoIterator<Item = (&'a RequestBuilder, &'a HttpResponse)>,
+    config: &Config,
+) {
+    if config.client_options.no_history {
+        return;
+    }
+    let recorded = open_history_log().and_then(|log| {
+        for (request, response) in exchanges {
+            let tags = config.client_options.tags.clone();
+            let entry = HistoryEntry::from_request_response(request, response, tags)?;
+            log.append(&entry)?;
+        }
+        Ok(())
+    });
+    if let Err(e) = recorded {
+        if !config.quiet {
+            eprintln!("Warning: failed to record history: {:#}", e);
+        }
+    }
+}
+
+/// A client configured from the settings file, the global flags and the
+/// selected profile.
+fn build_client(settings: &Settings, config: &Config) -> Result<HttpClient> {
+    let mut client_config = build_client_config(settings);
+    config.client_options.apply(&mut client_config)?;
+    if let Some(name) = &config.client_options.profile {
+        apply_profile(settings, name, &mut client_config)?;
+    }
+    Ok(HttpClient::with_config(client_config)?)
+}
+
+fn build_client_config(settings: &Settings) -> ClientConfig {
+    let mut client_config = ClientConfig {
+        default_timeout_secs: settings.timeout,
+        follow_redirects: settings.follow_redirects,
+        ..ClientConfig::default()
+    };
+
+    if !settings.verify_ssl {
+        client_config.tls_config = TlsConfig::permissive();
+    }
+
+    if let Some(proxy) = &settings.proxy {
+        client_config.proxy_config = ProxyConfig::all(proxy);
+    }
+
+    client_config
+}
+
+fn apply_profile(settings: &Settings, name: &str, client_config: &mut ClientConfig) -> Result<()> {
+    let profile = settings.profile(name)?;
+    if let Some(oauth2) = &profile.oauth2 {
+        let provider = OAuth2Provider::new(oauth2.clone())?.with_device_prompt(|device| {
+            eprintln!(
+                "To sign in, open {} and enter the code {}",
+                device.verification_uri, device.user_code
+            );
+        });
+        client_config.oauth2 = Some(Arc::new(provider));
+    }
+    Ok(())
+}
+
+fn build_request(
+    method: &str,
+    request_config: RequestConfig,
+    settings: &Settings,
+) -> RequestBuilder {
+    let mut request = RequestBuilder::new(request_config.url, method)
+        .set_timeout(Duration::from_secs(settings.timeout));
+
+    for (key, value) in request_config.headers {
+        request = request.append_header(key, value);
+    }
+    request = apply_default_headers(request, settings);
+
+    if let Some((username, password)) = request_config.auth {
+        request = request.basic_auth(username, password);
+    }
+
+    if let Some(secs) = request_config.timeout {
+        request = request.set_timeout(Duration::from_secs(secs));
+    }
+
+    if !request_config.form.is_empty() {
+        request = request.multipart_body(build_multipart(request_config.form));
+    } else if let Some(body) = request_config.body {
+        request = request.text_body(body);
+    }
+
+    request
+}
+
+/// Adds the settings file's default headers and User-Agent to `request`,
+/// unless it already sets them.
+fn apply_default_headers(mut request: RequestBuilder, settings: &Settings) -> RequestBuilder {
+    for (key, value) in &settings.headers {
+        if !request.has_header(key) {
+            request = request.header(key, value);
+        }
+    }
+
+    if let Some(user_agent) = &settings.user_agent {
+        if !request.has_header("User-Agent") {
+            request = request.header("User-Agent", user_agent);
+        }
+    }
+
+    request
+}
+
+fn build_multipart(fields: Vec<FormField>) -> Multipart {
+    fields
+        .into_iter()
+        .fold(Multipart::new(), |form, field| match field {
+            FormField::Text { name, value } => form.text(name, value),
+            FormField::File {
+                name,
+                path,
+                content_type,
+                filename,
+            } => {
+                let mut part = Part::file(name, path);
+                if let Some(content_type) = content_type {
+                    part = part.mime(content_type);
+                }
+                if let Some(filename) = filename {
+                    part = part.file_name(filename);
+                }
+                form.part(part)
+            }
+        })
+}
+
+fn print_verbose_body(request_config: &RequestConfig) {
+    for field in &request_config.form {
+        match field {
+            FormField::Text { name, value } => eprintln!("[VERBOSE] Form: {}={}", name, value),
+            FormField::File { name, path, .. } => eprintln!("[VERBOSE] Form: {}=@{}", name, path),
+        }
+    }
+    if let Some(body) = &request_config.body {
+        eprintln!("[VERBOSE] Body: {}", body);
+    }
+}
+
+fn print_response(response: &HttpResponse, output: Option<PathBuf>, config: &Config) -> Result<()> {
     if let Some(path) = output {
-        std::fs::write(&path, &body)?;
         if config.verbose {
             eprintln!("[VERBOSE] Response written to {:?}", path);
         }
     } else {
-        println!("HTTP/1.1 {}", status);
-        for (name, value) in headers.iter() {
-            if let Ok(value_str) = value.to_str() {
-                println!("{}: {}", name, value_str);
-            }
-        }
+        print_response_headers(response);
         println!();
-        println!("{}", body);
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(&response.body)?;
+        stdout.flush()?;
     }
 
     Ok(())
 }
 
-fn println_response_headers(response: &reqwest::Response, config: &Config) {
-    let status = response.status();
-    let headers = response.headers();
+fn print_response_headers(response: &HttpResponse) {
+    println!("HTTP/1.1 {}", status_line(response.status));
+    for (name, value) in response.headers.iter() {
+        println!("{}: {}", name, value);
+    }
+}
 
-    println!("HTTP/1.1 {}", status);
-    for (name, value) in headers.iter() {
-        if let Ok(value_str) = value.to_str() {
-            println!("{}: {}", name, value_str);
+/// Prints what changed since the recording: `+` added, `-` removed,
+/// `~` changed.
+fn print_response_diff(diff: &ResponseDiff) {
+    if diff.status_changed() {
+        println!(
+            "Status: {} -> {}",
+            status_line(diff.status_before),
+            status_line(diff.status_after)
+        );
+    } else {
+        println!("Status: {} (unchanged)", status_line(diff.status_after));
+    }
+    println!(
+        "Latency: {}ms -> {}ms ({:+}ms)",
+        diff.duration_before_ms,
+        diff.duration_after_ms,
+        diff.latency_delta_ms()
+    );
+
+    if !diff.headers.is_empty() {
+        println!();
+        println!("Headers:");
+        for change in &diff.headers {
+            match change {
+                HeaderChange::Added { name, value } => println!("+ {}: {}", name, value),
+                HeaderChange::Removed { name, value } => println!("- {}: {}", name, value),
+                HeaderChange::Changed {
+                    name,
+                    before,
+                    after,
+                } => println!("~ {}: {} -> {}", name, before, after),
+            }
         }
     }
+
+    match &diff.body {
+        BodyDiff::Same => {}
+        BodyDiff::Json(changes) => {
+            println!();
+            println!("Body:");
+            for change in changes {
+                match change {
+                    JsonChange::Added { path, value } => println!("+ {}: {}", path, value),
+                    JsonChange::Removed { path, value } => println!("- {}: {}", path, value),
+                    JsonChange::Changed {
+                        path,
+                        before,
+                        after,
+                    } => println!("~ {}: {} -> {}", path, before, after),
+                }
+            }
+        }
+        BodyDiff::Text(changes) => {
+            println!();
+            println!("Body:");
+            for change in changes {
+                match change {
+                    LineChange::Added(line) => println!("+ {}", line),
+                    LineChange::Removed(line) => println!("- {}", line),
+                }
+            }
+        }
+        BodyDiff::NotRecorded => {
+            println!();
+            println!("Body: not recorded, cannot compare");
+        }
+    }
+
+    if diff.is_empty() {
+        println!();
+        println!("No differences");
+    }
+}
+
+fn status_line(status: u16) -> String {
+    match reqwest::StatusCode::from_u16(status)
+        .ok()
+        .and_then(|s| s.canonical_reason())
+    {
+        Some(reason) => format!("{} {}", status, reason),
+        None => status.to_string(),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::collections::HashMap;
+    use hurl_lib::http::auth::Auth;
+    use hurl_lib::http::request::RequestBody;
+
+    fn request_config(body: Option<String>) -> RequestConfig {
+        RequestConfig::new(
+            "https://example.com",
+            vec!["Accept: application/json".to_string()],
+            Some("user:pass".to_string()),
+            Some(10),
+            body,
+        )
+        .unwrap()
+    }
 
     #[test]
-    fn test_build_client_without_timeout() {
-        let client = build_client(None);
-        assert!(client.is_ok());
+    fn test_build_request_method_and_url() {
+        let request = build_request("DELETE", request_config(None), &Settings::default());
+        assert_eq!(request.method(), "DELETE");
+        assert_eq!(request.url(), "https://example.com");
+        assert!(request.body().is_none());
     }
 
     #[test]
-    fn test_build_client_with_timeout() {
-        let client = build_client(Some(30));
-        assert!(client.is_ok());
+    fn test_build_request_headers() {
+        let request = build_request("GET", request_config(None), &Settings::default());
+        assert_eq!(request.headers().get("Accept"), Some("application/json"));
     }
 
     #[test]
-    fn test_apply_headers() {
-        let mut headers = HashMap::new();
-        headers.insert("Content-Type".to_string(), "application/json".to_string());
-        headers.insert("Accept".to_string(), "application/json".to_string());
-
-        let client = reqwest::Client::new();
-        let request = client.get("http://example.com");
-        let request = apply_headers(request, headers);
+    fn test_build_request_auth() {
+        let request = build_request("GET", request_config(None), &Settings::default());
+        match request.auth() {
+            Auth::Basic { username, password } => {
+                assert_eq!(username, "user");
+                assert_eq!(password, "pass");
+            }
+            _ => panic!("expected Basic auth"),
+        }
+    }
 
-        assert!(request.build().is_ok());
+    #[test]
+    fn test_build_request_timeout() {
+        let request = build_request("GET", request_config(None), &Settings::default());
+        assert_eq!(request.timeout(), Duration::from_secs(10));
     }
 
     #[test]
-    fn test_apply_auth() {
-        let client = reqwest::Client::new();
-        let request = client.get("http://example.com");
-        let request = apply_auth(request, Some(("user".to_string(), "pass".to_string())));
+    fn test_build_request_body() {
+        let request = build_request(
+            "POST",
+            request_config(Some("payload".to_string())),
+            &Settings::default(),
+        );
+        match request.body() {
+            Some(RequestBody::Text(text)) => assert_eq!(text, "payload"),
+            _ => panic!("expected text body"),
+        }
+    }
 
-        assert!(request.build().is_ok());
+    #[test]
+    fn test_build_request_form() {
+        let config = request_config(None)
+            .with_form(vec![
+                "title=Report".to_string(),
+                "file=@report.bin;type=application/pdf".to_string(),
+            ])
+            .unwrap();
+        let request = build_request("POST", config, &Settings::default());
+        let Some(RequestBody::Multipart(form)) = request.body() else {
+            panic!("expected multipart body");
+        };
+        assert_eq!(form.parts().len(), 2);
+        assert_eq!(form.parts()[1].filename.as_deref(), Some("report.bin"));
+        assert_eq!(
+            form.parts()[1].content_type.as_deref(),
+            Some("application/pdf")
+        );
     }
 
     #[test]
-    fn test_apply_auth_none() {
-        let client = reqwest::Client::new();
-        let request = client.get("http://example.com");
-        let request = apply_auth(request, None);
+    fn test_build_request_settings_defaults() {
+        let mut settings = Settings::default();
+        settings
+            .headers
+            .insert("accept".to_string(), "text/plain".to_string());
+        settings
+            .headers
+            .insert("X-Team".to_string(), "platform".to_string());
+        settings.user_agent = Some("hurl-test".to_string());
+        settings.timeout = 5;
+
+        let request = build_request(
+            "GET",
+            RequestConfig::new(
+                "https://example.com",
+                vec!["Accept: application/json".to_string()],
+                None,
+                None,
+                None,
+            )
+            .unwrap(),
+            &settings,
+        );
+
+        assert_eq!(request.headers().len(), 3);
+        assert_eq!(request.headers().get("Accept"), Some("application/json"));
+        assert_eq!(request.headers().get("User-Agent"), Some("hurl-test"));
+        assert_eq!(request.timeout(), Duration::from_secs(5));
+    }
 
-        assert!(request.build().is_ok());
+    #[test]
+    fn test_status_line() {
+        assert_eq!(status_line(200), "200 OK");
+        assert_eq!(status_line(404), "404 Not Found");
+        assert_eq!(status_line(599), "599");
     }
 }
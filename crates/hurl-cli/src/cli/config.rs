@@ -1,3 +1,6 @@
+use crate::cli::options::ClientOptions;
+use anyhow::Result;
+use hurl_lib::config::Config as Settings;
 use std::path::PathBuf;
 
 #[derive(Debug, Clone)]
@@ -5,6 +8,7 @@ pub struct Config {
     pub verbose: bool,
     pub quiet: bool,
     pub config_path: Option<PathBuf>,
+    pub client_options: ClientOptions,
 }
 
 impl Config {
@@ -13,10 +17,27 @@ impl Config {
             verbose,
             quiet,
             config_path,
+            client_options: ClientOptions::default(),
         }
     }
 
+    pub fn with_client_options(mut self, client_options: ClientOptions) -> Self {
+        self.client_options = client_options;
+        self
+    }
+
     pub fn should_output(&self) -> bool {
         !self.quiet
     }
+
+    pub fn load_settings(&self) -> Result<Settings> {
+        match &self.config_path {
+            Some(path) => Ok(Settings::from_file(path.clone())?),
+            // Without a config file, redirects are only followed with -L, like curl.
+            None => Ok(Settings {
+                follow_redirects: false,
+                ..Settings::default()
+            }),
+        }
+    }
 }
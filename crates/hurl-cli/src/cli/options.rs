@@ -0,0 +1,950 @@
+use anyhow::{anyhow, Context, Result};
+use clap::Args;
+use hurl_lib::http::auth::Auth;
+use hurl_lib::http::chaining::Variables;
+use hurl_lib::http::client::{ClientConfig, HttpClient};
+use hurl_lib::http::cookies::{CookieFileFormat, CookiePersistence, SharedCookieJar};
+use hurl_lib::http::proxy::split_list;
+use hurl_lib::http::rate_limit::{Rate, RateLimiter};
+use hurl_lib::http::request::RequestBuilder;
+use hurl_lib::http::security::{CertificateValidation, ProxyAuth, ProxyType, TlsVersion};
+use hurl_lib::storage::cache::CachePolicies;
+use hurl_lib::storage::history::{SearchQuery, SortKey};
+use hurl_lib::storage::{DiskCache, HistoryLog};
+use hurl_lib::test::reporters::ReportFormat;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Transport options shared by every subcommand that sends requests.
+#[derive(Args, Debug, Clone, Default)]
+pub struct ClientOptions {
+    #[arg(
+        global = true,
+        long,
+        value_name = "N",
+        help = "Retry transient failures up to N times"
+    )]
+    pub retry: Option<u32>,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "MS",
+        help = "Base delay between retries in milliseconds"
+    )]
+    pub retry_delay: Option<u64>,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "CODES",
+        value_delimiter = ',',
+        help = "Status codes to retry (default: 429,502,503,504)"
+    )]
+    pub retry_on: Vec<u16>,
+
+    #[arg(
+        global = true,
+        long,
+        help = "Also retry non-idempotent methods such as POST"
+    )]
+    pub retry_all_methods: bool,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "[HOST=]RATE",
+        help = "Limit request rate, e.g. 50/s or api.example.com=5/s (repeatable)"
+    )]
+    pub rate: Vec<String>,
+
+    #[arg(global = true, short = 'L', long, help = "Follow redirects")]
+    pub location: bool,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "N",
+        help = "Maximum number of redirects to follow"
+    )]
+    pub max_redirs: Option<u32>,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "NAME",
+        help = "Use the named profile from the config file"
+    )]
+    pub profile: Option<String>,
+
+    #[arg(
+        global = true,
+        long,
+        help = "Use HTTP Digest authentication with --auth credentials"
+    )]
+    pub digest: bool,
+
+    #[arg(
+        global = true,
+        short = 'x',
+        long,
+        value_name = "URL",
+        help = "Send requests through this proxy"
+    )]
+    pub proxy: Option<String>,
+
+    #[arg(
+        global = true,
+        short = 'U',
+        long,
+        value_name = "USER:PASS",
+        help = "Proxy credentials"
+    )]
+    pub proxy_user: Option<String>,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "HOSTS",
+        help = "Comma-separated hosts, domains or CIDRs to reach directly ('*' for all)"
+    )]
+    pub noproxy: Option<String>,
+
+    #[arg(
+        global = true,
+        short = 'k',
+        long,
+        help = "Skip TLS certificate verification"
+    )]
+    pub insecure: bool,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "FILE",
+        help = "PEM bundle of CA certificates to trust"
+    )]
+    pub cacert: Option<PathBuf>,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "FILE",
+        help = "Client certificate (PEM, or PKCS#12 .p12/.pfx)"
+    )]
+    pub cert: Option<PathBuf>,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "FILE",
+        requires = "cert",
+        help = "Private key for --cert (defaults to the --cert file)"
+    )]
+    pub key: Option<PathBuf>,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "PASSWORD",
+        help = "Password for a PKCS#12 client certificate"
+    )]
+    pub pass: Option<String>,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "HASHES",
+        value_delimiter = ';',
+        help = "Public key pins, e.g. sha256//<base64>;sha256//<base64>"
+    )]
+    pub pinnedpubkey: Vec<String>,
+
+    #[arg(
+        global = true,
+        long,
+        value_name = "LIST",
+        value_delimiter = ':',
+        help = "Allowed TLS cipher suites, colon-separated"
+    )]
+    pub ciphers: Vec<String>,
+
+    #[arg(global = true, long = "tlsv1.2", help = "Require TLS 1.2 or later")]
+    pub tlsv1_2: bool,
+
+    #[arg(
+        global = true,
+        long = "tlsv1.3",
+        conflicts_with = "tlsv1_2",
+        help = "Require TLS 1.3"
+    )]
+    pub tlsv1_3: bool,
+
+    #[arg(
+        global = true,
+        short = 'b',
+        long,
+        value_name = "FILE",
+        help = "Read cookies from FILE (JSON or Netscape cookies.txt) before sending"
+    )]
+    pub cookie: Option<PathBuf>,
+
+    #[arg(
+        global = true,
+        short = 'c',
+        long,
+        value_name = "FILE",
+        help = "Write all cookies to FILE after the request (JSON for .json, else Netscape)"
+    )]
+    pub cookie_jar: Option<PathBuf>,
+
+    #[arg(
+        global = true,
+        long,
+        help = "Cache GET responses on disk, honoring Cache-Control (see `hurl cache`)"
+    )]
+    pub cache: bool,
+
+    #[arg(
+        global = true,
+        long = "tag",
+        value_name = "TAG",
+        help = "Tag the recorded history entry (repeatable)"
+    )]
+    pub tags: Vec<String>,
+
+    #[arg(
+        global = true,
+        long,
+        help = "Do not record this request in the history"
+    )]
+    pub no_history: bool,
+}
+
+/// Environment variables with this prefix become template variables for
+/// `hurl test`: `HURL_VAR_token` sets `{{token}}`.
+pub const VARIABLE_ENV_PREFIX: &str = "HURL_VAR_";
+
+/// What `hurl test` runs and how.
+#[derive(Args, Debug, Clone, Default)]
+pub struct TestOptions {
+    #[arg(
+        value_name = "PATH",
+        help = "Test files, directories to search for .hml files, or glob patterns (default: .)"
+    )]
+    pub paths: Vec<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "TEXT",
+        help = "Run only tests whose name contains TEXT"
+    )]
+    pub name: Option<String>,
+
+    // Shares its id with the global history `--tag`, which clap then
+    // leaves off this subcommand.
+    #[arg(
+        long = "tag",
+        value_name = "TAG",
+        help = "Run only tests with any of these tags (repeatable)"
+    )]
+    pub tags: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 1,
+        help = "Run up to N files at once; tests within a file run in order"
+    )]
+    pub parallel: usize,
+
+    #[arg(long, help = "Stop after the first failing test")]
+    pub fail_fast: bool,
+
+    #[arg(
+        long = "report",
+        value_name = "FORMAT:FILE",
+        help = "Write a report: junit, tap, json, html or text (repeatable)"
+    )]
+    pub reports: Vec<String>,
+
+    #[arg(long, value_name = "FILE", help = "Same as --report html:FILE")]
+    pub report_html: Option<PathBuf>,
+
+    #[arg(
+        long = "var",
+        value_name = "NAME=VALUE",
+        help = "Set a template variable (repeatable); overrides --variables-file and HURL_VAR_* variables"
+    )]
+    pub vars: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Read template variables from a JSON object or NAME=VALUE lines (repeatable)"
+    )]
+    pub variables_file: Vec<PathBuf>,
+}
+
+impl TestOptions {
+    /// The reports to write, from `--report` and `--report-html`.
+    pub fn reports(&self) -> Result<Vec<(ReportFormat, PathBuf)>> {
+        let mut reports = Vec::new();
+        for spec in &self.reports {
+            let (format, path) = spec
+                .split_once(':')
+                .filter(|(_, path)| !path.is_empty())
+                .ok_or_else(|| anyhow!("Invalid --report '{}': expected FORMAT:FILE", spec))?;
+            let format = ReportFormat::parse(format).ok_or_else(|| {
+                anyhow!(
+                    "Unknown report format '{}': expected junit, tap, json, html or text",
+                    format
+                )
+            })?;
+            reports.push((format, PathBuf::from(path)));
+        }
+        if let Some(path) = &self.report_html {
+            reports.push((ReportFormat::Html, path.clone()));
+        }
+        Ok(reports)
+    }
+
+    /// Template variables from the environment, then `--variables-file`,
+    /// then `--var`, later ones winning.
+    pub fn variables(&self) -> Result<Variables> {
+        self.variables_with_env(std::env::vars())
+    }
+
+    fn variables_with_env(
+        &self,
+        env: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Variables> {
+        let mut variables = Variables::new();
+        variables.set_from_env_prefix(VARIABLE_ENV_PREFIX, env);
+        for path in &self.variables_file {
+            variables
+                .load_file(path)
+                .with_context(|| format!("Failed to read variables from {}", path.display()))?;
+        }
+        for assignment in &self.vars {
+            variables
+                .set_assignment(assignment)
+                .with_context(|| format!("Invalid --var '{}'", assignment))?;
+        }
+        Ok(variables)
+    }
+}
+
+/// The on-disk response cache under the XDG cache directory.
+pub fn open_disk_cache() -> Result<DiskCache> {
+    let dir = DiskCache::default_dir()
+        .ok_or_else(|| anyhow!("Cannot locate a cache directory; set XDG_CACHE_HOME"))?;
+    DiskCache::open(&dir, CachePolicies::default())
+        .with_context(|| format!("Failed to open cache {}", dir.display()))
+}
+
+/// The request history under the XDG data directory.
+pub fn open_history_log() -> Result<HistoryLog> {
+    let dir = HistoryLog::default_dir()
+        .ok_or_else(|| anyhow!("Cannot locate a data directory; set XDG_DATA_HOME"))?;
+    HistoryLog::open(&dir).with_context(|| format!("Failed to open history {}", dir.display()))
+}
+
+impl ClientOptions {
+    pub fn apply(&self, client_config: &mut ClientConfig) -> Result<()> {
+        client_config.max_retries = self.retry.unwrap_or(0);
+
+        if let Some(delay) = self.retry_delay {
+            client_config.retry_delay_ms = delay;
+        }
+
+        if !self.retry_on.is_empty() {
+            if let Some(code) = self.retry_on.iter().find(|c| !(100..=599).contains(*c)) {
+                return Err(anyhow!("Invalid status code in --retry-on: {}", code));
+            }
+            client_config.retry_on_status = self.retry_on.clone();
+        }
+
+        client_config.retry_non_idempotent = self.retry_all_methods;
+
+        if self.location {
+            client_config.follow_redirects = true;
+        }
+        if let Some(max) = self.max_redirs {
+            client_config.max_redirects = max;
+        }
+
+        client_config.rate_limiter = self.rate_limiter()?.map(Arc::new);
+        self.apply_proxy(client_config)?;
+        self.apply_tls(client_config);
+        self.apply_cookies(client_config)?;
+        if self.cache {
+            client_config.cache = Some(Arc::new(open_disk_cache()?));
+        }
+        Ok(())
+    }
+
+    /// Writes the client's cookies to the `--cookie-jar` file, if one was given.
+    pub fn save_cookies(&self, client: &HttpClient) -> Result<()> {
+        if let (Some(path), Some(jar)) = (&self.cookie_jar, client.cookie_jar()) {
+            let mut cookies = jar.snapshot();
+            cookies.remove_expired();
+            CookiePersistence::new(path)
+                .with_format(CookieFileFormat::from_path(path))
+                .save(&cookies)
+                .with_context(|| format!("Failed to write cookie jar {}", path.display()))?;
+        }
+        Ok(())
+    }
+
+    /// Switches `-u` credentials from Basic to Digest when `--digest` is set.
+    pub fn apply_auth(&self, request: RequestBuilder) -> RequestBuilder {
+        match request.auth() {
+            Auth::Basic { username, password } if self.digest => {
+                let auth = Auth::digest(username, password);
+                request.with_auth(auth)
+            }
+            _ => request,
+        }
+    }
+
+    /// Builds the limiter for the `--rate` options; `None` when there are
+    /// none.
+    fn rate_limiter(&self) -> Result<Option<RateLimiter>> {
+        if self.rate.is_empty() {
+            return Ok(None);
+        }
+        let mut limiter = RateLimiter::new();
+        for spec in &self.rate {
+            let (host, rate) = match spec.rsplit_once('=') {
+                Some((host, rate)) => (Some(host.trim()), rate),
+                None => (None, spec.as_str()),
+            };
+            let rate = Rate::parse(rate).ok_or_else(|| {
+                anyhow!(
+                    "Invalid --rate '{}': expected N/s, N/m or N/h, optionally prefixed by HOST=",
+                    spec
+                )
+            })?;
+            limiter = match host {
+                Some("") => return Err(anyhow!("Invalid --rate '{}': empty host", spec)),
+                Some(host) => limiter.with_host(host, rate),
+                None => limiter.with_global(rate),
+            };
+        }
+        Ok(Some(limiter))
+    }
+
+    fn apply_proxy(&self, client_config: &mut ClientConfig) -> Result<()> {
+        let proxy = &mut client_config.proxy_config;
+
+        if let Some(url) = &self.proxy {
+            proxy.proxy = Some(ProxyType::All { url: url.clone() });
+        }
+        if let Some(user) = &self.proxy_user {
+            let (username, password) = user
+                .split_once(':')
+                .ok_or_else(|| anyhow!("--proxy-user must be in the form USER:PASS"))?;
+            proxy.auth = Some(ProxyAuth {
+                username: username.to_string(),
+                password: password.to_string(),
+            });
+        }
+        if let Some(hosts) = &self.noproxy {
+            proxy.no_proxy = split_list(hosts);
+        }
+        Ok(())
+    }
+
+    fn apply_cookies(&self, client_config: &mut ClientConfig) -> Result<()> {
+        if let Some(path) = &self.cookie {
+            let jar = CookiePersistence::new(path)
+                .load()
+                .with_context(|| format!("Failed to read cookies from {}", path.display()))?;
+            client_config.cookie_jar = Some(Arc::new(SharedCookieJar::new(jar)));
+        }
+        Ok(())
+    }
+
+    fn apply_tls(&self, client_config: &mut ClientConfig) {
+        let tls = &mut client_config.tls_config;
+
+        if let Some(ca_bundle) = &self.cacert {
+            tls.validation = CertificateValidation::Custom {
+                ca_bundle: ca_bundle.clone(),
+            };
+        }
+        // Like curl, -k wins over --cacert.
+        if self.insecure {
+            tls.validation = CertificateValidation::Permissive;
+        }
+
+        if let Some(cert) = &self.cert {
+            tls.client_cert = Some(cert.clone());
+            tls.client_key = self.key.clone();
+            if !tls.is_pkcs12() && tls.client_key.is_none() {
+                tls.client_key = Some(cert.clone());
+            }
+            tls.client_cert_password = self.pass.clone();
+        }
+
+        if !self.pinnedpubkey.is_empty() {
+            tls.cert_pinning = Some(self.pinnedpubkey.clone());
+        }
+        if !self.ciphers.is_empty() {
+            tls.ciphers = self.ciphers.clone();
+        }
+
+        if self.tlsv1_3 {
+            tls.min_tls_version = TlsVersion::TLS1_3;
+        } else if self.tlsv1_2 {
+            tls.min_tls_version = TlsVersion::TLS1_2;
+        }
+    }
+}
+
+/// Filters and paging for `hurl history search`.
+#[derive(Args, Debug, Clone, Default)]
+pub struct HistoryFilter {
+    #[arg(long, help = "Match URLs containing this text")]
+    pub url: Option<String>,
+
+    #[arg(long, help = "Match this method")]
+    pub method: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "CODE",
+        help = "Match a status code (404) or class (4xx)"
+    )]
+    pub status: Option<String>,
+
+    #[arg(long = "tag", value_name = "TAG", help = "Match any of these tags")]
+    pub tags: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "TIME",
+        help = "Recorded at or after TIME (RFC 3339, YYYY-MM-DD or Unix seconds)"
+    )]
+    pub since: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "TIME",
+        help = "Recorded at or before TIME; a bare date includes the whole day"
+    )]
+    pub until: Option<String>,
+
+    #[arg(long, value_name = "MS", help = "Took at least MS milliseconds")]
+    pub min_duration: Option<u128>,
+
+    #[arg(long, value_name = "MS", help = "Took at most MS milliseconds")]
+    pub max_duration: Option<u128>,
+
+    #[arg(
+        long = "header",
+        value_name = "NAME[:VALUE]",
+        help = "Request or response header NAME, with a value containing VALUE"
+    )]
+    pub headers: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "WORDS",
+        help = "Match request or response bodies containing all WORDS"
+    )]
+    pub text: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "KEY",
+        default_value = "timestamp",
+        help = "Sort by timestamp, duration, status, method or url"
+    )]
+    pub sort: String,
+
+    #[arg(long, help = "Sort ascending instead of descending")]
+    pub asc: bool,
+
+    #[arg(short = 'n', long, value_name = "N", help = "Show at most N entries")]
+    pub limit: Option<usize>,
+
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 0,
+        help = "Skip the first N entries"
+    )]
+    pub offset: usize,
+}
+
+impl HistoryFilter {
+    pub fn to_query(&self) -> Result<SearchQuery> {
+        let mut query = SearchQuery::new();
+        query.url = self.url.clone();
+        query.method = self.method.clone();
+        query.tags = (!self.tags.is_empty()).then(|| self.tags.clone());
+        query.min_duration_ms = self.min_duration;
+        query.max_duration_ms = self.max_duration;
+        query.text = self.text.clone();
+        query.descending = !self.asc;
+        query.offset = self.offset;
+        query.limit = self.limit;
+        query.sort = SortKey::parse(&self.sort)
+            .ok_or_else(|| anyhow!("Unknown sort key '{}'", self.sort))?;
+
+        if let Some(status) = &self.status {
+            match status.to_ascii_lowercase().strip_suffix("xx") {
+                Some(class) => {
+                    query.status_class = Some(
+                        class
+                            .parse()
+                            .ok()
+                            .filter(|c| (1..=5).contains(c))
+                            .ok_or_else(|| anyhow!("Invalid status class '{}'", status))?,
+                    )
+                }
+                None => {
+                    query.status = Some(
+                        status
+                            .parse()
+                            .map_err(|_| anyhow!("Invalid status '{}'", status))?,
+                    )
+                }
+            }
+        }
+        if let Some(since) = &self.since {
+            query.since = Some(parse_time(since, false)?);
+        }
+        if let Some(until) = &self.until {
+            query.until = Some(parse_time(until, true)?);
+        }
+        for header in &self.headers {
+            let (name, value) = match header.split_once(':') {
+                Some((name, value)) => (name.trim(), Some(value.trim())),
+                None => (header.trim(), None),
+            };
+            query = query.header(name, value);
+        }
+        Ok(query)
+    }
+}
+
+/// Parses an RFC 3339 time, a local `YYYY-MM-DD` date or Unix seconds. A
+/// date stands for its first second, or its last when `end_of_day` is set.
+fn parse_time(value: &str, end_of_day: bool) -> Result<u64> {
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Ok(seconds);
+    }
+    if let Ok(at) = chrono::DateTime::parse_from_rfc3339(value) {
+        return u64::try_from(at.timestamp())
+            .map_err(|_| anyhow!("Time '{}' is before 1970", value));
+    }
+    let date = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| {
+        anyhow!(
+            "Invalid time '{}'; use RFC 3339, YYYY-MM-DD or Unix seconds",
+            value
+        )
+    })?;
+    let time = if end_of_day {
+        chrono::NaiveTime::from_hms_opt(23, 59, 59)
+    } else {
+        chrono::NaiveTime::from_hms_opt(0, 0, 0)
+    }
+    .expect("valid time");
+    let at = date
+        .and_time(time)
+        .and_local_timezone(chrono::Local)
+        .earliest()
+        .ok_or_else(|| anyhow!("Time '{}' does not exist locally", value))?;
+    u64::try_from(at.timestamp()).map_err(|_| anyhow!("Time '{}' is before 1970", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_disable_retries() {
+        let mut client_config = ClientConfig::default();
+        ClientOptions::default().apply(&mut client_config).unwrap();
+        assert_eq!(client_config.max_retries, 0);
+        assert!(!client_config.retry_non_idempotent);
+    }
+
+    #[test]
+    fn test_retry_options() {
+        let options = ClientOptions {
+            retry: Some(4),
+            retry_delay: Some(250),
+            retry_on: vec![503, 429],
+            retry_all_methods: true,
+            ..ClientOptions::default()
+        };
+        let mut client_config = ClientConfig::default();
+        options.apply(&mut client_config).unwrap();
+        assert_eq!(client_config.max_retries, 4);
+        assert_eq!(client_config.retry_delay_ms, 250);
+        assert_eq!(client_config.retry_on_status, vec![503, 429]);
+        assert!(client_config.retry_non_idempotent);
+    }
+
+    #[test]
+    fn test_test_variables_precedence() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("vars.json");
+        std::fs::write(&file, r#"{"host": "file.test", "user": "file"}"#).unwrap();
+        let env = vec![
+            ("HURL_VAR_host".to_string(), "env.test".to_string()),
+            ("HURL_VAR_region".to_string(), "eu".to_string()),
+        ];
+
+        let options = TestOptions {
+            vars: vec!["user=cli".into()],
+            variables_file: vec![file],
+            ..TestOptions::default()
+        };
+        let variables = options.variables_with_env(env).unwrap();
+        assert_eq!(variables.get_string("region").as_deref(), Some("eu"));
+        assert_eq!(variables.get_string("host").as_deref(), Some("file.test"));
+        assert_eq!(variables.get_string("user").as_deref(), Some("cli"));
+
+        let bad = TestOptions {
+            vars: vec!["novalue".into()],
+            ..TestOptions::default()
+        };
+        assert!(bad.variables().is_err());
+    }
+
+    #[test]
+    fn test_report_options() {
+        let options = TestOptions {
+            reports: vec!["junit:out.xml".into(), "JSON:C:/out.json".into()],
+            report_html: Some("out.html".into()),
+            ..TestOptions::default()
+        };
+        assert_eq!(
+            options.reports().unwrap(),
+            vec![
+                (ReportFormat::Junit, PathBuf::from("out.xml")),
+                (ReportFormat::Json, PathBuf::from("C:/out.json")),
+                (ReportFormat::Html, PathBuf::from("out.html")),
+            ]
+        );
+
+        for bad in ["junit", "junit:", "yaml:out.yml"] {
+            let options = TestOptions {
+                reports: vec![bad.into()],
+                ..TestOptions::default()
+            };
+            assert!(options.reports().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_rate_options() {
+        let options = ClientOptions {
+            rate: vec!["50/s".into(), "api.example.com=5/s".into()],
+            ..ClientOptions::default()
+        };
+        let mut client_config = ClientConfig::default();
+        options.apply(&mut client_config).unwrap();
+        assert!(client_config.rate_limiter.is_some());
+
+        ClientOptions::default().apply(&mut client_config).unwrap();
+        assert!(client_config.rate_limiter.is_none());
+
+        for bad in ["fast", "api.example.com=", "=5/s", "5/fortnight"] {
+            let options = ClientOptions {
+                rate: vec![bad.into()],
+                ..ClientOptions::default()
+            };
+            assert!(
+                options.apply(&mut ClientConfig::default()).is_err(),
+                "{}",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn test_redirect_options() {
+        let options = ClientOptions {
+            location: true,
+            max_redirs: Some(12),
+            ..ClientOptions::default()
+        };
+        let mut client_config = ClientConfig {
+            follow_redirects: false,
+            ..ClientConfig::default()
+        };
+        options.apply(&mut client_config).unwrap();
+        assert!(client_config.follow_redirects);
+        assert_eq!(client_config.max_redirects, 12);
+    }
+
+    #[test]
+    fn test_digest_switches_auth_scheme() {
+        let request = RequestBuilder::get("http://a.test").basic_auth("user", "pass");
+        let options = ClientOptions {
+            digest: true,
+            ..ClientOptions::default()
+        };
+        match options.apply_auth(request.clone()).auth() {
+            Auth::Digest {
+                username, password, ..
+            } => assert_eq!((username.as_str(), password.as_str()), ("user", "pass")),
+            other => panic!("expected Digest auth, got {:?}", other),
+        }
+        assert!(matches!(
+            ClientOptions::default().apply_auth(request).auth(),
+            Auth::Basic { .. }
+        ));
+    }
+
+    #[test]
+    fn test_proxy_options() {
+        let options = ClientOptions {
+            proxy: Some("http://proxy.test:3128".to_string()),
+            proxy_user: Some("alice:pa:ss".to_string()),
+            noproxy: Some("localhost, 10.0.0.0/8".to_string()),
+            ..ClientOptions::default()
+        };
+        let mut client_config = ClientConfig::default();
+        options.apply(&mut client_config).unwrap();
+
+        let proxy = &client_config.proxy_config;
+        assert_eq!(proxy.url(), Some("http://proxy.test:3128"));
+        let auth = proxy.auth.as_ref().unwrap();
+        assert_eq!(auth.username, "alice");
+        assert_eq!(auth.password, "pa:ss");
+        assert_eq!(proxy.no_proxy, vec!["localhost", "10.0.0.0/8"]);
+    }
+
+    #[test]
+    fn test_proxy_user_requires_password() {
+        let options = ClientOptions {
+            proxy_user: Some("alice".to_string()),
+            ..ClientOptions::default()
+        };
+        assert!(options.apply(&mut ClientConfig::default()).is_err());
+    }
+
+    #[test]
+    fn test_tls_options() {
+        let options = ClientOptions {
+            cacert: Some(PathBuf::from("ca.pem")),
+            cert: Some(PathBuf::from("client.pem")),
+            pinnedpubkey: vec!["sha256//abc".to_string()],
+            tlsv1_3: true,
+            ..ClientOptions::default()
+        };
+        let mut client_config = ClientConfig::default();
+        options.apply(&mut client_config).unwrap();
+
+        let tls = &client_config.tls_config;
+        assert!(matches!(
+            &tls.validation,
+            CertificateValidation::Custom { ca_bundle } if ca_bundle == &PathBuf::from("ca.pem")
+        ));
+        assert_eq!(tls.client_key, Some(PathBuf::from("client.pem")));
+        assert_eq!(tls.cert_pinning, Some(vec!["sha256//abc".to_string()]));
+        assert_eq!(tls.min_tls_version, TlsVersion::TLS1_3);
+    }
+
+    #[test]
+    fn test_insecure_and_pkcs12() {
+        let options = ClientOptions {
+            insecure: true,
+            cacert: Some(PathBuf::from("ca.pem")),
+            cert: Some(PathBuf::from("client.p12")),
+            pass: Some("secret".to_string()),
+            ..ClientOptions::default()
+        };
+        let mut client_config = ClientConfig::default();
+        options.apply(&mut client_config).unwrap();
+
+        let tls = &client_config.tls_config;
+        assert!(matches!(tls.validation, CertificateValidation::Permissive));
+        assert!(tls.client_key.is_none());
+        assert_eq!(tls.client_cert_password.as_deref(), Some("secret"));
+        assert!(tls.validate().is_ok());
+    }
+
+    #[test]
+    fn test_invalid_retry_status() {
+        let options = ClientOptions {
+            retry_on: vec![42],
+            ..ClientOptions::default()
+        };
+        assert!(options.apply(&mut ClientConfig::default()).is_err());
+    }
+
+    #[test]
+    fn test_cookie_file_is_loaded_into_client_jar() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cookies.json");
+        let mut jar = hurl_lib::http::cookies::CookieJar::new();
+        jar.add(hurl_lib::http::cookies::Cookie::new("session", "abc").with_domain("a.test"));
+        CookiePersistence::new(&path).save(&jar).unwrap();
+
+        let options = ClientOptions {
+            cookie: Some(path),
+            ..ClientOptions::default()
+        };
+        let mut client_config = ClientConfig::default();
+        options.apply(&mut client_config).unwrap();
+        let loaded = client_config.cookie_jar.unwrap().snapshot();
+        assert_eq!(loaded.get("session").unwrap().value, "abc");
+    }
+
+    #[test]
+    fn test_history_filter_to_query() {
+        let filter = HistoryFilter {
+            status: Some("4XX".to_string()),
+            since: Some("1700000000".to_string()),
+            until: Some("2023-11-14T22:13:20Z".to_string()),
+            headers: vec!["X-Trace: abc".to_string(), "Cookie".to_string()],
+            sort: "duration".to_string(),
+            limit: Some(5),
+            ..Default::default()
+        };
+        let query = filter.to_query().unwrap();
+        assert_eq!(query.status_class, Some(4));
+        assert_eq!(query.status, None);
+        assert_eq!(query.since, Some(1_700_000_000));
+        assert_eq!(query.until, Some(1_700_000_000));
+        assert_eq!(query.headers[0].name, "X-Trace");
+        assert_eq!(query.headers[0].value.as_deref(), Some("abc"));
+        assert_eq!(query.headers[1].value, None);
+        assert_eq!(query.sort, SortKey::Duration);
+        assert!(query.descending);
+        assert_eq!(query.limit, Some(5));
+
+        let day = HistoryFilter {
+            since: Some("2024-03-01".to_string()),
+            until: Some("2024-03-01".to_string()),
+            sort: "timestamp".to_string(),
+            ..Default::default()
+        }
+        .to_query()
+        .unwrap();
+        assert_eq!(day.until.unwrap() - day.since.unwrap(), 86_399);
+
+        let invalid = |filter: HistoryFilter| filter.to_query().is_err();
+        let sorted = |sort: &str| HistoryFilter {
+            sort: sort.to_string(),
+            ..Default::default()
+        };
+        assert!(invalid(sorted("size")));
+        assert!(invalid(HistoryFilter {
+            status: Some("9xx".to_string()),
+            ..sorted("status")
+        }));
+        assert!(invalid(HistoryFilter {
+            since: Some("last tuesday".to_string()),
+            ..sorted("status")
+        }));
+    }
+}
@@ -1,13 +1,78 @@
 use anyhow::{anyhow, Result};
-use std::collections::HashMap;
+use hurl_lib::http::headers::Headers;
 
 #[derive(Debug, Clone)]
 pub struct RequestConfig {
     pub url: String,
-    pub headers: HashMap<String, String>,
+    pub headers: Headers,
     pub auth: Option<(String, String)>,
     pub timeout: Option<u64>,
     pub body: Option<String>,
+    pub form: Vec<FormField>,
+}
+
+/// One `-F` field: `name=value` or `name=@path[;type=mime][;filename=name]`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FormField {
+    Text {
+        name: String,
+        value: String,
+    },
+    File {
+        name: String,
+        path: String,
+        content_type: Option<String>,
+        filename: Option<String>,
+    },
+}
+
+impl FormField {
+    pub fn parse(field: &str) -> Result<Self> {
+        let (name, value) = field.split_once('=').ok_or_else(|| {
+            anyhow!(
+                "Invalid form field: '{}'. Expected 'name=value' or 'name=@file'",
+                field
+            )
+        })?;
+        let name = name.trim().to_string();
+        if name.is_empty() {
+            return Err(anyhow!("Form field name cannot be empty"));
+        }
+
+        let Some(spec) = value.strip_prefix('@') else {
+            return Ok(FormField::Text {
+                name,
+                value: value.to_string(),
+            });
+        };
+
+        let mut params = spec.split(';');
+        let path = params.next().unwrap_or_default().to_string();
+        if path.is_empty() {
+            return Err(anyhow!("Missing file path in form field '{}'", name));
+        }
+        let mut content_type = None;
+        let mut filename = None;
+        for param in params {
+            match param.split_once('=') {
+                Some(("type", v)) => content_type = Some(v.trim().to_string()),
+                Some(("filename", v)) => filename = Some(v.trim().trim_matches('"').to_string()),
+                _ => {
+                    return Err(anyhow!(
+                        "Unknown form field option '{}'. Expected 'type=' or 'filename='",
+                        param
+                    ))
+                }
+            }
+        }
+
+        Ok(FormField::File {
+            name,
+            path,
+            content_type,
+            filename,
+        })
+    }
 }
 
 impl RequestConfig {
@@ -27,8 +92,9 @@ impl RequestConfig {
             .map_err(|e| anyhow!("Invalid URL: {}", e))
     }
 
-    pub fn parse_headers(header_strings: Vec<String>) -> Result<HashMap<String, String>> {
-        let mut headers = HashMap::new();
+    /// Parses `-H` values in order; repeating a name sends it several times.
+    pub fn parse_headers(header_strings: Vec<String>) -> Result<Headers> {
+        let mut headers = Headers::new();
 
         for header in header_strings {
             let parts: Vec<&str> = header.splitn(2, ':').collect();
@@ -46,7 +112,7 @@ impl RequestConfig {
                 return Err(anyhow!("Header key cannot be empty"));
             }
 
-            headers.insert(key, value);
+            headers.append(key, value);
         }
 
         Ok(headers)
@@ -102,8 +168,17 @@ impl RequestConfig {
             auth,
             timeout,
             body,
+            form: Vec::new(),
         })
     }
+
+    pub fn with_form(mut self, fields: Vec<String>) -> Result<Self> {
+        self.form = fields
+            .iter()
+            .map(|f| FormField::parse(f))
+            .collect::<Result<_>>()?;
+        Ok(self)
+    }
 }
 
 #[cfg(test)]
@@ -200,4 +275,36 @@ mod tests {
         let timeout = RequestConfig::parse_timeout(None).unwrap();
         assert!(timeout.is_none());
     }
+
+    #[test]
+    fn test_parse_form_text_field() {
+        assert_eq!(
+            FormField::parse("title=a=b").unwrap(),
+            FormField::Text {
+                name: "title".to_string(),
+                value: "a=b".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_form_file_field() {
+        assert_eq!(
+            FormField::parse("file=@./photo.bin;type=image/png;filename=p.png").unwrap(),
+            FormField::File {
+                name: "file".to_string(),
+                path: "./photo.bin".to_string(),
+                content_type: Some("image/png".to_string()),
+                filename: Some("p.png".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_form_field_invalid() {
+        assert!(FormField::parse("novalue").is_err());
+        assert!(FormField::parse("=value").is_err());
+        assert!(FormField::parse("file=@").is_err());
+        assert!(FormField::parse("file=@a.txt;charset=utf8").is_err());
+    }
 }
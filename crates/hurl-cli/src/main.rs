@@ -26,6 +26,9 @@ struct Cli {
 
     #[arg(global = true, long, help = "Path to configuration file")]
     config: Option<PathBuf>,
+
+    #[command(flatten)]
+    client: cli::options::ClientOptions,
 }
 
 #[derive(Subcommand)]
@@ -59,6 +62,15 @@ enum Commands {
         #[arg(short = 'd', long, help = "Request body data")]
         data: Option<String>,
 
+        #[arg(
+            short = 'F',
+            long = "form",
+            value_name = "NAME=VALUE",
+            conflicts_with = "data",
+            help = "Add a multipart form field; use name=@path[;type=mime] to upload a file"
+        )]
+        form: Vec<String>,
+
         #[arg(short = 'u', long, help = "Set Basic authentication (user:password)")]
         auth: Option<String>,
 
@@ -80,6 +92,15 @@ enum Commands {
         #[arg(short = 'd', long, help = "Request body data")]
         data: Option<String>,
 
+        #[arg(
+            short = 'F',
+            long = "form",
+            value_name = "NAME=VALUE",
+            conflicts_with = "data",
+            help = "Add a multipart form field; use name=@path[;type=mime] to upload a file"
+        )]
+        form: Vec<String>,
+
         #[arg(short = 'u', long, help = "Set Basic authentication (user:password)")]
         auth: Option<String>,
 
@@ -119,6 +140,15 @@ enum Commands {
         #[arg(short = 'd', long, help = "Request body data")]
         data: Option<String>,
 
+        #[arg(
+            short = 'F',
+            long = "form",
+            value_name = "NAME=VALUE",
+            conflicts_with = "data",
+            help = "Add a multipart form field; use name=@path[;type=mime] to upload a file"
+        )]
+        form: Vec<String>,
+
         #[arg(short = 'u', long, help = "Set Basic authentication (user:password)")]
         auth: Option<String>,
 
@@ -161,13 +191,126 @@ enum Commands {
         #[arg(short = 'o', long, help = "Output response to file")]
         output: Option<PathBuf>,
     },
+
+    #[command(about = "Send every request in a JSONL file")]
+    Batch {
+        #[arg(value_name = "FILE", help = "One JSON request per line")]
+        file: PathBuf,
+
+        #[arg(
+            short = 'j',
+            long,
+            value_name = "N",
+            default_value_t = 10,
+            help = "Maximum requests in flight"
+        )]
+        concurrency: usize,
+
+        #[arg(
+            long,
+            value_name = "FILE",
+            help = "Write result lines to FILE instead of stdout"
+        )]
+        out: Option<PathBuf>,
+
+        #[arg(long, help = "Include response bodies in the results")]
+        include_body: bool,
+
+        #[arg(long, help = "Cancel the rest after a request gets no response")]
+        stop_on_error: bool,
+    },
+
+    #[command(about = "Run .hml test files")]
+    Test {
+        #[command(flatten)]
+        options: Box<cli::options::TestOptions>,
+    },
+
+    #[command(about = "Browse, replay and manage recorded requests")]
+    History {
+        #[command(subcommand)]
+        action: HistoryAction,
+    },
+
+    #[command(about = "Inspect or clear the response cache")]
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum HistoryAction {
+    #[command(about = "List recent requests, newest first")]
+    List {
+        #[arg(
+            short = 'n',
+            long,
+            default_value_t = 20,
+            help = "Number of entries to show"
+        )]
+        limit: usize,
+    },
+
+    #[command(about = "Show a recorded request and its response")]
+    Show {
+        #[arg(value_name = "ID", help = "Entry id or unique id prefix")]
+        id: String,
+    },
+
+    #[command(about = "Search recorded requests")]
+    Search {
+        #[command(flatten)]
+        filter: Box<cli::options::HistoryFilter>,
+    },
+
+    #[command(about = "Send a recorded request again")]
+    Replay {
+        #[arg(value_name = "ID", help = "Entry id or unique id prefix")]
+        id: String,
+
+        #[arg(long, help = "Compare the new response with the recorded one")]
+        diff: bool,
+    },
+
+    #[command(about = "Delete recorded requests")]
+    Delete {
+        #[arg(
+            value_name = "ID",
+            required_unless_present = "all",
+            help = "Entry ids or unique id prefixes"
+        )]
+        ids: Vec<String>,
+
+        #[arg(long, conflicts_with = "ids", help = "Delete the whole history")]
+        all: bool,
+    },
+
+    #[command(about = "Export the history as JSON")]
+    Export {
+        #[arg(short = 'o', long, help = "Write to a file instead of stdout")]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum CacheAction {
+    #[command(about = "Show cache size and hit statistics")]
+    Stats,
+
+    #[command(about = "Remove every cached response")]
+    Clear,
+
+    #[command(about = "List cached responses")]
+    Ls,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
 
-    let config = cli::config::Config::new(cli.verbose, cli.quiet, cli.config);
+    let config = cli::config::Config::new(cli.verbose, cli.quiet, cli.config)
+        .with_client_options(cli.client);
 
     if config.verbose {
         eprintln!("[VERBOSE] Starting HURL v{}", VERSION);
@@ -185,18 +328,20 @@ async fn main() -> Result<()> {
             url,
             header,
             data,
+            form,
             auth,
             timeout,
             output,
-        } => commands::handle_post(&url, header, data, auth, timeout, output, &config).await,
+        } => commands::handle_post(&url, header, data, form, auth, timeout, output, &config).await,
         Commands::Put {
             url,
             header,
             data,
+            form,
             auth,
             timeout,
             output,
-        } => commands::handle_put(&url, header, data, auth, timeout, output, &config).await,
+        } => commands::handle_put(&url, header, data, form, auth, timeout, output, &config).await,
         Commands::Delete {
             url,
             header,
@@ -208,10 +353,11 @@ async fn main() -> Result<()> {
             url,
             header,
             data,
+            form,
             auth,
             timeout,
             output,
-        } => commands::handle_patch(&url, header, data, auth, timeout, output, &config).await,
+        } => commands::handle_patch(&url, header, data, form, auth, timeout, output, &config).await,
         Commands::Head {
             url,
             header,
@@ -225,6 +371,41 @@ async fn main() -> Result<()> {
             timeout,
             output,
         } => commands::handle_options(&url, header, auth, timeout, output, &config).await,
+        Commands::Batch {
+            file,
+            concurrency,
+            out,
+            include_body,
+            stop_on_error,
+        } => {
+            commands::handle_batch(
+                &file,
+                concurrency,
+                out,
+                include_body,
+                stop_on_error,
+                &config,
+            )
+            .await
+        }
+        Commands::Test { options } => commands::handle_test(&options, &config).await,
+        Commands::History { action } => match action {
+            HistoryAction::List { limit } => commands::handle_history_list(limit, &config),
+            HistoryAction::Show { id } => commands::handle_history_show(&id, &config),
+            HistoryAction::Search { filter } => commands::handle_history_search(&filter, &config),
+            HistoryAction::Replay { id, diff } => {
+                commands::handle_history_replay(&id, diff, &config).await
+            }
+            HistoryAction::Delete { ids, all } => {
+                commands::handle_history_delete(ids, all, &config)
+            }
+            HistoryAction::Export { output } => commands::handle_history_export(output, &config),
+        },
+        Commands::Cache { action } => match action {
+            CacheAction::Stats => commands::handle_cache_stats(&config),
+            CacheAction::Clear => commands::handle_cache_clear(&config),
+            CacheAction::Ls => commands::handle_cache_ls(&config),
+        },
     };
 
     match result {
@@ -1,7 +1,54 @@
 use assert_cmd::Command;
 use predicates::prelude::*;
 use std::fs;
-use std::path::PathBuf;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::thread::JoinHandle;
+
+/// Serves canned HTTP responses, one connection each, and returns the raw requests.
+fn serve(responses: Vec<&'static str>) -> (String, JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let handle = std::thread::spawn(move || {
+        let mut requests = Vec::new();
+        for response in responses {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            loop {
+                let n = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request).to_string();
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let content_length = text[..end]
+                        .lines()
+                        .find_map(|l| {
+                            let (name, value) = l.split_once(':')?;
+                            name.eq_ignore_ascii_case("content-length")
+                                .then(|| value.trim().parse::<usize>().ok())?
+                        })
+                        .unwrap_or(0);
+                    if request.len() >= end + 4 + content_length {
+                        break;
+                    }
+                }
+                if n == 0 {
+                    break;
+                }
+            }
+            stream.write_all(response.as_bytes()).unwrap();
+            requests.push(String::from_utf8_lossy(&request).to_string());
+        }
+        requests
+    });
+    (url, handle)
+}
+
+fn serve_once(response: &'static str) -> (String, JoinHandle<String>) {
+    let (url, handle) = serve(vec![response]);
+    let handle = std::thread::spawn(move || handle.join().unwrap().remove(0));
+    (url, handle)
+}
 
 #[test]
 fn test_help_flag() {
@@ -253,3 +300,838 @@ fn test_get_with_verbose() {
         .arg("--quiet");
     cmd.assert().success();
 }
+
+#[test]
+fn test_local_get_prints_response() {
+    let (url, server) =
+        serve_once("HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 5\r\n\r\nhello");
+
+    let mut cmd = Command::cargo_bin("hurl").unwrap();
+    cmd.arg("get")
+        .arg(format!("{}/greeting", url))
+        .arg("-H")
+        .arg("X-Trace: abc");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("HTTP/1.1 200 OK"))
+        .stdout(predicate::str::contains("hello"));
+
+    let request = server.join().unwrap();
+    assert!(request.starts_with("GET /greeting HTTP/1.1"));
+    assert!(request.to_lowercase().contains("x-trace: abc"));
+}
+
+#[test]
+fn test_local_post_sends_body_and_auth() {
+    let (url, server) = serve_once("HTTP/1.1 201 Created\r\nContent-Length: 0\r\n\r\n");
+
+    let mut cmd = Command::cargo_bin("hurl").unwrap();
+    cmd.arg("post")
+        .arg(&url)
+        .arg("-d")
+        .arg("payload")
+        .arg("-u")
+        .arg("user:pass");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("201 Created"));
+
+    let request = server.join().unwrap();
+    assert!(request.starts_with("POST / HTTP/1.1"));
+    assert!(request.contains("authorization: Basic dXNlcjpwYXNz"));
+    assert!(request.ends_with("payload"));
+}
+
+#[test]
+fn test_local_get_writes_output_file() {
+    let (url, server) = serve_once("HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\nbody");
+    let temp_dir = tempfile::tempdir().unwrap();
+    let output_path = temp_dir.path().join("response.txt");
+
+    let mut cmd = Command::cargo_bin("hurl").unwrap();
+    cmd.arg("get").arg(&url).arg("-o").arg(&output_path);
+    cmd.assert().success();
+
+    server.join().unwrap();
+    assert_eq!(fs::read_to_string(&output_path).unwrap(), "body");
+}
+
+#[test]
+fn test_config_file_default_headers() {
+    let (url, server) = serve_once("HTTP/1.1 204 No Content\r\n\r\n");
+    let temp_dir = tempfile::tempdir().unwrap();
+    let config_path = temp_dir.path().join("hurl.json");
+    fs::write(
+        &config_path,
+        r#"{"timeout": 10, "follow_redirects": true, "verify_ssl": true, "proxy": null,
+            "user_agent": "hurl-ci", "headers": {"X-Team": "platform"}}"#,
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("hurl").unwrap();
+    cmd.arg("--config").arg(&config_path).arg("get").arg(&url);
+    cmd.assert().success();
+
+    let request = server.join().unwrap().to_lowercase();
+    assert!(request.contains("user-agent: hurl-ci"));
+    assert!(request.contains("x-team: platform"));
+}
+
+#[test]
+fn test_retry_on_service_unavailable() {
+    let (url, server) = serve(vec![
+        "HTTP/1.1 503 Service Unavailable\r\nConnection: close\r\nContent-Length: 0\r\n\r\n",
+        "HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 2\r\n\r\nok",
+    ]);
+
+    let mut cmd = Command::cargo_bin("hurl").unwrap();
+    cmd.arg("--verbose")
+        .arg("get")
+        .arg(&url)
+        .arg("--retry")
+        .arg("2")
+        .arg("--retry-delay")
+        .arg("1")
+        .arg("--retry-on")
+        .arg("503,429");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("200 OK"))
+        .stderr(predicate::str::contains("Completed after 2 attempts"));
+
+    assert_eq!(server.join().unwrap().len(), 2);
+}
+
+#[test]
+fn test_redirect_requires_location_flag() {
+    let (url, server) = serve_once(
+        "HTTP/1.1 302 Found\r\nLocation: /next\r\nConnection: close\r\nContent-Length: 0\r\n\r\n",
+    );
+
+    let mut cmd = Command::cargo_bin("hurl").unwrap();
+    cmd.arg("get").arg(&url);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("302 Found"));
+
+    server.join().unwrap();
+}
+
+#[test]
+fn test_follow_redirect_with_location_flag() {
+    let (url, server) = serve(vec![
+        "HTTP/1.1 301 Moved Permanently\r\nLocation: /moved\r\nConnection: close\r\nContent-Length: 0\r\n\r\n",
+        "HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 4\r\n\r\nhere",
+    ]);
+
+    let mut cmd = Command::cargo_bin("hurl").unwrap();
+    cmd.arg("--verbose").arg("get").arg(&url).arg("-L");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("here"))
+        .stderr(predicate::str::contains("301 /moved"));
+
+    let requests = server.join().unwrap();
+    assert!(requests[1].starts_with("GET /moved HTTP/1.1"));
+}
+
+#[test]
+fn test_proxy_flag_sends_credentials() {
+    let (proxy, server) = serve_once("HTTP/1.1 200 OK\r\nContent-Length: 7\r\n\r\nproxied");
+
+    let mut cmd = Command::cargo_bin("hurl").unwrap();
+    cmd.arg("get")
+        .arg("http://origin.test/resource")
+        .arg("-x")
+        .arg(&proxy)
+        .arg("--proxy-user")
+        .arg("alice:s3cret");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("proxied"));
+
+    let request = server.join().unwrap();
+    assert!(request.starts_with("GET http://origin.test/resource HTTP/1.1"));
+    assert!(request.contains("proxy-authorization: Basic YWxpY2U6czNjcmV0"));
+}
+
+#[test]
+fn test_env_proxy_and_noproxy() {
+    let (proxy, server) = serve_once("HTTP/1.1 200 OK\r\nContent-Length: 3\r\n\r\nenv");
+
+    let mut cmd = Command::cargo_bin("hurl").unwrap();
+    cmd.env("http_proxy", &proxy)
+        .arg("get")
+        .arg("http://origin.test/");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("env"));
+    assert!(server
+        .join()
+        .unwrap()
+        .starts_with("GET http://origin.test/ HTTP/1.1"));
+
+    let (url, direct) = serve_once("HTTP/1.1 200 OK\r\nContent-Length: 6\r\n\r\ndirect");
+    let mut cmd = Command::cargo_bin("hurl").unwrap();
+    cmd.env("http_proxy", "http://127.0.0.1:9")
+        .arg("get")
+        .arg(&url)
+        .arg("--noproxy")
+        .arg("127.0.0.0/8");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("direct"));
+    direct.join().unwrap();
+}
+
+#[test]
+fn test_digest_auth_handshake() {
+    let (url, server) = serve(vec![
+        "HTTP/1.1 401 Unauthorized\r\nWWW-Authenticate: Digest realm=\"api\", nonce=\"abc123\", qop=\"auth\"\r\nConnection: close\r\nContent-Length: 0\r\n\r\n",
+        "HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 7\r\n\r\nwelcome",
+    ]);
+
+    let mut cmd = Command::cargo_bin("hurl").unwrap();
+    cmd.arg("get")
+        .arg(format!("{}/secret", url))
+        .arg("-u")
+        .arg("user:pass")
+        .arg("--digest");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("welcome"));
+
+    let requests = server.join().unwrap();
+    assert!(!requests[0].to_lowercase().contains("authorization:"));
+    let authorization = requests[1]
+        .lines()
+        .find(|l| l.to_lowercase().starts_with("authorization:"))
+        .unwrap();
+    assert!(authorization.contains("Digest username=\"user\""));
+    assert!(authorization.contains("nonce=\"abc123\""));
+    assert!(authorization.contains("uri=\"/secret\""));
+    assert!(authorization.contains("nc=00000001"));
+}
+
+#[test]
+fn test_profile_oauth2_client_credentials() {
+    let (url, server) = serve(vec![
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nConnection: close\r\nContent-Length: 46\r\n\r\n{\"access_token\":\"tok-9\",\"token_type\":\"Bearer\"}",
+        "HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 6\r\n\r\nsecret",
+    ]);
+    let temp_dir = tempfile::tempdir().unwrap();
+    let config_path = temp_dir.path().join("hurl.json");
+    fs::write(
+        &config_path,
+        format!(
+            r#"{{"timeout": 10, "follow_redirects": true, "verify_ssl": true, "proxy": null,
+                "user_agent": null, "headers": {{}},
+                "profiles": {{"ci": {{"oauth2": {{"token_url": "{}/token",
+                    "client_id": "app", "client_secret": "s3cret"}}}}}}}}"#,
+            url
+        ),
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("hurl").unwrap();
+    cmd.arg("--config")
+        .arg(&config_path)
+        .arg("--profile")
+        .arg("ci")
+        .arg("get")
+        .arg(format!("{}/data", url));
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("secret"));
+
+    let requests = server.join().unwrap();
+    assert!(requests[0].starts_with("POST /token HTTP/1.1"));
+    assert!(requests[0].contains("grant_type=client_credentials"));
+    assert!(requests[1].contains("authorization: Bearer tok-9"));
+}
+
+#[test]
+fn test_post_multipart_form() {
+    let (url, server) = serve_once("HTTP/1.1 201 Created\r\nContent-Length: 8\r\n\r\nuploaded");
+    let temp_dir = tempfile::tempdir().unwrap();
+    let image = temp_dir.path().join("pixel.dat");
+    fs::write(&image, b"\x89PNG-bytes").unwrap();
+
+    let mut cmd = Command::cargo_bin("hurl").unwrap();
+    cmd.arg("post")
+        .arg(format!("{}/upload", url))
+        .arg("-F")
+        .arg("caption=hello world")
+        .arg("-F")
+        .arg(format!("file=@{};type=image/png", image.display()));
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("uploaded"));
+
+    let request = server.join().unwrap();
+    assert!(request.contains("content-type: multipart/form-data; boundary="));
+    assert!(request.contains("name=\"caption\"\r\n\r\nhello world\r\n"));
+    assert!(request
+        .contains("name=\"file\"; filename=\"pixel.dat\"\r\nContent-Type: image/png\r\n\r\n"));
+    assert!(request.contains("PNG-bytes"));
+}
+
+#[test]
+fn test_form_conflicts_with_data() {
+    let mut cmd = Command::cargo_bin("hurl").unwrap();
+    cmd.arg("post")
+        .arg("http://127.0.0.1:9/")
+        .arg("-d")
+        .arg("raw")
+        .arg("-F")
+        .arg("a=b");
+    cmd.assert().failure();
+}
+
+#[test]
+fn test_output_file_keeps_binary_body() {
+    let (url, server) = serve_once(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/octet-stream\r\nContent-Length: 6\r\n\r\n\u{0}\u{1}\u{2}\u{7f}ab",
+    );
+    let temp_dir = tempfile::tempdir().unwrap();
+    let output_path = temp_dir.path().join("blob.bin");
+
+    let mut cmd = Command::cargo_bin("hurl").unwrap();
+    cmd.arg("get")
+        .arg(format!("{}/blob", url))
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--quiet");
+    cmd.assert().success();
+    server.join().unwrap();
+
+    assert_eq!(fs::read(&output_path).unwrap(), b"\x00\x01\x02\x7fab");
+}
+
+#[test]
+fn test_stdout_keeps_binary_body() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let server = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut buf = [0u8; 4096];
+        let _ = stream.read(&mut buf).unwrap();
+        stream
+            .write_all(
+                b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\nConnection: close\r\n\r\n\xff\xfe\x00z",
+            )
+            .unwrap();
+    });
+
+    let mut cmd = Command::cargo_bin("hurl").unwrap();
+    cmd.arg("get").arg(format!("{}/blob", url));
+    let output = cmd.assert().success().get_output().stdout.clone();
+    server.join().unwrap();
+
+    assert!(output.ends_with(b"\n\n\xff\xfe\x00z"), "{:?}", output);
+}
+
+#[test]
+fn test_failed_request_leaves_no_output_file() {
+    let closed = {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        format!("http://{}/", listener.local_addr().unwrap())
+    };
+    let temp_dir = tempfile::tempdir().unwrap();
+    let output_path = temp_dir.path().join("out.bin");
+
+    let mut cmd = Command::cargo_bin("hurl").unwrap();
+    cmd.arg("get").arg(&closed).arg("-o").arg(&output_path);
+    cmd.assert().failure();
+    assert!(!output_path.exists());
+}
+
+#[test]
+fn test_cookie_jar_round_trip() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let jar_path = temp_dir.path().join("cookies.json");
+
+    let (url, server) = serve_once(
+        "HTTP/1.1 200 OK\r\nSet-Cookie: session=abc123; Path=/; Max-Age=3600\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+    );
+    let mut cmd = Command::cargo_bin("hurl").unwrap();
+    cmd.arg("get")
+        .arg(format!("{}/login", url))
+        .arg("-c")
+        .arg(&jar_path)
+        .arg("--quiet");
+    cmd.assert().success();
+    server.join().unwrap();
+
+    let saved = fs::read_to_string(&jar_path).unwrap();
+    assert!(saved.contains("session"));
+    assert!(saved.contains("abc123"));
+
+    let (url, server) =
+        serve_once("HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+    let mut cmd = Command::cargo_bin("hurl").unwrap();
+    cmd.arg("get")
+        .arg(format!("{}/profile", url))
+        .arg("-b")
+        .arg(&jar_path)
+        .arg("--quiet");
+    cmd.assert().success();
+
+    let request = server.join().unwrap().to_ascii_lowercase();
+    assert!(request.contains("cookie: session=abc123"));
+}
+
+#[test]
+fn test_cookie_jar_uses_netscape_format_for_curl() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let curl_jar = temp_dir.path().join("curl-cookies.txt");
+    fs::write(
+        &curl_jar,
+        "# Netscape HTTP Cookie File\n#HttpOnly_127.0.0.1\tFALSE\t/\tFALSE\t0\tsid\tfrom-curl\n",
+    )
+    .unwrap();
+    let out_jar = temp_dir.path().join("out.txt");
+
+    let (url, server) = serve_once(
+        "HTTP/1.1 200 OK\r\nSet-Cookie: theme=dark\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+    );
+    let mut cmd = Command::cargo_bin("hurl").unwrap();
+    cmd.arg("get")
+        .arg(format!("{}/", url))
+        .arg("-b")
+        .arg(&curl_jar)
+        .arg("-c")
+        .arg(&out_jar)
+        .arg("--quiet");
+    cmd.assert().success();
+
+    let request = server.join().unwrap().to_ascii_lowercase();
+    assert!(request.contains("sid=from-curl"));
+
+    let saved = fs::read_to_string(&out_jar).unwrap();
+    assert!(saved.starts_with("# Netscape HTTP Cookie File"));
+    assert!(saved.contains("#HttpOnly_127.0.0.1\tFALSE\t/\tFALSE\t0\tsid\tfrom-curl"));
+    assert!(saved.contains("127.0.0.1\tFALSE\t/\tFALSE\t0\ttheme\tdark"));
+}
+
+#[test]
+fn test_disk_cache_serves_repeat_requests_and_cache_subcommands() {
+    let cache_home = tempfile::tempdir().unwrap();
+    let (url, server) = serve_once(
+        "HTTP/1.1 200 OK\r\nCache-Control: max-age=600\r\nContent-Length: 6\r\nConnection: close\r\n\r\ncached",
+    );
+    let hurl = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("hurl").unwrap();
+        cmd.env("XDG_CACHE_HOME", cache_home.path()).args(args);
+        cmd
+    };
+
+    let page = format!("{}/page", url);
+    hurl(&["get", &page, "--cache"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("cached"));
+    server.join().unwrap();
+
+    // The server is gone, so this can only be answered from the cache.
+    hurl(&["get", &page, "--cache"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("cached"));
+
+    hurl(&["cache", "stats"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Entries:      1"))
+        .stdout(predicate::str::contains("Hits:         1"));
+    hurl(&["cache", "ls"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "200 GET {} 6 bytes, fresh",
+            page
+        )));
+    hurl(&["cache", "clear"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Removed 1 cached responses"));
+    hurl(&["cache", "stats"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Entries:      0"));
+}
+
+#[test]
+fn test_history_records_and_replays_requests() {
+    let data_home = tempfile::tempdir().unwrap();
+    let (url, server) = serve(vec![
+        "HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nfirst",
+        "HTTP/1.1 200 OK\r\nContent-Length: 6\r\nConnection: close\r\n\r\nsecond",
+    ]);
+    let hurl = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("hurl").unwrap();
+        cmd.env("XDG_DATA_HOME", data_home.path()).args(args);
+        cmd
+    };
+
+    let target = format!("{}/items?page=2", url);
+    hurl(&[
+        "get",
+        &target,
+        "-H",
+        "Authorization: Bearer secret",
+        "-H",
+        "X-Trace: abc",
+        "--tag",
+        "smoke",
+    ])
+    .assert()
+    .success();
+
+    let raw = fs::read_to_string(data_home.path().join("hurl/history.jsonl")).unwrap();
+    assert!(!raw.contains("secret"));
+
+    let listed = hurl(&["history", "list"]).output().unwrap();
+    let listed = String::from_utf8(listed.stdout).unwrap();
+    assert!(listed.contains(&format!("200 GET {}", target)));
+    assert!(listed.contains("[smoke]"));
+    let id = listed.split_whitespace().next().unwrap().to_string();
+
+    hurl(&["history", "search", "--tag", "smoke", "--status", "200"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(&id));
+    hurl(&["history", "search", "--status", "404"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_empty());
+    hurl(&[
+        "history",
+        "search",
+        "--text",
+        "FIRST",
+        "--status",
+        "2xx",
+        "--header",
+        "x-trace:abc",
+    ])
+    .assert()
+    .success()
+    .stdout(predicate::str::contains(&id));
+    hurl(&["history", "search", "--text", "missing"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_empty());
+    hurl(&["history", "show", &id])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("> Authorization: [REDACTED]"))
+        .stdout(predicate::str::contains("> X-Trace: abc"))
+        .stdout(predicate::str::contains("first"));
+
+    hurl(&["history", "replay", &id])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("second"));
+    let requests = server.join().unwrap();
+    let replayed = requests[1].to_ascii_lowercase();
+    assert!(replayed.starts_with("get /items?page=2 "));
+    assert!(replayed.contains("x-trace: abc"));
+    assert!(!replayed.contains("authorization"));
+
+    let exported = hurl(&["history", "export"]).output().unwrap();
+    let exported: serde_json::Value = serde_json::from_slice(&exported.stdout).unwrap();
+    assert_eq!(exported.as_array().unwrap().len(), 2);
+
+    hurl(&["history", "delete", &id])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Deleted 1 history entries"));
+    hurl(&["history", "delete", "--all"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Deleted 1 history entries"));
+    hurl(&["history", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_empty());
+}
+
+#[test]
+fn test_history_replay_diff() {
+    let data_home = tempfile::tempdir().unwrap();
+    let (url, server) = serve(vec![
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nX-Version: 1\r\nContent-Length: 25\r\nConnection: close\r\n\r\n{\"name\":\"ann\",\"tags\":[1]}",
+        "HTTP/1.1 500 Internal Server Error\r\nContent-Type: application/json\r\nX-Region: eu\r\nContent-Length: 27\r\nConnection: close\r\n\r\n{\"name\":\"bob\",\"tags\":[1,2]}",
+    ]);
+    let hurl = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("hurl").unwrap();
+        cmd.env("XDG_DATA_HOME", data_home.path()).args(args);
+        cmd
+    };
+
+    hurl(&["get", &url, "--quiet"]).assert().success();
+    let listed = hurl(&["history", "list"]).output().unwrap();
+    let listed = String::from_utf8(listed.stdout).unwrap();
+    let id = listed.split_whitespace().next().unwrap().to_string();
+
+    hurl(&["history", "replay", &id, "--diff"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Status: 200 OK -> 500 Internal Server Error",
+        ))
+        .stdout(predicate::str::contains("Latency: "))
+        .stdout(predicate::str::contains("- x-version: 1"))
+        .stdout(predicate::str::contains("+ x-region: eu"))
+        .stdout(predicate::str::contains("~ content-length: 25 -> 27"))
+        .stdout(predicate::str::contains("~ $.name: \"ann\" -> \"bob\""))
+        .stdout(predicate::str::contains("+ $.tags[1]: 2"));
+    server.join().unwrap();
+}
+
+#[test]
+fn test_batch_runs_jsonl_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let (url, server) = serve(vec![
+        "HTTP/1.1 201 Created\r\nContent-Length: 4\r\nConnection: close\r\n\r\nmade",
+        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+    ]);
+    let input = dir.path().join("requests.jsonl");
+    fs::write(
+        &input,
+        format!(
+            "{{\"id\": \"create\", \"method\": \"POST\", \"url\": \"{url}/users\", \"headers\": {{\"X-Team\": \"core\"}}, \"body\": {{\"name\": \"Ann\"}}}}\n\
+             {{\"url\": \"{url}/missing\"}}\n"
+        ),
+    )
+    .unwrap();
+    let out = dir.path().join("results.jsonl");
+    let config_path = dir.path().join("hurl.json");
+    fs::write(
+        &config_path,
+        r#"{"timeout": 10, "follow_redirects": true, "verify_ssl": true, "proxy": null,
+            "user_agent": "hurl-ci", "headers": {"X-Team": "platform"}}"#,
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("hurl").unwrap();
+    cmd.env("XDG_DATA_HOME", dir.path())
+        .arg("--config")
+        .arg(&config_path)
+        .arg("batch")
+        .arg(&input)
+        .args([
+            "--concurrency",
+            "1",
+            "--include-body",
+            "--tag",
+            "nightly",
+            "--out",
+        ])
+        .arg(&out)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Requests:  2"))
+        .stdout(predicate::str::contains("Succeeded: 1 (50.0%)"))
+        .stdout(predicate::str::contains("p95"));
+
+    let results: Vec<serde_json::Value> = fs::read_to_string(&out)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0]["id"], "create");
+    assert_eq!(results[0]["status"], 201);
+    assert_eq!(results[0]["body"], "made");
+    assert_eq!(results[1]["id"], "2");
+    assert_eq!(results[1]["status"], 404);
+    assert!(results[1]["duration_ms"].is_u64());
+
+    let requests = server.join().unwrap();
+    assert!(requests[0].starts_with("POST /users "));
+    assert!(requests[0].to_ascii_lowercase().contains("x-team: core"));
+    assert!(!requests[0]
+        .to_ascii_lowercase()
+        .contains("x-team: platform"));
+    assert!(requests[0].ends_with("{\"name\":\"Ann\"}"));
+    assert!(requests[1]
+        .to_ascii_lowercase()
+        .contains("x-team: platform"));
+    assert!(requests[1]
+        .to_ascii_lowercase()
+        .contains("user-agent: hurl-ci"));
+
+    let history = fs::read_to_string(dir.path().join("hurl/history.jsonl")).unwrap();
+    let entries: Vec<serde_json::Value> = history
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(entries.len(), 2);
+    assert!(entries[0]["url"].as_str().unwrap().ends_with("/users"));
+    assert_eq!(entries[1]["status"], 404);
+    assert!(entries
+        .iter()
+        .all(|e| e["tags"] == serde_json::json!(["nightly"])));
+}
+
+#[test]
+fn test_batch_reports_bad_lines_and_failures() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("bad.jsonl");
+    fs::write(&input, "{\"method\": \"GET\"}\n").unwrap();
+    let mut cmd = Command::cargo_bin("hurl").unwrap();
+    cmd.arg("batch")
+        .arg(&input)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("line 1"));
+
+    let closed = {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        format!("http://{}/", listener.local_addr().unwrap())
+    };
+    fs::write(&input, format!("{{\"url\": \"{closed}\"}}\n")).unwrap();
+    let mut cmd = Command::cargo_bin("hurl").unwrap();
+    cmd.arg("batch")
+        .arg(&input)
+        .args(["--retry", "0"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("\"status\":null"))
+        .stderr(predicate::str::contains("1 of 1 requests failed"));
+}
+
+#[test]
+fn test_no_history_flag_skips_recording() {
+    let data_home = tempfile::tempdir().unwrap();
+    let (url, server) =
+        serve_once("HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+    let mut cmd = Command::cargo_bin("hurl").unwrap();
+    cmd.env("XDG_DATA_HOME", data_home.path())
+        .arg("get")
+        .arg(&url)
+        .arg("--no-history")
+        .arg("--quiet");
+    cmd.assert().success();
+    server.join().unwrap();
+
+    assert!(!data_home.path().join("hurl/history.jsonl").exists());
+}
+
+#[test]
+fn test_test_command_runs_hml_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let (url, server) = serve(vec![
+        "HTTP/1.1 200 OK\r\nX-Token: t0k\r\nContent-Length: 9\r\nConnection: close\r\n\r\n{\"id\": 7}",
+        "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nhi",
+    ]);
+    fs::create_dir(dir.path().join("suite")).unwrap();
+    fs::write(
+        dir.path().join("suite/login.hml"),
+        "@test Login\n\
+         POST {{base}}/login\n\
+         {\"user\": \"{{user}}\"}\n\
+         capture token: header X-Token\n\
+         capture id: jsonpath $.id\n\
+         assert_status: 200\n\
+         \n\
+         @test Profile\n\
+         GET {{base}}/users/{{id}}\n\
+         Authorization: Bearer {{token}}\n\
+         assert_body: hi\n",
+    )
+    .unwrap();
+    fs::write(dir.path().join("suite/notes.txt"), "not a test").unwrap();
+    let vars = dir.path().join("vars.env");
+    fs::write(&vars, "user = ann\n").unwrap();
+    let report = dir.path().join("report.html");
+    let junit = dir.path().join("junit.xml");
+    let json = dir.path().join("report.json");
+
+    let mut cmd = Command::cargo_bin("hurl").unwrap();
+    cmd.env("HURL_VAR_base", &url)
+        .arg("test")
+        .arg(dir.path().join("suite"))
+        .arg("--variables-file")
+        .arg(&vars)
+        .arg("--report-html")
+        .arg(&report)
+        .arg("--report")
+        .arg(format!("junit:{}", junit.display()))
+        .arg("--report")
+        .arg(format!("json:{}", json.display()))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("✓ Login"))
+        .stdout(predicate::str::contains("✓ Profile"))
+        .stdout(predicate::str::contains("2 passed, 0 failed"));
+
+    assert!(fs::read_to_string(&report).unwrap().contains("Profile"));
+    let xml = fs::read_to_string(&junit).unwrap();
+    assert!(xml.contains("tests=\"2\" failures=\"0\" errors=\"0\""));
+    assert!(xml.contains("<testcase name=\"Login\" classname=\"hurl test\""));
+    let results: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&json).unwrap()).unwrap();
+    assert_eq!(results["summary"]["passed"], 2);
+    assert_eq!(results["tests"][1]["name"], "Profile");
+    let requests = server.join().unwrap();
+    assert!(requests[0].starts_with("POST /login "));
+    assert!(requests[0].ends_with("{\"user\": \"ann\"}"));
+    assert!(requests[1].starts_with("GET /users/7 "));
+    assert!(requests[1]
+        .to_ascii_lowercase()
+        .contains("authorization: bearer t0k"));
+}
+
+#[test]
+fn test_test_command_fails_on_assertion_failure() {
+    let dir = tempfile::tempdir().unwrap();
+    let (url, server) = serve_once(
+        "HTTP/1.1 500 Internal Server Error\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+    );
+    let file = dir.path().join("smoke.hml");
+    fs::write(
+        &file,
+        "@test Health\n@tag: smoke\nGET {{base}}/health\nassert_status: 200\n\n\
+         @test Other\n@tag: smoke\nGET {{base}}/other\nassert_status: 200\n\n\
+         @test Untagged\nGET {{base}}/untagged\nassert_status: 200\n",
+    )
+    .unwrap();
+    let junit = dir.path().join("junit.xml");
+
+    let mut cmd = Command::cargo_bin("hurl").unwrap();
+    cmd.arg("test")
+        .arg(&file)
+        .args(["--tag", "smoke", "--fail-fast", "--var"])
+        .arg(format!("base={}", url))
+        .arg("--report")
+        .arg(format!("junit:{}", junit.display()))
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("✗ Health"))
+        .stdout(predicate::str::contains("Expected: 200"))
+        .stdout(predicate::str::contains("0 passed, 1 failed, 1 skipped"))
+        .stderr(predicate::str::contains("1 of 1 tests failed"));
+    assert!(server.join().unwrap().starts_with("GET /health "));
+    let xml = fs::read_to_string(&junit).unwrap();
+    assert!(xml.contains("tests=\"2\" failures=\"1\" errors=\"0\" skipped=\"1\""));
+    assert!(xml.contains("<testcase name=\"Other\""));
+    assert!(xml.contains("<skipped/>"));
+
+    let mut cmd = Command::cargo_bin("hurl").unwrap();
+    cmd.arg("test")
+        .arg(&file)
+        .args(["--report", "yaml:out.yml"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown report format 'yaml'"));
+
+    let mut cmd = Command::cargo_bin("hurl").unwrap();
+    cmd.arg("test")
+        .arg(dir.path().join("*.txt"))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No .hml files match"));
+}
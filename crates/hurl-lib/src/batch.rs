@@ -1,6 +1,14 @@
 use std::collections::HashMap;
-use std::time::Instant;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use crate::http::client::HttpClient;
+use crate::http::headers::Headers;
 use crate::http::request::RequestBuilder;
 use crate::http::response::HttpResponse;
 use crate::Result;
@@ -27,12 +35,108 @@ impl BatchRequest {
     }
 }
 
+/// One line of a JSONL batch file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BatchLine {
+    id: Option<String>,
+    #[serde(default = "default_method")]
+    method: String,
+    url: String,
+    #[serde(default)]
+    headers: Headers,
+    body: Option<Value>,
+    #[serde(default)]
+    metadata: HashMap<String, Value>,
+}
+
+fn default_method() -> String {
+    "GET".to_string()
+}
+
+/// Parses a JSONL batch file, one request per line:
+///
+/// ```text
+/// {"id": "user", "method": "POST", "url": "https://api.example.com/users",
+///  "headers": {"Accept": "application/json"}, "body": {"name": "Ann"},
+///  "metadata": {"team": "core"}}
+/// ```
+///
+/// Only `url` is required. `method` defaults to GET and `id` to the line
+/// number. `headers` is an object or a list of `[name, value]` pairs. A
+/// string `body` is sent as text, any other JSON value as JSON. Blank lines
+/// are skipped.
+pub fn parse_jsonl(input: &str) -> Result<Vec<BatchRequest>> {
+    let mut requests = Vec::new();
+    for (index, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let number = index + 1;
+        let line: BatchLine = serde_json::from_str(line)
+            .map_err(|e| crate::Error::Parse(format!("line {}: {}", number, e)))?;
+
+        let mut request = RequestBuilder::new(line.url, line.method.to_ascii_uppercase());
+        for (name, value) in &line.headers {
+            request = request.append_header(name, value);
+        }
+        request = match line.body {
+            None => request,
+            Some(Value::String(text)) => request.text_body(text),
+            Some(value) => request.json_body(value),
+        };
+        request
+            .validate()
+            .map_err(|e| crate::Error::Parse(format!("line {}: {}", number, e)))?;
+
+        let metadata = line
+            .metadata
+            .into_iter()
+            .map(|(key, value)| match value {
+                Value::String(text) => (key, text),
+                other => (key, other.to_string()),
+            })
+            .collect();
+        requests.push(BatchRequest {
+            id: line.id.unwrap_or_else(|| number.to_string()),
+            request,
+            metadata,
+        });
+    }
+    Ok(requests)
+}
+
 #[derive(Debug, Clone)]
 pub struct BatchResponse {
     pub id: String,
+    /// Position of the request in the batch. Ids may repeat and cancelled
+    /// requests leave gaps, so this is how a response finds its request.
+    pub index: usize,
     pub response: HttpResponse,
     pub success: bool,
     pub error: Option<String>,
+    /// Time from sending the request to receiving the full response, not
+    /// counting time spent waiting for a free slot.
+    pub duration: Duration,
+}
+
+impl BatchResponse {
+    /// One line of batch output: `id`, `status` (null when no response
+    /// arrived), `success`, `duration_ms`, `error` and, if asked for, the
+    /// response `body`.
+    pub fn to_json(&self, include_body: bool) -> Value {
+        let mut line = json!({
+            "id": self.id,
+            "status": self.error.is_none().then_some(self.response.status),
+            "success": self.success,
+            "duration_ms": self.duration.as_millis() as u64,
+            "error": self.error,
+        });
+        if include_body && self.error.is_none() {
+            line["body"] = Value::String(self.response.text());
+        }
+        line
+    }
 }
 
 #[derive(Debug)]
@@ -66,46 +170,88 @@ impl BatchExecutor {
         self
     }
 
-    pub fn execute<F>(&self, client_fn: F) -> Result<BatchResult>
-    where
-        F: Fn(RequestBuilder) -> Result<HttpResponse>,
-    {
+    /// Sends every request through `client`, with at most `max_concurrent`
+    /// in flight. Requests are started in the order they were added and
+    /// responses are returned in that order.
+    ///
+    /// With `stop_on_error`, the first request that fails without a
+    /// response aborts those still in flight and skips the rest; they are
+    /// counted in `BatchStats::cancelled` and have no response.
+    pub async fn execute(&self, client: &HttpClient) -> Result<BatchResult> {
         let start = Instant::now();
-        let mut responses = Vec::new();
+        let semaphore = Arc::new(Semaphore::new(self.max_concurrent.max(1)));
+        let mut slots: Vec<Option<BatchResponse>> = vec![None; self.requests.len()];
         let mut stats = BatchStats::default();
+        let mut tasks: JoinSet<(usize, Result<HttpResponse>, Duration)> = JoinSet::new();
+        let mut pending = self.requests.iter().enumerate().peekable();
 
-        for batch_req in &self.requests {
-            match client_fn(batch_req.request.clone()) {
-                Ok(response) => {
-                    let success = response.is_success();
-                    responses.push(BatchResponse {
-                        id: batch_req.id.clone(),
-                        response: response.clone(),
-                        success,
-                        error: None,
+        while pending.peek().is_some() || !tasks.is_empty() {
+            tokio::select! {
+                biased;
+
+                Some(joined) = tasks.join_next(), if !tasks.is_empty() => {
+                    let (index, result, duration) = match joined {
+                        Ok(done) => done,
+                        Err(e) if e.is_cancelled() => {
+                            stats.cancelled += 1;
+                            continue;
+                        }
+                        Err(e) => std::panic::resume_unwind(e.into_panic()),
+                    };
+                    let id = self.requests[index].id.clone();
+                    stats.latencies.push(duration);
+                    slots[index] = Some(match result {
+                        Ok(response) => {
+                            let success = response.is_success();
+                            stats.successful += 1;
+                            if success {
+                                stats.succeeded += 1;
+                            }
+                            BatchResponse {
+                                id,
+                                index,
+                                response,
+                                success,
+                                error: None,
+                                duration,
+                            }
+                        }
+                        Err(e) => {
+                            stats.failed += 1;
+                            if self.stop_on_error {
+                                tasks.abort_all();
+                                stats.cancelled += pending.by_ref().count();
+                            }
+                            BatchResponse {
+                                id,
+                                index,
+                                response: HttpResponse::new(0, HashMap::new(), "".to_string()),
+                                success: false,
+                                error: Some(e.to_string()),
+                                duration,
+                            }
+                        }
                     });
-                    stats.successful += 1;
-                    if success {
-                        stats.succeeded += 1;
-                    }
                 }
-                Err(e) => {
-                    responses.push(BatchResponse {
-                        id: batch_req.id.clone(),
-                        response: HttpResponse::new(0, HashMap::new(), "".to_string()),
-                        success: false,
-                        error: Some(e.to_string()),
-                    });
-                    stats.failed += 1;
 
-                    if self.stop_on_error {
-                        break;
-                    }
+                permit = semaphore.clone().acquire_owned(), if pending.peek().is_some() => {
+                    let permit = permit.expect("semaphore is never closed");
+                    let (index, batch_req) = pending.next().expect("peeked");
+                    let client = client.clone();
+                    let request = batch_req.request.clone();
+                    tasks.spawn(async move {
+                        let _permit = permit;
+                        let sent = Instant::now();
+                        let result = client.execute(request).await;
+                        (index, result, sent.elapsed())
+                    });
                 }
             }
         }
 
+        stats.latencies.sort();
         stats.duration = start.elapsed();
+        let responses: Vec<BatchResponse> = slots.into_iter().flatten().collect();
         stats.total = responses.len();
 
         Ok(BatchResult { responses, stats })
@@ -114,6 +260,10 @@ impl BatchExecutor {
     pub fn request_count(&self) -> usize {
         self.requests.len()
     }
+
+    pub fn max_concurrent(&self) -> usize {
+        self.max_concurrent
+    }
 }
 
 impl Default for BatchExecutor {
@@ -128,7 +278,12 @@ pub struct BatchStats {
     pub succeeded: usize,
     pub successful: usize,
     pub failed: usize,
+    /// Requests skipped or aborted because of `stop_on_error`.
+    pub cancelled: usize,
+    /// Wall-clock time of the whole batch.
     pub duration: std::time::Duration,
+    /// Per-request durations, sorted ascending.
+    pub latencies: Vec<Duration>,
 }
 
 impl Default for BatchStats {
@@ -138,7 +293,9 @@ impl Default for BatchStats {
             succeeded: 0,
             successful: 0,
             failed: 0,
+            cancelled: 0,
             duration: std::time::Duration::from_secs(0),
+            latencies: Vec::new(),
         }
     }
 }
@@ -167,6 +324,23 @@ impl BatchStats {
             self.duration / self.total as u32
         }
     }
+
+    pub fn mean_latency(&self) -> Duration {
+        if self.latencies.is_empty() {
+            Duration::from_secs(0)
+        } else {
+            self.latencies.iter().sum::<Duration>() / self.latencies.len() as u32
+        }
+    }
+
+    /// Nearest-rank percentile of the request latencies, `p` in 0..=100.
+    pub fn percentile(&self, p: f64) -> Duration {
+        if self.latencies.is_empty() {
+            return Duration::from_secs(0);
+        }
+        let rank = (p.clamp(0.0, 100.0) / 100.0 * self.latencies.len() as f64).ceil() as usize;
+        self.latencies[rank.saturating_sub(1)]
+    }
 }
 
 #[derive(Debug)]
@@ -188,3 +362,178 @@ impl BatchResult {
         self.responses.iter().filter(|r| !r.success).collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::client::ClientConfig;
+    use crate::http::test_server::{MockResponse, TestServer};
+
+    fn batch(server: &TestServer, count: usize) -> Vec<BatchRequest> {
+        (0..count)
+            .map(|i| {
+                let url = server.url(&format!("/{}", i));
+                BatchRequest::new(&format!("req_{}", i), RequestBuilder::get(url))
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_is_bounded_and_order_kept() {
+        let server = TestServer::start(|req, n| {
+            // Later requests answer sooner, so completion order differs
+            // from submission order.
+            MockResponse::ok(req.path.clone()).delay(Duration::from_millis(300 - 40 * n as u64))
+        })
+        .await;
+        let executor = BatchExecutor::new(2).add_requests(batch(&server, 5));
+        let client = HttpClient::new().unwrap();
+
+        let running = tokio::spawn(async move { executor.execute(&client).await });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(server.request_count(), 2);
+
+        let result = running.await.unwrap().unwrap();
+        let ids: Vec<&str> = result.responses.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, ["req_0", "req_1", "req_2", "req_3", "req_4"]);
+        assert_eq!(result.responses[3].response.text(), "/3");
+        assert_eq!(result.stats.total, 5);
+        assert_eq!(result.stats.succeeded, 5);
+        assert_eq!(result.stats.latencies.len(), 5);
+        assert!(result.stats.latencies.windows(2).all(|w| w[0] <= w[1]));
+        assert!(result.stats.percentile(100.0) >= Duration::from_millis(250));
+    }
+
+    #[tokio::test]
+    async fn test_stop_on_error_cancels_outstanding_requests() {
+        let server =
+            TestServer::start(|_, _| MockResponse::ok("slow").delay(Duration::from_millis(500)))
+                .await;
+        let closed = {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            format!("http://{}/", listener.local_addr().unwrap())
+        };
+
+        let mut requests = batch(&server, 3);
+        requests.insert(1, BatchRequest::new("broken", RequestBuilder::get(closed)));
+        let executor = BatchExecutor::new(2)
+            .add_requests(requests)
+            .with_stop_on_error(true);
+
+        let client = HttpClient::with_config(ClientConfig {
+            max_retries: 0,
+            ..ClientConfig::default()
+        })
+        .unwrap();
+        let result = executor.execute(&client).await.unwrap();
+        assert_eq!(result.stats.failed, 1);
+        assert_eq!(result.stats.cancelled, 3);
+        assert_eq!(result.stats.total, 1);
+        assert_eq!(result.responses[0].id, "broken");
+        assert_eq!(result.responses[0].index, 1);
+        assert!(result.responses[0].error.is_some());
+        assert!(result.stats.duration < Duration::from_millis(500));
+    }
+
+    #[tokio::test]
+    async fn test_errors_are_collected_without_stop_on_error() {
+        let server = TestServer::start(|req, _| match req.path.as_str() {
+            "/1" => MockResponse::new(500),
+            _ => MockResponse::ok("fine"),
+        })
+        .await;
+        let executor = BatchExecutor::new(4).add_requests(batch(&server, 3));
+
+        let result = executor.execute(&HttpClient::new().unwrap()).await.unwrap();
+        assert_eq!(result.stats.total, 3);
+        assert_eq!(result.stats.successful, 3);
+        assert_eq!(result.stats.succeeded, 2);
+        assert_eq!(result.get_all_failed()[0].id, "req_1");
+    }
+
+    #[test]
+    fn test_parse_jsonl() {
+        let input = r#"{"id": "create", "method": "post", "url": "https://example.com/users", "headers": {"Accept": "application/json"}, "body": {"name": "Ann"}, "metadata": {"team": "core", "priority": 1}}
+
+{"url": "https://example.com/ping", "headers": [["X-Tag", "a"], ["X-Tag", "b"]], "body": "hello"}
+"#;
+        let requests = parse_jsonl(input).unwrap();
+        assert_eq!(requests.len(), 2);
+
+        let create = &requests[0];
+        assert_eq!(create.id, "create");
+        assert_eq!(create.request.method(), "POST");
+        assert_eq!(
+            create.request.headers().get("accept"),
+            Some("application/json")
+        );
+        assert_eq!(
+            create.request.body().as_ref().unwrap().to_bytes().unwrap(),
+            br#"{"name":"Ann"}"#.as_slice()
+        );
+        assert_eq!(create.metadata["team"], "core");
+        assert_eq!(create.metadata["priority"], "1");
+
+        let ping = &requests[1];
+        assert_eq!(ping.id, "3");
+        assert_eq!(ping.request.method(), "GET");
+        assert_eq!(ping.request.headers().get_all("X-Tag"), ["a", "b"]);
+        assert_eq!(
+            ping.request.body().as_ref().unwrap().to_bytes().unwrap(),
+            b"hello".as_slice()
+        );
+    }
+
+    #[test]
+    fn test_parse_jsonl_reports_line_numbers() {
+        let missing_url = parse_jsonl("{\"url\": \"https://a.test\"}\n{\"method\": \"GET\"}");
+        assert!(missing_url.unwrap_err().to_string().contains("line 2"));
+
+        let unknown = parse_jsonl("{\"url\": \"https://a.test\", \"headrs\": {}}");
+        assert!(unknown.unwrap_err().to_string().contains("line 1"));
+
+        let bad_url = parse_jsonl("{\"url\": \"not a url\"}");
+        assert!(bad_url.is_err());
+    }
+
+    #[test]
+    fn test_response_to_json() {
+        let ok = BatchResponse {
+            id: "a".to_string(),
+            index: 0,
+            response: HttpResponse::new(201, HashMap::new(), "made".to_string()),
+            success: true,
+            error: None,
+            duration: Duration::from_millis(42),
+        };
+        assert_eq!(
+            ok.to_json(true),
+            json!({"id": "a", "status": 201, "success": true, "duration_ms": 42, "error": null, "body": "made"})
+        );
+        assert!(ok.to_json(false).get("body").is_none());
+
+        let failed = BatchResponse {
+            error: Some("refused".to_string()),
+            success: false,
+            ..ok
+        };
+        assert_eq!(failed.to_json(true)["status"], Value::Null);
+        assert!(failed.to_json(true).get("body").is_none());
+    }
+
+    #[test]
+    fn test_latency_percentiles() {
+        let stats = BatchStats {
+            latencies: (1..=10).map(Duration::from_millis).collect(),
+            ..Default::default()
+        };
+        assert_eq!(stats.percentile(50.0), Duration::from_millis(5));
+        assert_eq!(stats.percentile(95.0), Duration::from_millis(10));
+        assert_eq!(stats.percentile(0.0), Duration::from_millis(1));
+        assert_eq!(stats.mean_latency(), Duration::from_micros(5500));
+        assert_eq!(
+            BatchStats::default().percentile(99.0),
+            Duration::from_secs(0)
+        );
+    }
+}
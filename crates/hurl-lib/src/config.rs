@@ -1,4 +1,6 @@
+use crate::http::oauth2::OAuth2Config;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -9,6 +11,15 @@ pub struct Config {
     pub proxy: Option<String>,
     pub user_agent: Option<String>,
     pub headers: std::collections::HashMap<String, String>,
+    #[serde(default)]
+    pub profiles: HashMap<String, Profile>,
+}
+
+/// Named per-environment settings selected with `--profile`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Profile {
+    #[serde(default)]
+    pub oauth2: Option<OAuth2Config>,
 }
 
 impl Default for Config {
@@ -20,6 +31,7 @@ impl Default for Config {
             proxy: None,
             user_agent: None,
             headers: std::collections::HashMap::new(),
+            profiles: HashMap::new(),
         }
     }
 }
@@ -31,9 +43,47 @@ impl Config {
         Ok(config)
     }
 
+    pub fn profile(&self, name: &str) -> crate::Result<&Profile> {
+        self.profiles
+            .get(name)
+            .ok_or_else(|| crate::Error::Config(format!("unknown profile '{}'", name)))
+    }
+
     pub fn to_file(&self, path: PathBuf) -> crate::Result<()> {
         let content = serde_json::to_string_pretty(self)?;
         std::fs::write(path, content)?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::oauth2::OAuth2GrantType;
+
+    #[test]
+    fn test_profiles_are_optional() {
+        let config: Config = serde_json::from_str(
+            r#"{"timeout": 5, "follow_redirects": true, "verify_ssl": true,
+                "proxy": null, "user_agent": null, "headers": {}}"#,
+        )
+        .unwrap();
+        assert!(config.profiles.is_empty());
+        assert!(config.profile("staging").is_err());
+    }
+
+    #[test]
+    fn test_oauth2_profile() {
+        let config: Config = serde_json::from_str(
+            r#"{"timeout": 5, "follow_redirects": true, "verify_ssl": true,
+                "proxy": null, "user_agent": null, "headers": {},
+                "profiles": {"staging": {"oauth2": {
+                    "token_url": "https://idp.test/token",
+                    "client_id": "app", "client_secret": "s3cret", "scope": "read"}}}}"#,
+        )
+        .unwrap();
+        let oauth2 = config.profile("staging").unwrap().oauth2.as_ref().unwrap();
+        assert_eq!(oauth2.grant, OAuth2GrantType::ClientCredentials);
+        assert_eq!(oauth2.scope.as_deref(), Some("read"));
+    }
+}
@@ -1,8 +1,6 @@
 use base64::{engine::general_purpose::STANDARD, Engine};
-use md5;
 
-#[derive(Debug, Clone)]
-#[derive(Default)]
+#[derive(Debug, Clone, Default)]
 pub enum Auth {
     #[default]
     None,
@@ -143,6 +141,7 @@ impl Auth {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn compute_digest_response(
         username: &str,
         password: &str,
@@ -154,23 +153,19 @@ impl Auth {
         nc: u32,
         cnonce: Option<&str>,
     ) -> String {
-        let ha1 = format!("{}:{}:{}", username, realm, password);
-        let ha1_hash = format!("{:x}", md5::compute(ha1.as_bytes()));
-
-        let ha2 = format!("{}:{}", method, uri);
-        let ha2_hash = format!("{:x}", md5::compute(ha2.as_bytes()));
-
-        let response = if let Some(q) = qop {
-            let cnonce_val = cnonce.unwrap_or("0");
-            format!(
-                "{}:{}:{:08x}:{}:{}:{}",
-                ha1_hash, nonce, nc, cnonce_val, q, ha2_hash
-            )
-        } else {
-            format!("{}:{}:{}", ha1_hash, nonce, ha2_hash)
-        };
-
-        format!("{:x}", md5::compute(response.as_bytes()))
+        super::digest::compute_response(
+            super::digest::DigestAlgorithm::Md5,
+            super::digest::DigestInput {
+                username,
+                password,
+                method,
+                uri,
+                body_hash: None,
+            },
+            realm,
+            nonce,
+            qop.map(|q| (q, nc, cnonce.unwrap_or("0"))),
+        )
     }
 
     pub fn validate(&self) -> crate::Result<()> {
@@ -232,4 +227,3 @@ impl Auth {
         }
     }
 }
-
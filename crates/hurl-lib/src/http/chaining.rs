@@ -1,7 +1,10 @@
+use crate::http::client::HttpClient;
+use crate::http::request::RequestBuilder;
 use crate::http::response::HttpResponse;
 use crate::{Error, Result};
 use serde_json::Value;
 use std::collections::HashMap;
+use std::path::Path;
 
 #[derive(Debug, Clone)]
 pub struct Variables {
@@ -55,6 +58,99 @@ impl Variables {
     pub fn clear(&mut self) {
         self.vars.clear();
     }
+
+    /// Adds every variable from `other`, replacing any with the same name.
+    pub fn merge(&mut self, other: Variables) {
+        self.vars.extend(other.vars);
+    }
+
+    /// Sets a variable from a `name=value` assignment, as given to `--var`.
+    pub fn set_assignment(&mut self, assignment: &str) -> Result<()> {
+        let (name, value) = assignment
+            .split_once('=')
+            .filter(|(name, _)| !name.trim().is_empty())
+            .ok_or_else(|| Error::Parse(format!("expected name=value, got '{}'", assignment)))?;
+        self.set(name.trim(), Value::String(value.to_string()));
+        Ok(())
+    }
+
+    /// Loads variables from a file holding either a JSON object or
+    /// `name=value` lines, where blank lines and `#` comments are skipped
+    /// and space around names and values is trimmed.
+    pub fn load_file(&mut self, path: &Path) -> Result<()> {
+        let content = std::fs::read_to_string(path)?;
+        if content.trim_start().starts_with('{') {
+            let object: serde_json::Map<String, Value> = serde_json::from_str(&content)
+                .map_err(|e| Error::Parse(format!("{}: {}", path.display(), e)))?;
+            self.vars.extend(object);
+            return Ok(());
+        }
+        for (n, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, value) = line
+                .split_once('=')
+                .filter(|(name, _)| !name.trim().is_empty())
+                .ok_or_else(|| {
+                    Error::Parse(format!("{}:{}: expected name=value", path.display(), n + 1))
+                })?;
+            self.set(name.trim(), Value::String(value.trim().to_string()));
+        }
+        Ok(())
+    }
+
+    /// Sets a variable for each of `env`'s `(key, value)` pairs whose key
+    /// starts with `prefix`, named by the rest: with `HURL_VAR_`,
+    /// `HURL_VAR_token` sets `token`.
+    pub fn set_from_env_prefix(
+        &mut self,
+        prefix: &str,
+        env: impl IntoIterator<Item = (String, String)>,
+    ) {
+        for (key, value) in env {
+            if let Some(name) = key.strip_prefix(prefix).filter(|n| !n.is_empty()) {
+                self.set(name, Value::String(value));
+            }
+        }
+    }
+
+    /// [`set_from_env_prefix`](Self::set_from_env_prefix) over the process
+    /// environment.
+    pub fn set_from_process_env(&mut self, prefix: &str) {
+        self.set_from_env_prefix(prefix, std::env::vars());
+    }
+
+    /// Replaces each `{{name}}` in `template` with the variable's value.
+    /// Fails on a name that is not defined.
+    pub fn render(&self, template: &str) -> Result<String> {
+        let mut result = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(start) = rest.find("{{") {
+            let Some(len) = rest[start + 2..].find("}}") else {
+                break;
+            };
+            let name = rest[start + 2..start + 2 + len].trim();
+            let value = self
+                .vars
+                .get(name)
+                .ok_or_else(|| Error::Parse(format!("undefined variable: {}", name)))?;
+            result.push_str(&rest[..start]);
+            result.push_str(&display_value(value));
+            rest = &rest[start + 2 + len + 2..];
+        }
+        result.push_str(rest);
+        Ok(result)
+    }
+}
+
+/// Text substituted for a variable: strings as-is, anything else as JSON.
+fn display_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        _ => value.to_string(),
+    }
 }
 
 impl Default for Variables {
@@ -175,7 +271,7 @@ impl Extractor {
 
         for rule in rules {
             let value = match &rule.rule_type {
-                ExtractionType::JsonPath(path) => Self::extract_json_path(&response.body, path)?,
+                ExtractionType::JsonPath(path) => Self::extract_json_path(&response.text(), path)?,
                 ExtractionType::Header(name) => {
                     Value::String(Self::extract_header(response, name)?)
                 }
@@ -272,13 +368,7 @@ impl ChainRequest {
 
         for (key, value) in vars.all().iter() {
             let placeholder = format!("${{{}}}", key);
-            let value_str = match value {
-                Value::String(s) => s.clone(),
-                Value::Number(n) => n.to_string(),
-                Value::Bool(b) => b.to_string(),
-                _ => value.to_string(),
-            };
-            result = result.replace(&placeholder, &value_str);
+            result = result.replace(&placeholder, &display_value(value));
         }
 
         result
@@ -306,31 +396,41 @@ impl RequestChain {
 
         for req in &self.requests {
             let substituted = req.substitute_variables(&vars);
-            let name = substituted
-                .name
-                .clone()
-                .unwrap_or_else(|| format!("req_{}", context.steps.len()));
-            let extractions = substituted.extractions.clone();
-
             let response = client_fn(
                 &substituted.method,
                 &substituted.url_template,
                 substituted.headers.clone(),
                 substituted.body_template.clone(),
             )?;
+            Self::record_step(substituted, response, &mut vars, &mut context)?;
+        }
 
-            let extracted = Extractor::apply_extractions(&response, &extractions)?;
-            let extracted_vars = extracted.vars.clone();
-            vars.vars.extend(extracted.vars);
+        Ok(ChainResult {
+            context,
+            variables: vars,
+            success: true,
+        })
+    }
 
-            let step = ChainStep {
-                name,
-                request: substituted,
-                response: response.clone(),
-                extracted_vars,
-            };
+    /// Runs the chain through `client`, so its retries, cookies and rate
+    /// limiter apply to every step.
+    pub async fn execute(&self, client: &HttpClient) -> Result<ChainResult> {
+        let mut vars = Variables::new();
+        let mut context = ChainContext::new();
+
+        for req in &self.requests {
+            let substituted = req.substitute_variables(&vars);
+            let mut builder =
+                RequestBuilder::new(&substituted.url_template, substituted.method.to_uppercase());
+            for (name, value) in &substituted.headers {
+                builder = builder.header(name, value);
+            }
+            if let Some(body) = &substituted.body_template {
+                builder = builder.text_body(body);
+            }
 
-            context.steps.push(step);
+            let response = client.execute(builder).await?;
+            Self::record_step(substituted, response, &mut vars, &mut context)?;
         }
 
         Ok(ChainResult {
@@ -339,6 +439,29 @@ impl RequestChain {
             success: true,
         })
     }
+
+    fn record_step(
+        request: ChainRequest,
+        response: HttpResponse,
+        vars: &mut Variables,
+        context: &mut ChainContext,
+    ) -> Result<()> {
+        let name = request
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("req_{}", context.steps.len()));
+        let extracted = Extractor::apply_extractions(&response, &request.extractions)?;
+        let extracted_vars = extracted.vars.clone();
+        vars.vars.extend(extracted.vars);
+
+        context.steps.push(ChainStep {
+            name,
+            request,
+            response,
+            extracted_vars,
+        });
+        Ok(())
+    }
 }
 
 impl Default for RequestChain {
@@ -396,3 +519,69 @@ impl ChainResult {
         self.variables.get(name)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_render_placeholders() {
+        let mut vars = Variables::new();
+        vars.set("id", json!(7));
+        vars.set("name", json!("ann"));
+
+        assert_eq!(
+            vars.render("/users/{{id}}?q={{ name }}&x={{").unwrap(),
+            "/users/7?q=ann&x={{"
+        );
+        assert!(vars.render("{{missing}}").is_err());
+    }
+
+    #[test]
+    fn test_assignments_and_variables_file() {
+        let mut vars = Variables::new();
+        vars.set_assignment("token=a=b").unwrap();
+        assert_eq!(vars.get_string("token").as_deref(), Some("a=b"));
+        assert!(vars.set_assignment("novalue").is_err());
+        assert!(vars.set_assignment("=1").is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let env_file = dir.path().join("vars.env");
+        std::fs::write(
+            &env_file,
+            "# staging\nbase=https://staging.test\n\nuser = bob\n",
+        )
+        .unwrap();
+        vars.load_file(&env_file).unwrap();
+        assert_eq!(
+            vars.get_string("base").as_deref(),
+            Some("https://staging.test")
+        );
+        assert_eq!(vars.get_string("user").as_deref(), Some("bob"));
+
+        let json_file = dir.path().join("vars.json");
+        std::fs::write(&json_file, r#"{"retries": 3, "user": "carol"}"#).unwrap();
+        vars.load_file(&json_file).unwrap();
+        assert_eq!(vars.get_number("retries"), Some(3));
+        assert_eq!(vars.get_string("user").as_deref(), Some("carol"));
+
+        std::fs::write(&env_file, "ok=1\nbroken\n").unwrap();
+        let err = vars.load_file(&env_file).unwrap_err().to_string();
+        assert!(err.contains("vars.env:2"), "{}", err);
+    }
+
+    #[test]
+    fn test_set_from_env_prefix() {
+        let env = vec![
+            ("HURL_VAR_host".to_string(), "api.test".to_string()),
+            ("HURL_VAR_".to_string(), "unnamed".to_string()),
+            ("PATH".to_string(), "/usr/bin".to_string()),
+        ];
+        let mut vars = Variables::new();
+        vars.set_from_env_prefix("HURL_VAR_", env);
+        assert_eq!(vars.get_string("host").as_deref(), Some("api.test"));
+        assert_eq!(vars.get_string("PATH"), None);
+        assert_eq!(vars.get_string(""), None);
+    }
+}
@@ -1,17 +1,32 @@
-use super::compression::CompressionConfig;
-use super::request::RequestBuilder;
+use super::auth::Auth;
+use super::compression::{CompressionConfig, Decompressor, StreamDecoder};
+use super::cookies::SharedCookieJar;
+use super::digest::{hash_body, DigestCache, DigestChallenge, DigestInput};
+use super::headers::Headers;
+use super::oauth2::{is_invalid_token, OAuth2Provider};
+use super::proxy::{EnvProxies, ProxyResolver};
+use super::rate_limit::RateLimiter;
+use super::redirect::{is_redirect, redirect_method, resolve_location, same_origin, RedirectHop};
+use super::request::{RequestBody, RequestBuilder};
 use super::response::HttpResponse;
+use super::retry::{RetryPolicy, DEFAULT_RETRY_STATUSES};
 use super::security::{ProxyConfig, TlsConfig};
-use reqwest::cookie::Jar;
+use crate::storage::cache::{CacheLookup, CacheStore};
 use reqwest::Client as ReqClient;
+use std::borrow::Borrow;
 use std::collections::HashMap;
+use std::future::Future;
+use std::ops::Deref;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
 
 #[derive(Clone)]
 pub struct HttpClient {
     client: Arc<ReqClient>,
     config: Arc<ClientConfig>,
+    digest: Arc<DigestCache>,
+    cookies: Option<Arc<SharedCookieJar>>,
 }
 
 pub struct ClientConfig {
@@ -20,10 +35,26 @@ pub struct ClientConfig {
     pub enable_cookies: bool,
     pub max_retries: u32,
     pub retry_delay_ms: u64,
+    pub max_retry_delay_ms: u64,
+    pub retry_on_status: Vec<u16>,
+    pub retry_non_idempotent: bool,
     pub max_redirects: u32,
+    pub follow_redirects: bool,
     pub tls_config: TlsConfig,
     pub proxy_config: ProxyConfig,
     pub compression_config: CompressionConfig,
+    /// Supplies bearer tokens for requests that carry no auth of their own.
+    pub oauth2: Option<Arc<OAuth2Provider>>,
+    /// Jar to read and store cookies in; a fresh one is used when unset.
+    /// Ignored when `enable_cookies` is false.
+    pub cookie_jar: Option<Arc<SharedCookieJar>>,
+    /// Serves GET requests from this cache and stores their responses:
+    /// a `Mutex<ResponseCache>` in memory or a
+    /// [`DiskCache`](crate::storage::disk_cache::DiskCache).
+    pub cache: Option<Arc<dyn CacheStore>>,
+    /// Paces every request sent, including retries and redirects. Share
+    /// one limiter between clients to pace them together.
+    pub rate_limiter: Option<Arc<RateLimiter>>,
 }
 
 impl Default for ClientConfig {
@@ -34,14 +65,31 @@ impl Default for ClientConfig {
             enable_cookies: true,
             max_retries: 3,
             retry_delay_ms: 100,
+            max_retry_delay_ms: 30_000,
+            retry_on_status: DEFAULT_RETRY_STATUSES.to_vec(),
+            retry_non_idempotent: false,
             max_redirects: 5,
+            follow_redirects: true,
             tls_config: TlsConfig::new(),
             proxy_config: ProxyConfig::new(),
             compression_config: CompressionConfig::new(),
+            oauth2: None,
+            cookie_jar: None,
+            cache: None,
+            rate_limiter: None,
         }
     }
 }
 
+impl ClientConfig {
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy::new(self.max_retries, Duration::from_millis(self.retry_delay_ms))
+            .with_max_delay(Duration::from_millis(self.max_retry_delay_ms))
+            .with_statuses(self.retry_on_status.clone())
+            .with_non_idempotent(self.retry_non_idempotent)
+    }
+}
+
 impl HttpClient {
     pub fn new() -> crate::Result<Self> {
         Self::with_config(ClientConfig::default())
@@ -51,26 +99,24 @@ impl HttpClient {
         config.tls_config.validate()?;
         config.proxy_config.validate()?;
 
-        let jar = Arc::new(Jar::default());
         let mut builder = ReqClient::builder()
             .pool_max_idle_per_host(config.max_connections)
-            .cookie_provider(jar);
-
-        builder = match &config.proxy_config.proxy {
-            Some(super::security::ProxyType::Http { url }) => builder.proxy(
-                reqwest::Proxy::http(url)
-                    .map_err(|e| crate::Error::Http(format!("Proxy config failed: {}", e)))?,
-            ),
-            Some(super::security::ProxyType::Https { url }) => builder.proxy(
-                reqwest::Proxy::https(url)
-                    .map_err(|e| crate::Error::Http(format!("Proxy config failed: {}", e)))?,
-            ),
-            Some(super::security::ProxyType::Socks5 { url }) => builder.proxy(
-                reqwest::Proxy::all(url)
-                    .map_err(|e| crate::Error::Http(format!("Proxy config failed: {}", e)))?,
-            ),
-            None => builder,
-        };
+            .redirect(reqwest::redirect::Policy::none())
+            .use_preconfigured_tls(super::tls::build_rustls_config(&config.tls_config)?);
+
+        let cookies = config
+            .enable_cookies
+            .then(|| config.cookie_jar.clone().unwrap_or_default());
+        if let Some(jar) = &cookies {
+            builder = builder.cookie_provider(jar.clone());
+        }
+
+        let resolver = ProxyResolver::new(config.proxy_config.clone(), EnvProxies::from_env());
+        let mut proxy = reqwest::Proxy::custom(move |url| resolver.resolve(url));
+        if let Some(auth) = &config.proxy_config.auth {
+            proxy = proxy.basic_auth(&auth.username, &auth.password);
+        }
+        builder = builder.proxy(proxy);
 
         let client = builder
             .build()
@@ -78,27 +124,287 @@ impl HttpClient {
         Ok(HttpClient {
             client: Arc::new(client),
             config: Arc::new(config),
+            digest: Arc::new(DigestCache::new()),
+            cookies,
         })
     }
 
+    /// The jar cookies are read from and stored in, unless cookies are disabled.
+    pub fn cookie_jar(&self) -> Option<&Arc<SharedCookieJar>> {
+        self.cookies.as_ref()
+    }
+
     pub async fn execute(&self, builder: RequestBuilder) -> crate::Result<HttpResponse> {
-        self.execute_with_redirects(builder, 0).await
+        match &self.config.cache {
+            Some(cache) => self.execute_cached(cache.as_ref(), builder).await,
+            None => self.execute_uncached(builder).await,
+        }
     }
 
-    async fn execute_with_redirects(
+    async fn execute_cached(
         &self,
+        cache: &dyn CacheStore,
         builder: RequestBuilder,
-        redirect_count: u32,
     ) -> crate::Result<HttpResponse> {
-        if redirect_count > self.config.max_redirects {
-            return Err(crate::Error::Http(format!(
-                "Maximum redirects exceeded: {}",
-                self.config.max_redirects
-            )));
+        let lookup = cache.lookup(&builder)?;
+        let request = match lookup {
+            CacheLookup::Fresh(response) => return Ok(response),
+            CacheLookup::Stale(validators) => validators
+                .into_iter()
+                .fold(builder.clone(), |b, (name, value)| b.header(name, value)),
+            CacheLookup::Miss => builder.clone(),
+        };
+
+        let request_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let response = self.execute_uncached(request).await?;
+        cache.complete(&builder, response, request_time)
+    }
+
+    async fn execute_uncached(&self, builder: RequestBuilder) -> crate::Result<HttpResponse> {
+        let (response, attempts) = self
+            .with_retries(builder, |b| async move {
+                self.execute_with_redirects(b).await?.buffer().await
+            })
+            .await?;
+        Ok(response.with_attempts(attempts))
+    }
+
+    /// Like [`HttpClient::execute`], but writes the decoded body to a sink as
+    /// it arrives instead of buffering it, so downloads of any size run in
+    /// constant memory. The sink comes from `open`, called with the final
+    /// response head, so a request that fails never opens it. The returned
+    /// response carries status and headers with an empty body. Retries only
+    /// happen before the body starts, and the response cache is not
+    /// consulted.
+    pub async fn execute_to<W, F, Fut>(
+        &self,
+        builder: RequestBuilder,
+        open: F,
+    ) -> crate::Result<HttpResponse>
+    where
+        W: AsyncWrite + Unpin,
+        F: FnOnce(&HttpResponse) -> Fut,
+        Fut: Future<Output = std::io::Result<W>>,
+    {
+        let (pending, attempts) = self
+            .with_retries(builder, |b| self.execute_with_redirects(b))
+            .await?;
+        let mut sink = open(&pending).await?;
+        Ok(pending.stream_to(&mut sink).await?.with_attempts(attempts))
+    }
+
+    async fn with_retries<T, F, Fut>(
+        &self,
+        builder: RequestBuilder,
+        send: F,
+    ) -> crate::Result<(T, u32)>
+    where
+        T: Borrow<HttpResponse>,
+        F: Fn(RequestBuilder) -> Fut,
+        Fut: Future<Output = crate::Result<T>>,
+    {
+        let policy = self.config.retry_policy();
+        let retryable_method = policy.allows_method(builder.method());
+        let mut attempt = 0;
+
+        loop {
+            attempt += 1;
+            let result = send(builder.clone()).await;
+            let can_retry = retryable_method && attempt <= policy.max_retries;
+
+            let delay = match &result {
+                Ok(resp) if can_retry && policy.should_retry_status(resp.borrow().status) => {
+                    policy.delay_for(attempt, Some(resp.borrow()))
+                }
+                Err(e) if can_retry && RetryPolicy::should_retry_error(e) => {
+                    policy.delay_for(attempt, None)
+                }
+                _ => return result.map(|resp| (resp, attempt)),
+            };
+
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    async fn execute_with_redirects(
+        &self,
+        builder: RequestBuilder,
+    ) -> crate::Result<PendingResponse> {
+        let start = Instant::now();
+        let origin = builder.build_url()?;
+        let mut current = builder;
+        let mut hops = Vec::new();
+
+        loop {
+            let url = current.build_url()?;
+            let first_party = same_origin(&origin, &url);
+            let response = self.send_authenticated(&current, first_party).await?;
+
+            let location = match response.header("Location") {
+                Some(loc) if self.config.follow_redirects && is_redirect(response.status) => {
+                    loc.to_string()
+                }
+                _ => {
+                    return Ok(response.map_head(|head| {
+                        head.with_duration(start.elapsed()).with_redirects(hops)
+                    }));
+                }
+            };
+
+            if hops.len() as u32 >= self.config.max_redirects {
+                return Err(crate::Error::Http(format!(
+                    "Maximum redirects exceeded: {}",
+                    self.config.max_redirects
+                )));
+            }
+
+            let next_url = resolve_location(&url, &location)?;
+            let (method, keep_body) = redirect_method(response.status, current.method());
+            hops.push(RedirectHop {
+                method: current.method().to_string(),
+                url,
+                status: response.status,
+                location,
+            });
+
+            let mut next = current.with_url(next_url.clone()).with_method(method);
+            if !keep_body {
+                next = next
+                    .without_body()
+                    .remove_header("Content-Type")
+                    .remove_header("Content-Length");
+            }
+            // Credentials set for the first origin must not leak to
+            // another one, including a hand-written Cookie header.
+            if !same_origin(&origin, &next_url) {
+                next = next
+                    .with_auth(Auth::None)
+                    .remove_header("Authorization")
+                    .remove_header("Cookie");
+            }
+            current = next;
+        }
+    }
+
+    /// Sends one hop, performing whichever auth exchange the request needs.
+    /// OAuth2 tokens are only attached on the origin the request started at.
+    async fn send_authenticated(
+        &self,
+        builder: &RequestBuilder,
+        first_party: bool,
+    ) -> crate::Result<PendingResponse> {
+        match (builder.auth(), &self.config.oauth2) {
+            (Auth::Digest { .. }, _) => self.send_digest(builder).await,
+            (Auth::None, Some(provider)) if first_party && !builder.has_header("Authorization") => {
+                self.send_oauth2(builder, provider).await
+            }
+            _ => self.send(builder).await,
+        }
+    }
+
+    /// Attaches the provider's token, and if the server rejects it as
+    /// `invalid_token`, refreshes once and resends.
+    async fn send_oauth2(
+        &self,
+        builder: &RequestBuilder,
+        provider: &OAuth2Provider,
+    ) -> crate::Result<PendingResponse> {
+        let token = provider.token(self).await?;
+        let response = self
+            .send(&builder.clone().with_auth(token.to_auth()))
+            .await?;
+
+        let rejected = response.status == 401
+            && response
+                .header_all("WWW-Authenticate")
+                .into_iter()
+                .any(is_invalid_token);
+        if !rejected {
+            return Ok(response);
+        }
+
+        provider.invalidate(&token).await;
+        let token = provider.token(self).await?;
+        self.send(&builder.clone().with_auth(token.to_auth())).await
+    }
+
+    /// Runs the Digest handshake when the request carries `Auth::Digest`:
+    /// answer from a cached challenge if there is one, otherwise send once
+    /// without credentials and answer the 401 challenge. A rejected nonce is
+    /// only retried when the server marks it stale. The body is hashed only
+    /// when the challenge asks for `qop=auth-int`.
+    async fn send_digest(&self, builder: &RequestBuilder) -> crate::Result<PendingResponse> {
+        let Auth::Digest {
+            username, password, ..
+        } = builder.auth()
+        else {
+            return self.send(builder).await;
+        };
+
+        let url = url::Url::parse(&builder.build_url()?)
+            .map_err(|e| crate::Error::Http(format!("bad url: {}", e)))?;
+        let origin = url.origin().ascii_serialization();
+        let uri = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+
+        let unauthenticated = builder.clone().with_auth(Auth::None);
+        let mut answered_fresh_challenge = false;
+        let mut body_hash = None;
+        for _ in 0..3 {
+            body_hash = match (self.digest.auth_int_algorithm(&origin), body_hash) {
+                (Some(algorithm), Some((hashed_with, hash))) if hashed_with == algorithm => {
+                    Some((algorithm, hash))
+                }
+                (Some(algorithm), _) => Some((
+                    algorithm,
+                    hash_body(algorithm, builder.body().as_ref()).await?,
+                )),
+                (None, _) => None,
+            };
+            let input = DigestInput {
+                username,
+                password,
+                method: builder.method(),
+                uri: &uri,
+                body_hash: body_hash.as_ref().map(|(_, hash)| hash.as_str()),
+            };
+            let request = match self.digest.authorize(&origin, input) {
+                Some(header) => unauthenticated.clone().header("Authorization", header),
+                None => unauthenticated.clone(),
+            };
+            let response = self.send(&request).await?;
+            if response.status != 401 {
+                return Ok(response);
+            }
+
+            // Servers may offer each algorithm in its own header line.
+            let offered = response.header_all("WWW-Authenticate").join(", ");
+            let challenge = match DigestChallenge::parse(&offered) {
+                Some(challenge) if !answered_fresh_challenge || challenge.stale => challenge,
+                _ => return Ok(response),
+            };
+            self.digest.store(&origin, challenge);
+            answered_fresh_challenge = true;
         }
+        Err(crate::Error::Http("Digest authentication failed".into()))
+    }
 
+    pub(crate) async fn send_once(&self, builder: &RequestBuilder) -> crate::Result<HttpResponse> {
+        self.send(builder).await?.buffer().await
+    }
+
+    /// Sends one request and returns as soon as the response headers arrive.
+    async fn send(&self, builder: &RequestBuilder) -> crate::Result<PendingResponse> {
         builder.validate()?;
         let url = builder.build_url()?;
+        if let Some(limiter) = &self.config.rate_limiter {
+            limiter.acquire(&url).await;
+        }
         let start = Instant::now();
 
         let mut request = match builder.method() {
@@ -115,7 +421,7 @@ impl HttpClient {
         let timeout_dur = builder.timeout();
         request = request.timeout(timeout_dur);
 
-        if self.config.compression_config.enabled {
+        if self.config.compression_config.enabled && !builder.has_header("Accept-Encoding") {
             request = request.header(
                 "Accept-Encoding",
                 self.config.compression_config.accept_encoding_header(),
@@ -131,35 +437,31 @@ impl HttpClient {
         }
 
         if let Some(body) = builder.body() {
-            let ct = body.content_type();
-            request = request.header("Content-Type", ct);
-            let bytes = body.to_bytes()?;
-            request = request.body(bytes);
+            if !builder.has_header("Content-Type") {
+                request = request.header("Content-Type", body.content_type());
+            }
+            request = match body {
+                RequestBody::Multipart(form) => request
+                    .header("Content-Length", form.content_length()?)
+                    .body(reqwest::Body::wrap_stream(form.stream())),
+                _ => request.body(body.to_bytes()?),
+            };
         }
 
-        let resp = request
-            .send()
-            .await
-            .map_err(|e| crate::Error::Http(format!("Request failed: {}", e)))?;
+        let resp = request.send().await?;
 
         let status = resp.status().as_u16();
-        let headers = resp
+        let headers: Headers = resp
             .headers()
             .iter()
-            .map(|(n, v)| (n.as_str().to_string(), v.to_str().unwrap_or("").to_string()))
+            .map(|(n, v)| (n.as_str(), String::from_utf8_lossy(v.as_bytes())))
             .collect();
 
-        let body_bytes = resp
-            .bytes()
-            .await
-            .map_err(|e| crate::Error::Http(format!("Body read failed: {}", e)))?;
-
-        let body_text = String::from_utf8_lossy(&body_bytes).to_string();
-
-        let dur = start.elapsed();
-        let http_resp = HttpResponse::new(status, headers, body_text).with_duration(dur);
-
-        Ok(http_resp)
+        let head = HttpResponse::new(status, headers, Vec::new()).with_duration(start.elapsed());
+        if let Some(limiter) = &self.config.rate_limiter {
+            limiter.observe(&url, &head);
+        }
+        Ok(PendingResponse { head, body: resp })
     }
 
     pub async fn get(&self, url: impl Into<String>) -> crate::Result<HttpResponse> {
@@ -185,8 +487,153 @@ impl HttpClient {
     }
 }
 
+/// A response whose status and headers have arrived but whose body is still
+/// on the wire, so the caller can choose between buffering and streaming it.
+struct PendingResponse {
+    head: HttpResponse,
+    body: reqwest::Response,
+}
+
+impl PendingResponse {
+    fn map_head(self, f: impl FnOnce(HttpResponse) -> HttpResponse) -> Self {
+        PendingResponse {
+            head: f(self.head),
+            body: self.body,
+        }
+    }
+
+    async fn buffer(self) -> crate::Result<HttpResponse> {
+        let start = Instant::now();
+        let raw = self.body.bytes().await?;
+        let body = if raw.is_empty() {
+            raw
+        } else {
+            Decompressor::auto_decompress(&raw, self.head.header("Content-Encoding"))?.into()
+        };
+        let duration = self.head.duration + start.elapsed();
+        Ok(HttpResponse { body, ..self.head }.with_duration(duration))
+    }
+
+    async fn stream_to<W>(mut self, sink: &mut W) -> crate::Result<HttpResponse>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let start = Instant::now();
+        let mut decoder = StreamDecoder::new(self.head.header("Content-Encoding"));
+        let mut received = false;
+        while let Some(chunk) = self.body.chunk().await? {
+            received = true;
+            sink.write_all(&decoder.decode(&chunk)?).await?;
+        }
+        if received {
+            sink.write_all(&decoder.finish()?).await?;
+        }
+        sink.flush().await?;
+        let duration = self.head.duration + start.elapsed();
+        Ok(self.head.with_duration(duration))
+    }
+}
+
+impl Deref for PendingResponse {
+    type Target = HttpResponse;
+
+    fn deref(&self) -> &HttpResponse {
+        &self.head
+    }
+}
+
+impl Borrow<HttpResponse> for PendingResponse {
+    fn borrow(&self) -> &HttpResponse {
+        &self.head
+    }
+}
+
 impl Default for HttpClient {
     fn default() -> Self {
         Self::new().expect("Failed to create default HTTP client")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::test_server::{MockResponse, TestServer};
+    use std::io::Write;
+
+    fn binary_payload() -> Vec<u8> {
+        (0..300_000u32).map(|i| (i * 31 % 256) as u8).collect()
+    }
+
+    #[tokio::test]
+    async fn test_binary_body_is_not_corrupted() {
+        let payload = binary_payload();
+        let server =
+            TestServer::sequence(vec![MockResponse::ok(payload.clone())
+                .header("Content-Type", "application/octet-stream")])
+            .await;
+
+        let response = HttpClient::new()
+            .unwrap()
+            .get(server.url("/blob"))
+            .await
+            .unwrap();
+        assert_eq!(response.body, payload);
+    }
+
+    #[tokio::test]
+    async fn test_execute_to_streams_decoded_body() {
+        let payload = binary_payload();
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&payload).unwrap();
+        let server = TestServer::sequence(vec![
+            MockResponse::new(503),
+            MockResponse::ok(encoder.finish().unwrap()).header("Content-Encoding", "gzip"),
+        ])
+        .await;
+        let client = HttpClient::with_config(ClientConfig {
+            retry_delay_ms: 1,
+            ..ClientConfig::default()
+        })
+        .unwrap();
+
+        let mut sink = Vec::new();
+        let response = client
+            .execute_to(RequestBuilder::get(server.url("/download")), |_| {
+                std::future::ready(Ok(&mut sink))
+            })
+            .await
+            .unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.attempts, 2);
+        assert!(response.body.is_empty());
+        assert_eq!(sink, payload);
+    }
+
+    #[tokio::test]
+    async fn test_repeated_headers_survive_both_directions() {
+        let server = TestServer::start(|_, _| {
+            MockResponse::ok("")
+                .header("Set-Cookie", "a=1")
+                .header("Link", "</next>; rel=next")
+                .header("Set-Cookie", "b=2")
+        })
+        .await;
+
+        let response = HttpClient::new()
+            .unwrap()
+            .execute(
+                RequestBuilder::get(server.url("/"))
+                    .append_header("Accept", "text/html")
+                    .append_header("Accept", "application/json"),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.header_all("set-cookie"), vec!["a=1", "b=2"]);
+        assert_eq!(response.header("link"), Some("</next>; rel=next"));
+        assert_eq!(
+            server.requests()[0].header_all("Accept"),
+            vec!["text/html", "application/json"]
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use std::io::Read;
+use std::io::{Read, Write};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CompressionCodec {
@@ -130,6 +130,59 @@ impl Decompressor {
     }
 }
 
+/// Incremental counterpart of [`Decompressor`] for bodies that are consumed
+/// chunk by chunk instead of being buffered.
+pub enum StreamDecoder {
+    Identity,
+    Gzip(flate2::write::GzDecoder<Vec<u8>>),
+    Deflate(flate2::write::DeflateDecoder<Vec<u8>>),
+    Brotli(Box<brotli::DecompressorWriter<Vec<u8>>>),
+}
+
+impl StreamDecoder {
+    pub fn new(content_encoding: Option<&str>) -> Self {
+        match content_encoding.and_then(CompressionCodec::from_header_value) {
+            Some(CompressionCodec::Gzip) => {
+                StreamDecoder::Gzip(flate2::write::GzDecoder::new(Vec::new()))
+            }
+            Some(CompressionCodec::Deflate) => {
+                StreamDecoder::Deflate(flate2::write::DeflateDecoder::new(Vec::new()))
+            }
+            Some(CompressionCodec::Brotli) => {
+                StreamDecoder::Brotli(Box::new(brotli::DecompressorWriter::new(Vec::new(), 4096)))
+            }
+            None => StreamDecoder::Identity,
+        }
+    }
+
+    /// Feeds one compressed chunk and returns whatever it decoded to.
+    pub fn decode(&mut self, chunk: &[u8]) -> crate::Result<Vec<u8>> {
+        let (result, out) = match self {
+            StreamDecoder::Identity => return Ok(chunk.to_vec()),
+            StreamDecoder::Gzip(d) => (d.write_all(chunk), d.get_mut()),
+            StreamDecoder::Deflate(d) => (d.write_all(chunk), d.get_mut()),
+            StreamDecoder::Brotli(d) => (d.write_all(chunk), d.get_mut()),
+        };
+        result.map_err(|e| crate::Error::Http(format!("decompression failed: {}", e)))?;
+        Ok(std::mem::take(out))
+    }
+
+    /// Flushes the decoder at end of body, returning any trailing output.
+    pub fn finish(self) -> crate::Result<Vec<u8>> {
+        let err = |e: std::io::Error| crate::Error::Http(format!("decompression failed: {}", e));
+        match self {
+            StreamDecoder::Identity => Ok(Vec::new()),
+            StreamDecoder::Gzip(d) => d.finish().map_err(err),
+            StreamDecoder::Deflate(d) => d.finish().map_err(err),
+            StreamDecoder::Brotli(mut d) => {
+                d.close().map_err(err)?;
+                d.into_inner()
+                    .map_err(|_| crate::Error::Http("brotli stream truncated".into()))
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -215,4 +268,27 @@ mod tests {
     fn test_unknown_codec() {
         assert_eq!(CompressionCodec::from_header_value("unknown"), None);
     }
+
+    #[test]
+    fn test_stream_decoder_gzip_in_chunks() {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        let original: Vec<u8> = (0..50_000u32).map(|i| (i % 7) as u8).collect();
+        encoder.write_all(&original).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut decoder = StreamDecoder::new(Some("gzip"));
+        let mut decoded = Vec::new();
+        for chunk in compressed.chunks(100) {
+            decoded.extend(decoder.decode(chunk).unwrap());
+        }
+        decoded.extend(decoder.finish().unwrap());
+        assert_eq!(decoded, original);
+    }
+
+    #[test]
+    fn test_stream_decoder_identity() {
+        let mut decoder = StreamDecoder::new(Some("identity"));
+        assert_eq!(decoder.decode(b"raw").unwrap(), b"raw");
+        assert!(decoder.finish().unwrap().is_empty());
+    }
 }
@@ -1,5 +1,41 @@
-use std::collections::HashMap;
+use publicsuffix::{List, Psl};
+use reqwest::header::HeaderValue;
+use std::sync::{OnceLock, RwLock};
 use std::time::{SystemTime, UNIX_EPOCH};
+use url::Url;
+
+/// The Public Suffix List (https://publicsuffix.org/list/), ICANN and
+/// private sections, embedded at build time.
+const PUBLIC_SUFFIX_LIST: &str = include_str!("../../data/public_suffix_list.dat");
+
+fn public_suffix_list() -> &'static List {
+    static LIST: OnceLock<List> = OnceLock::new();
+    LIST.get_or_init(|| {
+        PUBLIC_SUFFIX_LIST
+            .parse()
+            .expect("the embedded public suffix list parses")
+    })
+}
+
+/// True when cookies may not be scoped to `domain`: it is a rule of the
+/// Public Suffix List, such as `co.uk` or `github.io`, or a single label.
+pub fn is_public_suffix(domain: &str) -> bool {
+    let domain = domain.trim_matches('.').to_ascii_lowercase();
+    if !domain.contains('.') {
+        return true;
+    }
+    match public_suffix_list().suffix(domain.as_bytes()) {
+        Some(suffix) => suffix.as_bytes() == domain.as_bytes(),
+        None => false,
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
 
 #[derive(Debug, Clone)]
 pub struct Cookie {
@@ -73,11 +109,119 @@ impl Cookie {
         false
     }
 
+    /// Parses a `Set-Cookie` header received from `url`. Returns `None` when
+    /// the header is malformed or the cookie would be rejected: a Domain the
+    /// host is not within, a public suffix, a Secure cookie from plain HTTP,
+    /// `SameSite=None` without Secure, or a violated `__Secure-`/`__Host-`
+    /// prefix. Host-only cookies keep a bare domain; cookies with a Domain
+    /// attribute are stored as `.domain` and also match subdomains.
+    pub fn parse(header: &str, url: &Url) -> Option<Cookie> {
+        let host = url.host_str()?.trim_matches(|c| c == '[' || c == ']');
+        let host = host.to_ascii_lowercase();
+        let secure_origin = url.scheme() == "https";
+
+        let mut attrs = header.split(';');
+        let (name, value) = attrs.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+        let mut cookie = Cookie::new(name, value.trim().trim_matches('"'));
+        cookie.path = None;
+
+        let mut domain_attr = None;
+        let mut max_age = None;
+        let mut explicit_same_site = false;
+        for attr in attrs {
+            let (key, val) = match attr.split_once('=') {
+                Some((k, v)) => (k.trim(), v.trim()),
+                None => (attr.trim(), ""),
+            };
+            match key.to_ascii_lowercase().as_str() {
+                "domain" if !val.trim_start_matches('.').is_empty() => {
+                    domain_attr = Some(val.trim_start_matches('.').to_ascii_lowercase())
+                }
+                "path" if val.starts_with('/') => cookie.path = Some(val.to_string()),
+                "max-age" => max_age = val.parse::<i64>().ok().or(max_age),
+                "expires" => {
+                    if let Some(at) = crate::utils::parse_http_date(val) {
+                        cookie.expires = Some(at.max(0) as u64);
+                    }
+                }
+                "secure" => cookie.secure = true,
+                "httponly" => cookie.http_only = true,
+                "samesite" => {
+                    explicit_same_site = true;
+                    cookie.same_site = match val.to_ascii_lowercase().as_str() {
+                        "strict" => SameSite::Strict,
+                        "none" => SameSite::None,
+                        _ => SameSite::Lax,
+                    };
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(secs) = max_age {
+            cookie.expires = Some(if secs <= 0 {
+                0
+            } else {
+                now_secs().saturating_add(secs as u64)
+            });
+        }
+
+        let has_domain_attr = domain_attr.is_some();
+        cookie.domain = Some(match domain_attr {
+            Some(domain) if domain == host => host.clone(),
+            Some(domain) if is_public_suffix(&domain) => return None,
+            Some(domain) if host.ends_with(&format!(".{}", domain)) => {
+                if host.parse::<std::net::IpAddr>().is_ok() {
+                    return None;
+                }
+                format!(".{}", domain)
+            }
+            Some(_) => return None,
+            None => host.clone(),
+        });
+        if cookie.path.is_none() {
+            cookie.path = Some(default_path(url.path()));
+        }
+
+        if cookie.secure && !secure_origin {
+            return None;
+        }
+        if explicit_same_site && cookie.same_site == SameSite::None && !cookie.secure {
+            return None;
+        }
+        if name.starts_with("__Secure-") && !cookie.secure {
+            return None;
+        }
+        if name.starts_with("__Host-")
+            && (!cookie.secure || has_domain_attr || cookie.path.as_deref() != Some("/"))
+        {
+            return None;
+        }
+        Some(cookie)
+    }
+
+    /// Whether the cookie should be sent with a request to `url`.
+    pub fn matches_url(&self, url: &Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        let host = host.trim_matches(|c| c == '[' || c == ']');
+        (!self.secure || url.scheme() == "https")
+            && self.matches_domain(host)
+            && self.matches_path(url.path())
+    }
+
     pub fn matches_domain(&self, request_domain: &str) -> bool {
         match &self.domain {
             Some(domain) => {
-                if domain.starts_with('.') {
-                    request_domain.ends_with(domain) || request_domain.ends_with(&domain[1..])
+                let request_domain = request_domain.to_ascii_lowercase();
+                let domain = domain.to_ascii_lowercase();
+                if let Some(bare) = domain.strip_prefix('.') {
+                    request_domain.ends_with(&domain) || request_domain == bare
                 } else {
                     request_domain == domain
                 }
@@ -86,13 +230,25 @@ impl Cookie {
         }
     }
 
+    /// RFC 6265 path-match: `/api` matches `/api` and `/api/v1`, not `/apis`.
     pub fn matches_path(&self, request_path: &str) -> bool {
         match &self.path {
-            Some(path) => request_path.starts_with(path),
+            Some(path) => {
+                request_path == path
+                    || (request_path.starts_with(path.as_str())
+                        && (path.ends_with('/') || request_path[path.len()..].starts_with('/')))
+            }
             None => true,
         }
     }
 
+    fn same_slot(&self, other: &Cookie) -> bool {
+        self.name == other.name
+            && self.domain.as_deref().map(str::to_ascii_lowercase)
+                == other.domain.as_deref().map(str::to_ascii_lowercase)
+            && self.path == other.path
+    }
+
     pub fn to_header_value(&self) -> String {
         format!("{}={}", self.name, self.value)
     }
@@ -106,7 +262,12 @@ impl Cookie {
             header.push_str(&format!("; Path={}", path));
         }
         if let Some(expires) = self.expires {
-            header.push_str(&format!("; Expires={}", expires));
+            if let Some(at) = chrono::DateTime::from_timestamp(expires as i64, 0) {
+                header.push_str(&format!(
+                    "; Expires={}",
+                    at.format("%a, %d %b %Y %H:%M:%S GMT")
+                ));
+            }
         }
         if self.secure {
             header.push_str("; Secure");
@@ -124,28 +285,71 @@ impl Cookie {
     }
 }
 
+/// Default-path of RFC 6265 5.1.4: the request path up to its last `/`.
+fn default_path(request_path: &str) -> String {
+    match request_path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(i) => request_path[..i].to_string(),
+    }
+}
+
+/// Cookies keyed by name, domain and path, kept in creation order.
 #[derive(Debug, Clone)]
 pub struct CookieJar {
-    cookies: HashMap<String, Cookie>,
+    cookies: Vec<Cookie>,
 }
 
 impl CookieJar {
     pub fn new() -> Self {
         CookieJar {
-            cookies: HashMap::new(),
+            cookies: Vec::new(),
         }
     }
 
+    /// Adds a cookie, replacing one with the same name, domain and path.
     pub fn add(&mut self, cookie: Cookie) {
-        self.cookies.insert(cookie.name.clone(), cookie);
+        match self.cookies.iter_mut().find(|c| c.same_slot(&cookie)) {
+            Some(existing) => *existing = cookie,
+            None => self.cookies.push(cookie),
+        }
+    }
+
+    /// Applies a `Set-Cookie` header received from `url`. Returns whether
+    /// the cookie was accepted. An already-expired cookie deletes its slot,
+    /// and plain HTTP responses cannot overwrite Secure cookies.
+    pub fn store(&mut self, header: &str, url: &Url) -> bool {
+        let Some(cookie) = Cookie::parse(header, url) else {
+            return false;
+        };
+        if url.scheme() != "https"
+            && self.cookies.iter().any(|c| {
+                c.secure
+                    && c.name == cookie.name
+                    && (c.matches_domain(cookie.domain.as_deref().unwrap_or_default())
+                        || cookie.matches_domain(c.domain.as_deref().unwrap_or_default()))
+                    && cookie.matches_path(c.path.as_deref().unwrap_or("/"))
+            })
+        {
+            return false;
+        }
+        if cookie.is_expired() {
+            self.cookies.retain(|c| !c.same_slot(&cookie));
+        } else {
+            self.add(cookie);
+        }
+        true
     }
 
     pub fn get(&self, name: &str) -> Option<&Cookie> {
-        self.cookies.get(name)
+        self.cookies.iter().find(|c| c.name == name)
     }
 
+    /// Removes every cookie called `name`, returning the first of them.
     pub fn remove(&mut self, name: &str) -> Option<Cookie> {
-        self.cookies.remove(name)
+        let first = self.cookies.iter().position(|c| c.name == name)?;
+        let removed = self.cookies.remove(first);
+        self.cookies.retain(|c| c.name != name);
+        Some(removed)
     }
 
     pub fn clear(&mut self) {
@@ -154,11 +358,22 @@ impl CookieJar {
 
     pub fn get_for_url(&self, domain: &str, path: &str) -> Vec<&Cookie> {
         self.cookies
-            .values()
+            .iter()
             .filter(|c| !c.is_expired() && c.matches_domain(domain) && c.matches_path(path))
             .collect()
     }
 
+    /// Cookies to send to `url`, longest path first as RFC 6265 suggests.
+    pub fn cookies_for_url(&self, url: &Url) -> Vec<&Cookie> {
+        let mut matching: Vec<&Cookie> = self
+            .cookies
+            .iter()
+            .filter(|c| !c.is_expired() && c.matches_url(url))
+            .collect();
+        matching.sort_by_key(|c| std::cmp::Reverse(c.path.as_deref().map_or(0, str::len)));
+        matching
+    }
+
     pub fn get_cookie_header(&self, domain: &str, path: &str) -> Option<String> {
         let matching = self.get_for_url(domain, path);
         if matching.is_empty() {
@@ -182,11 +397,11 @@ impl CookieJar {
     }
 
     pub fn all(&self) -> Vec<&Cookie> {
-        self.cookies.values().collect()
+        self.cookies.iter().collect()
     }
 
     pub fn remove_expired(&mut self) {
-        self.cookies.retain(|_, c| !c.is_expired());
+        self.cookies.retain(|c| !c.is_expired());
     }
 }
 
@@ -196,19 +411,132 @@ impl Default for CookieJar {
     }
 }
 
+/// A [`CookieJar`] shared with the HTTP client, which reads and updates it
+/// through reqwest's `CookieStore` hook on every request.
+#[derive(Debug, Default)]
+pub struct SharedCookieJar {
+    jar: RwLock<CookieJar>,
+}
+
+impl SharedCookieJar {
+    pub fn new(jar: CookieJar) -> Self {
+        SharedCookieJar {
+            jar: RwLock::new(jar),
+        }
+    }
+
+    /// A copy of the current cookies, e.g. for saving.
+    pub fn snapshot(&self) -> CookieJar {
+        self.jar.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn with_jar<R>(&self, f: impl FnOnce(&mut CookieJar) -> R) -> R {
+        f(&mut self.jar.write().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+impl reqwest::cookie::CookieStore for SharedCookieJar {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        self.with_jar(|jar| {
+            for header in cookie_headers {
+                if let Ok(header) = header.to_str() {
+                    jar.store(header, url);
+                }
+            }
+        });
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        let jar = self.jar.read().unwrap_or_else(|e| e.into_inner());
+        let header = jar
+            .cookies_for_url(url)
+            .iter()
+            .map(|c| c.to_header_value())
+            .collect::<Vec<_>>()
+            .join("; ");
+        if header.is_empty() {
+            return None;
+        }
+        HeaderValue::from_str(&header).ok()
+    }
+}
+
+/// On-disk cookie file formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CookieFileFormat {
+    /// hurl's own JSON list, which also keeps SameSite.
+    Json,
+    /// The tab-separated `cookies.txt` format used by curl, wget and
+    /// browser export extensions.
+    Netscape,
+}
+
+impl CookieFileFormat {
+    /// Picks JSON for `.json` files and Netscape for everything else.
+    pub fn from_path(path: &std::path::Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => CookieFileFormat::Json,
+            _ => CookieFileFormat::Netscape,
+        }
+    }
+
+    /// Detects the format of a cookie file from its contents.
+    pub fn detect(content: &str) -> Self {
+        match content.trim_start().chars().next() {
+            Some('[') | Some('{') => CookieFileFormat::Json,
+            _ => CookieFileFormat::Netscape,
+        }
+    }
+}
+
+const NETSCAPE_HEADER: &str = "# Netscape HTTP Cookie File";
+const HTTP_ONLY_PREFIX: &str = "#HttpOnly_";
+
 #[derive(Debug)]
 pub struct CookiePersistence {
     file_path: std::path::PathBuf,
+    format: CookieFileFormat,
 }
 
 impl CookiePersistence {
     pub fn new(file_path: impl Into<std::path::PathBuf>) -> Self {
         CookiePersistence {
             file_path: file_path.into(),
+            format: CookieFileFormat::Json,
         }
     }
 
+    /// Sets the format used by `save`. `load` always detects the format.
+    pub fn with_format(mut self, format: CookieFileFormat) -> Self {
+        self.format = format;
+        self
+    }
+
     pub fn save(&self, jar: &CookieJar) -> crate::Result<()> {
+        let content = match self.format {
+            CookieFileFormat::Json => Self::to_json(jar)?,
+            CookieFileFormat::Netscape => Self::to_netscape(jar),
+        };
+        std::fs::write(&self.file_path, content).map_err(crate::Error::Io)?;
+        Ok(())
+    }
+
+    pub fn load(&self) -> crate::Result<CookieJar> {
+        if !self.file_path.exists() {
+            return Ok(CookieJar::new());
+        }
+
+        let content = std::fs::read_to_string(&self.file_path).map_err(crate::Error::Io)?;
+        let mut jar = match CookieFileFormat::detect(&content) {
+            CookieFileFormat::Json => Self::from_json(&content)?,
+            CookieFileFormat::Netscape => Self::from_netscape(&content)?,
+        };
+
+        jar.remove_expired();
+        Ok(jar)
+    }
+
+    fn to_json(jar: &CookieJar) -> crate::Result<String> {
         let cookies = jar
             .all()
             .iter()
@@ -231,19 +559,12 @@ impl CookiePersistence {
             .collect::<Vec<_>>();
 
         let json = serde_json::json!(cookies);
-        let content = serde_json::to_string_pretty(&json)
-            .map_err(|e| crate::Error::Http(format!("Failed to serialize cookies: {}", e)))?;
-        std::fs::write(&self.file_path, content).map_err(crate::Error::Io)?;
-        Ok(())
+        serde_json::to_string_pretty(&json)
+            .map_err(|e| crate::Error::Http(format!("Failed to serialize cookies: {}", e)))
     }
 
-    pub fn load(&self) -> crate::Result<CookieJar> {
-        if !self.file_path.exists() {
-            return Ok(CookieJar::new());
-        }
-
-        let content = std::fs::read_to_string(&self.file_path).map_err(crate::Error::Io)?;
-        let json: Vec<serde_json::Value> = serde_json::from_str(&content)
+    fn from_json(content: &str) -> crate::Result<CookieJar> {
+        let json: Vec<serde_json::Value> = serde_json::from_str(content)
             .map_err(|e| crate::Error::Http(format!("Failed to deserialize cookies: {}", e)))?;
 
         let mut jar = CookieJar::new();
@@ -283,8 +604,387 @@ impl CookiePersistence {
 
             jar.add(cookie);
         }
+        Ok(jar)
+    }
+
+    /// One line per cookie: domain, include-subdomains flag, path, secure
+    /// flag, expiry (0 for session cookies), name and value. HttpOnly
+    /// cookies get curl's `#HttpOnly_` domain prefix. Cookies without a
+    /// domain cannot be expressed and are skipped.
+    fn to_netscape(jar: &CookieJar) -> String {
+        let mut out = format!(
+            "{}\n# Written by hurl. Edit at your own risk.\n\n",
+            NETSCAPE_HEADER
+        );
+        for cookie in jar.all() {
+            let Some(domain) = cookie.domain.as_deref() else {
+                continue;
+            };
+            let flag = |b: bool| if b { "TRUE" } else { "FALSE" };
+            out.push_str(&format!(
+                "{}{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                if cookie.http_only {
+                    HTTP_ONLY_PREFIX
+                } else {
+                    ""
+                },
+                domain,
+                flag(domain.starts_with('.')),
+                cookie.path.as_deref().unwrap_or("/"),
+                flag(cookie.secure),
+                cookie.expires.unwrap_or(0),
+                cookie.name,
+                cookie.value,
+            ));
+        }
+        out
+    }
 
-        jar.remove_expired();
+    fn from_netscape(content: &str) -> crate::Result<CookieJar> {
+        let mut jar = CookieJar::new();
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim_end_matches('\r');
+            let (line, http_only) = match line.strip_prefix(HTTP_ONLY_PREFIX) {
+                Some(rest) => (rest, true),
+                None => (line, false),
+            };
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() < 6 {
+                return Err(crate::Error::Parse(format!(
+                    "cookies.txt line {}: expected 7 tab-separated fields",
+                    index + 1
+                )));
+            }
+            let parse_flag = |field: &str| field.eq_ignore_ascii_case("TRUE");
+            let expires: u64 = fields[4].trim().parse().map_err(|_| {
+                crate::Error::Parse(format!(
+                    "cookies.txt line {}: invalid expiry '{}'",
+                    index + 1,
+                    fields[4]
+                ))
+            })?;
+
+            let host = fields[0].trim_start_matches('.').to_ascii_lowercase();
+            let domain = if parse_flag(fields[1]) {
+                format!(".{}", host)
+            } else {
+                host
+            };
+            let mut cookie = Cookie::new(fields[5], fields.get(6).copied().unwrap_or(""))
+                .with_domain(domain)
+                .with_path(fields[2]);
+            if parse_flag(fields[3]) {
+                cookie = cookie.secure();
+            }
+            if http_only {
+                cookie = cookie.http_only();
+            }
+            if expires != 0 {
+                cookie = cookie.with_expires(expires);
+            }
+
+            jar.add(cookie);
+        }
         Ok(jar)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::client::{ClientConfig, HttpClient};
+    use crate::http::test_server::{MockResponse, TestServer};
+    use std::sync::Arc;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_parse_attributes() {
+        let cookie = Cookie::parse(
+            "id=a3fWa; Max-Age=3600; Path=/docs; Secure; HttpOnly; SameSite=Strict",
+            &url("https://example.com/login"),
+        )
+        .unwrap();
+        assert_eq!(cookie.name, "id");
+        assert_eq!(cookie.value, "a3fWa");
+        assert_eq!(cookie.domain.as_deref(), Some("example.com"));
+        assert_eq!(cookie.path.as_deref(), Some("/docs"));
+        assert!(cookie.secure && cookie.http_only);
+        assert_eq!(cookie.same_site, SameSite::Strict);
+        let expires = cookie.expires.unwrap();
+        assert!(expires > now_secs() + 3500 && expires <= now_secs() + 3600);
+    }
+
+    #[test]
+    fn test_parse_expires_and_default_path() {
+        let cookie = Cookie::parse(
+            "lang=en; Expires=Wed, 21 Oct 2015 07:28:00 GMT",
+            &url("http://example.com/a/b/page"),
+        )
+        .unwrap();
+        assert_eq!(cookie.expires, Some(1445412480));
+        assert!(cookie.is_expired());
+        assert_eq!(cookie.path.as_deref(), Some("/a/b"));
+
+        let cookie = Cookie::parse(
+            "old=1; expires=Wednesday, 21-Oct-15 07:28:00 GMT",
+            &url("http://example.com/"),
+        )
+        .unwrap();
+        assert_eq!(cookie.expires, Some(1445412480));
+    }
+
+    #[test]
+    fn test_max_age_overrides_expires() {
+        let cookie = Cookie::parse(
+            "k=v; Max-Age=60; Expires=Wed, 21 Oct 2015 07:28:00 GMT",
+            &url("http://example.com/"),
+        )
+        .unwrap();
+        assert!(!cookie.is_expired());
+    }
+
+    #[test]
+    fn test_domain_rules() {
+        let origin = url("https://api.example.co.uk/");
+        let cookie = Cookie::parse("a=1; Domain=.example.co.uk", &origin).unwrap();
+        assert_eq!(cookie.domain.as_deref(), Some(".example.co.uk"));
+        assert!(cookie.matches_domain("www.example.co.uk"));
+        assert!(!cookie.matches_domain("badexample.co.uk"));
+
+        assert!(Cookie::parse("a=1; Domain=co.uk", &origin).is_none());
+        assert!(Cookie::parse("a=1; Domain=other.co.uk", &origin).is_none());
+        assert!(Cookie::parse("a=1; Domain=user.github.io", &url("https://github.io/")).is_none());
+        assert!(Cookie::parse("a=1; Domain=github.io", &url("https://me.github.io/")).is_none());
+        assert!(is_public_suffix("com") && !is_public_suffix("example.com"));
+    }
+
+    #[test]
+    fn test_full_public_suffix_list() {
+        for suffix in ["gov.in", "ac.nz", "azurestaticapps.net", "s3.amazonaws.com"] {
+            assert!(is_public_suffix(suffix), "{}", suffix);
+        }
+        // `*.ck` makes every second-level name a suffix, except `www.ck`.
+        assert!(is_public_suffix("anything.ck") && !is_public_suffix("www.ck"));
+        assert!(!is_public_suffix("nic.gov.in") && !is_public_suffix("Example.AC.NZ."));
+
+        let origin = url("https://x.gov.in/");
+        assert!(Cookie::parse("a=1; Domain=gov.in", &origin).is_none());
+        let cookie = Cookie::parse("a=1; Domain=x.gov.in", &origin).unwrap();
+        assert!(!cookie.matches_domain("y.gov.in"));
+    }
+
+    #[test]
+    fn test_secure_and_same_site_rules() {
+        let plain = url("http://example.com/");
+        let tls = url("https://example.com/");
+        assert!(Cookie::parse("a=1; Secure", &plain).is_none());
+        assert!(Cookie::parse("a=1; SameSite=None", &tls).is_none());
+        assert!(Cookie::parse("a=1; SameSite=None; Secure", &tls).is_some());
+        assert!(Cookie::parse("__Secure-a=1", &tls).is_none());
+        assert!(Cookie::parse("__Host-a=1; Secure; Path=/", &tls).is_some());
+        assert!(Cookie::parse("__Host-a=1; Secure; Path=/; Domain=example.com", &tls).is_none());
+        assert!(Cookie::parse("__Host-a=1; Secure; Path=/app", &tls).is_none());
+
+        let secure = Cookie::parse("s=1; Secure", &tls).unwrap();
+        assert!(secure.matches_url(&tls));
+        assert!(!secure.matches_url(&plain));
+    }
+
+    #[test]
+    fn test_path_match_is_segment_aware() {
+        let cookie = Cookie::new("a", "1").with_path("/api");
+        assert!(cookie.matches_path("/api"));
+        assert!(cookie.matches_path("/api/v1"));
+        assert!(!cookie.matches_path("/apis"));
+    }
+
+    #[test]
+    fn test_jar_store_replaces_deletes_and_protects_secure() {
+        let mut jar = CookieJar::new();
+        let tls = url("https://example.com/");
+        let plain = url("http://example.com/");
+
+        assert!(jar.store("a=1", &tls));
+        assert!(jar.store("a=2; Path=/other", &tls));
+        assert!(jar.store("a=3", &tls));
+        assert_eq!(jar.len(), 2);
+        assert_eq!(jar.get("a").unwrap().value, "3");
+
+        assert!(jar.store("a=gone; Max-Age=0", &tls));
+        assert_eq!(jar.len(), 1);
+
+        assert!(jar.store("s=secret; Secure", &tls));
+        assert!(!jar.store("s=hijacked", &plain));
+        assert_eq!(jar.get("s").unwrap().value, "secret");
+    }
+
+    #[test]
+    fn test_cookies_for_url_orders_by_path_length() {
+        let mut jar = CookieJar::new();
+        let origin = url("https://example.com/");
+        jar.store("short=1; Path=/", &origin);
+        jar.store("long=2; Path=/api/v1", &origin);
+        let names: Vec<_> = jar
+            .cookies_for_url(&url("https://example.com/api/v1/users"))
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["long", "short"]);
+    }
+
+    #[test]
+    fn test_persistence_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let persistence = CookiePersistence::new(dir.path().join("jar.json"));
+        let mut jar = CookieJar::new();
+        jar.store(
+            "a=1; Domain=example.com; Max-Age=600",
+            &url("https://www.example.com/"),
+        );
+        jar.store("b=2", &url("https://example.com/"));
+        persistence.save(&jar).unwrap();
+
+        let loaded = persistence.load().unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(
+            loaded.get("a").unwrap().domain.as_deref(),
+            Some(".example.com")
+        );
+        assert!(loaded.get("b").unwrap().expires.is_none());
+    }
+
+    #[test]
+    fn test_netscape_import_from_curl() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cookies.txt");
+        std::fs::write(
+            &path,
+            "# Netscape HTTP Cookie File\n\
+             # https://curl.se/docs/http-cookies.html\n\
+             \n\
+             .example.com\tTRUE\t/\tFALSE\t0\tsession\tabc\n\
+             #HttpOnly_api.example.com\tFALSE\t/v1\tTRUE\t4102444800\ttoken\tx=y\n\
+             old.example.com\tFALSE\t/\tFALSE\t1\tstale\t1\n",
+        )
+        .unwrap();
+
+        let jar = CookiePersistence::new(&path).load().unwrap();
+        assert_eq!(jar.len(), 2);
+
+        let session = jar.get("session").unwrap();
+        assert_eq!(session.domain.as_deref(), Some(".example.com"));
+        assert!(session.expires.is_none());
+        assert!(!session.http_only);
+
+        let token = jar.get("token").unwrap();
+        assert_eq!(token.domain.as_deref(), Some("api.example.com"));
+        assert_eq!(token.path.as_deref(), Some("/v1"));
+        assert_eq!(token.value, "x=y");
+        assert_eq!(token.expires, Some(4102444800));
+        assert!(token.secure && token.http_only);
+
+        let sent = jar.cookies_for_url(&url("https://api.example.com/v1/users"));
+        assert_eq!(sent.len(), 2);
+    }
+
+    #[test]
+    fn test_netscape_round_trip_and_detection() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("jar");
+        let mut jar = CookieJar::new();
+        jar.store(
+            "a=1; Domain=example.com; Max-Age=600; HttpOnly",
+            &url("https://www.example.com/"),
+        );
+        jar.store("b=2; Path=/app; Secure", &url("https://example.com/"));
+        CookiePersistence::new(&path)
+            .with_format(CookieFileFormat::Netscape)
+            .save(&jar)
+            .unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.starts_with("# Netscape HTTP Cookie File"));
+        assert!(content.contains("#HttpOnly_.example.com\tTRUE\t/\tFALSE\t"));
+        assert!(content.contains("example.com\tFALSE\t/app\tTRUE\t0\tb\t2\n"));
+
+        let loaded = CookiePersistence::new(&path).load().unwrap();
+        assert_eq!(loaded.len(), 2);
+        let a = loaded.get("a").unwrap();
+        assert!(a.http_only);
+        assert_eq!(a.expires, jar.get("a").unwrap().expires);
+        assert!(loaded.get("b").unwrap().secure);
+    }
+
+    #[test]
+    fn test_netscape_rejects_malformed_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cookies.txt");
+        std::fs::write(&path, "example.com\tFALSE\t/\n").unwrap();
+        assert!(CookiePersistence::new(&path).load().is_err());
+
+        assert_eq!(
+            CookieFileFormat::from_path(std::path::Path::new("jar.JSON")),
+            CookieFileFormat::Json
+        );
+        assert_eq!(
+            CookieFileFormat::from_path(std::path::Path::new("cookies.txt")),
+            CookieFileFormat::Netscape
+        );
+    }
+
+    #[tokio::test]
+    async fn test_client_uses_shared_jar() {
+        let server = TestServer::sequence(vec![
+            MockResponse::ok("")
+                .header("Set-Cookie", "session=abc; Path=/")
+                .header("Set-Cookie", "theme=dark; Path=/"),
+            MockResponse::ok(""),
+        ])
+        .await;
+        let mut preset = CookieJar::new();
+        preset.add(Cookie::new("preset", "1").with_domain("127.0.0.1"));
+        let jar = Arc::new(SharedCookieJar::new(preset));
+        let client = HttpClient::with_config(ClientConfig {
+            cookie_jar: Some(jar.clone()),
+            ..ClientConfig::default()
+        })
+        .unwrap();
+
+        client.get(server.url("/login")).await.unwrap();
+        client.get(server.url("/home")).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[0].header("Cookie"), Some("preset=1"));
+        let sent = requests[1].header("Cookie").unwrap();
+        for pair in ["preset=1", "session=abc", "theme=dark"] {
+            assert!(sent.contains(pair), "{} missing from {}", pair, sent);
+        }
+        assert_eq!(jar.snapshot().get("session").unwrap().value, "abc");
+    }
+
+    #[tokio::test]
+    async fn test_cookies_can_be_disabled() {
+        let server = TestServer::sequence(vec![
+            MockResponse::ok("").header("Set-Cookie", "session=abc"),
+            MockResponse::ok(""),
+        ])
+        .await;
+        let client = HttpClient::with_config(ClientConfig {
+            enable_cookies: false,
+            ..ClientConfig::default()
+        })
+        .unwrap();
+
+        client.get(server.url("/")).await.unwrap();
+        client.get(server.url("/")).await.unwrap();
+        assert!(client.cookie_jar().is_none());
+        assert_eq!(server.requests()[1].header("Cookie"), None);
+    }
+}
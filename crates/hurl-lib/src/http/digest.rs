@@ -0,0 +1,679 @@
+//! HTTP Digest access authentication (RFC 7616): challenge parsing, response
+//! computation, and per-origin nonce tracking for `HttpClient`.
+
+use super::request::RequestBody;
+use futures_util::TryStreamExt;
+use sha2::{Digest as _, Sha256, Sha512_256};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DigestAlgorithm {
+    Md5,
+    Md5Sess,
+    Sha256,
+    Sha256Sess,
+    Sha512_256,
+    Sha512_256Sess,
+}
+
+impl DigestAlgorithm {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_uppercase().as_str() {
+            "MD5" => Some(DigestAlgorithm::Md5),
+            "MD5-SESS" => Some(DigestAlgorithm::Md5Sess),
+            "SHA-256" => Some(DigestAlgorithm::Sha256),
+            "SHA-256-SESS" => Some(DigestAlgorithm::Sha256Sess),
+            "SHA-512-256" => Some(DigestAlgorithm::Sha512_256),
+            "SHA-512-256-SESS" => Some(DigestAlgorithm::Sha512_256Sess),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DigestAlgorithm::Md5 => "MD5",
+            DigestAlgorithm::Md5Sess => "MD5-sess",
+            DigestAlgorithm::Sha256 => "SHA-256",
+            DigestAlgorithm::Sha256Sess => "SHA-256-sess",
+            DigestAlgorithm::Sha512_256 => "SHA-512-256",
+            DigestAlgorithm::Sha512_256Sess => "SHA-512-256-sess",
+        }
+    }
+
+    pub fn is_session(&self) -> bool {
+        matches!(
+            self,
+            DigestAlgorithm::Md5Sess
+                | DigestAlgorithm::Sha256Sess
+                | DigestAlgorithm::Sha512_256Sess
+        )
+    }
+
+    /// Preference when a server offers several challenges.
+    fn strength(&self) -> u8 {
+        match self {
+            DigestAlgorithm::Md5 | DigestAlgorithm::Md5Sess => 0,
+            DigestAlgorithm::Sha256 | DigestAlgorithm::Sha256Sess => 1,
+            DigestAlgorithm::Sha512_256 | DigestAlgorithm::Sha512_256Sess => 2,
+        }
+    }
+
+    pub fn hash(&self, data: &[u8]) -> String {
+        let mut hasher = self.hasher();
+        hasher.update(data);
+        hasher.finish()
+    }
+
+    /// An incremental form of [`DigestAlgorithm::hash`], for data that
+    /// arrives in chunks.
+    pub fn hasher(&self) -> DigestHasher {
+        match self {
+            DigestAlgorithm::Md5 | DigestAlgorithm::Md5Sess => {
+                DigestHasher::Md5(md5::Context::new())
+            }
+            DigestAlgorithm::Sha256 | DigestAlgorithm::Sha256Sess => {
+                DigestHasher::Sha256(Sha256::new())
+            }
+            DigestAlgorithm::Sha512_256 | DigestAlgorithm::Sha512_256Sess => {
+                DigestHasher::Sha512_256(Sha512_256::new())
+            }
+        }
+    }
+}
+
+pub enum DigestHasher {
+    Md5(md5::Context),
+    Sha256(Sha256),
+    Sha512_256(Sha512_256),
+}
+
+impl DigestHasher {
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            DigestHasher::Md5(context) => context.consume(data),
+            DigestHasher::Sha256(hasher) => hasher.update(data),
+            DigestHasher::Sha512_256(hasher) => hasher.update(data),
+        }
+    }
+
+    /// The lower-case hex digest.
+    pub fn finish(self) -> String {
+        match self {
+            DigestHasher::Md5(context) => format!("{:x}", context.compute()),
+            DigestHasher::Sha256(hasher) => hex(&hasher.finalize()),
+            DigestHasher::Sha512_256(hasher) => hex(&hasher.finalize()),
+        }
+    }
+}
+
+/// H(entity-body) for `qop=auth-int`. Multipart bodies are hashed from
+/// [`Multipart::stream`](super::multipart::Multipart::stream), so file parts
+/// are never held in memory.
+pub(crate) async fn hash_body(
+    algorithm: DigestAlgorithm,
+    body: Option<&RequestBody>,
+) -> crate::Result<String> {
+    let mut hasher = algorithm.hasher();
+    match body {
+        Some(RequestBody::Multipart(form)) => {
+            let mut chunks = form.stream();
+            while let Some(chunk) = chunks.try_next().await? {
+                hasher.update(&chunk);
+            }
+        }
+        Some(body) => hasher.update(&body.to_bytes()?),
+        None => {}
+    }
+    Ok(hasher.finish())
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DigestChallenge {
+    pub realm: String,
+    pub nonce: String,
+    pub opaque: Option<String>,
+    pub qop: Vec<String>,
+    pub algorithm: DigestAlgorithm,
+    pub stale: bool,
+}
+
+/// Credentials and request details that go into one Digest response.
+#[derive(Debug, Clone, Copy)]
+pub struct DigestInput<'a> {
+    pub username: &'a str,
+    pub password: &'a str,
+    pub method: &'a str,
+    pub uri: &'a str,
+    /// H(entity-body) from [`DigestAlgorithm::hash`] with the challenge's
+    /// algorithm. Only `qop=auth-int` uses it; `None` means an empty body.
+    pub body_hash: Option<&'a str>,
+}
+
+impl DigestChallenge {
+    /// Picks the strongest supported Digest challenge out of a
+    /// `WWW-Authenticate` value, which may also carry other schemes.
+    pub fn parse(header: &str) -> Option<Self> {
+        parse_challenges(header)
+            .into_iter()
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("Digest"))
+            .filter_map(|(_, params)| Self::from_params(&params))
+            .max_by_key(|c| c.algorithm.strength())
+    }
+
+    fn from_params(params: &HashMap<String, String>) -> Option<Self> {
+        let algorithm = match params.get("algorithm") {
+            Some(name) => DigestAlgorithm::parse(name)?,
+            None => DigestAlgorithm::Md5,
+        };
+        Some(DigestChallenge {
+            realm: params.get("realm").cloned().unwrap_or_default(),
+            nonce: params.get("nonce")?.clone(),
+            opaque: params.get("opaque").cloned(),
+            qop: params
+                .get("qop")
+                .map(|q| {
+                    q.split(',')
+                        .map(|s| s.trim().to_ascii_lowercase())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            algorithm,
+            stale: params
+                .get("stale")
+                .is_some_and(|s| s.eq_ignore_ascii_case("true")),
+        })
+    }
+
+    /// Prefers `auth`, falling back to `auth-int` when that is all the server
+    /// allows, or to the legacy RFC 2069 form when no qop is offered.
+    pub fn select_qop(&self) -> Option<&'static str> {
+        if self.qop.iter().any(|q| q == "auth") {
+            Some("auth")
+        } else if self.qop.iter().any(|q| q == "auth-int") {
+            Some("auth-int")
+        } else {
+            None
+        }
+    }
+
+    pub fn response(&self, input: DigestInput<'_>, nc: u32, cnonce: &str) -> String {
+        let qop = self.select_qop();
+        compute_response(
+            self.algorithm,
+            input,
+            &self.realm,
+            &self.nonce,
+            qop.map(|q| (q, nc, cnonce)),
+        )
+    }
+
+    /// Builds the `Authorization` header value for the given nonce count.
+    pub fn authorization(&self, input: DigestInput<'_>, nc: u32, cnonce: &str) -> String {
+        let response = self.response(input, nc, cnonce);
+        let mut header = format!(
+            "Digest username=\"{}\", realm=\"{}\", nonce=\"{}\", uri=\"{}\", algorithm={}, response=\"{}\"",
+            quote(input.username),
+            quote(&self.realm),
+            quote(&self.nonce),
+            quote(input.uri),
+            self.algorithm.name(),
+            response
+        );
+        if let Some(qop) = self.select_qop() {
+            header.push_str(&format!(
+                ", qop={}, nc={:08x}, cnonce=\"{}\"",
+                qop,
+                nc,
+                quote(cnonce)
+            ));
+        }
+        if let Some(opaque) = &self.opaque {
+            header.push_str(&format!(", opaque=\"{}\"", quote(opaque)));
+        }
+        header
+    }
+}
+
+/// The `response` value from RFC 7616 section 3.4.1. `qop` carries the chosen
+/// qop, nonce count and client nonce.
+pub fn compute_response(
+    algorithm: DigestAlgorithm,
+    input: DigestInput<'_>,
+    realm: &str,
+    nonce: &str,
+    qop: Option<(&str, u32, &str)>,
+) -> String {
+    let h = |s: String| algorithm.hash(s.as_bytes());
+
+    let mut ha1 = h(format!("{}:{}:{}", input.username, realm, input.password));
+    if algorithm.is_session() {
+        let cnonce = qop.map(|(_, _, c)| c).unwrap_or_default();
+        ha1 = h(format!("{}:{}:{}", ha1, nonce, cnonce));
+    }
+
+    let ha2 = match qop {
+        Some(("auth-int", _, _)) => {
+            let body_hash = match input.body_hash {
+                Some(hash) => hash.to_string(),
+                None => algorithm.hash(b""),
+            };
+            h(format!("{}:{}:{}", input.method, input.uri, body_hash))
+        }
+        _ => h(format!("{}:{}", input.method, input.uri)),
+    };
+
+    match qop {
+        Some((qop, nc, cnonce)) => h(format!(
+            "{}:{}:{:08x}:{}:{}:{}",
+            ha1, nonce, nc, cnonce, qop, ha2
+        )),
+        None => h(format!("{}:{}:{}", ha1, nonce, ha2)),
+    }
+}
+
+fn quote(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Splits a challenge list such as `Basic realm="x", Digest nonce="y", qop="auth"`
+/// into (scheme, lower-cased params) pairs.
+pub fn parse_challenges(header: &str) -> Vec<(String, HashMap<String, String>)> {
+    let mut challenges: Vec<(String, HashMap<String, String>)> = Vec::new();
+    let mut chars = header.chars().peekable();
+
+    loop {
+        while chars.peek().is_some_and(|c| *c == ',' || c.is_whitespace()) {
+            chars.next();
+        }
+        let mut token = String::new();
+        while let Some(&c) = chars.peek() {
+            if c == '=' || c == ',' || c.is_whitespace() {
+                break;
+            }
+            token.push(c);
+            chars.next();
+        }
+        if token.is_empty() {
+            break;
+        }
+        while chars.peek().is_some_and(|c| *c == ' ' || *c == '\t') {
+            chars.next();
+        }
+
+        if chars.peek() != Some(&'=') {
+            challenges.push((token, HashMap::new()));
+            continue;
+        }
+        chars.next();
+        while chars.peek().is_some_and(|c| *c == ' ' || *c == '\t') {
+            chars.next();
+        }
+
+        let mut value = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => value.extend(chars.next()),
+                    '"' => break,
+                    _ => value.push(c),
+                }
+            }
+        } else {
+            while let Some(&c) = chars.peek() {
+                if c == ',' {
+                    break;
+                }
+                value.push(c);
+                chars.next();
+            }
+        }
+
+        match challenges.last_mut() {
+            Some((_, params)) => {
+                params.insert(token.to_ascii_lowercase(), value.trim().to_string());
+            }
+            // A bare param without a scheme (token68 or malformed) is ignored.
+            None => continue,
+        }
+    }
+
+    challenges
+}
+
+#[derive(Debug)]
+struct DigestSession {
+    challenge: DigestChallenge,
+    nc: u32,
+}
+
+/// Remembers the last challenge per origin so later requests can answer
+/// preemptively, incrementing the nonce count each time a nonce is reused.
+#[derive(Debug, Default)]
+pub struct DigestCache {
+    sessions: Mutex<HashMap<String, DigestSession>>,
+}
+
+impl DigestCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn store(&self, origin: &str, challenge: DigestChallenge) {
+        self.sessions
+            .lock()
+            .unwrap()
+            .insert(origin.to_string(), DigestSession { challenge, nc: 0 });
+    }
+
+    /// Returns the `Authorization` value for the next request to `origin`, or
+    /// `None` if no challenge has been seen yet.
+    pub fn authorize(&self, origin: &str, input: DigestInput<'_>) -> Option<String> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions.get_mut(origin)?;
+        session.nc += 1;
+        let cnonce = format!("{:016x}", fastrand::u64(..));
+        Some(session.challenge.authorization(input, session.nc, &cnonce))
+    }
+
+    /// The algorithm to hash the body with when the cached challenge for
+    /// `origin` selects `qop=auth-int`, the only case that needs the body.
+    pub fn auth_int_algorithm(&self, origin: &str) -> Option<DigestAlgorithm> {
+        let sessions = self.sessions.lock().unwrap();
+        let challenge = &sessions.get(origin)?.challenge;
+        (challenge.select_qop() == Some("auth-int")).then_some(challenge.algorithm)
+    }
+
+    pub fn nonce_count(&self, origin: &str) -> Option<u32> {
+        self.sessions.lock().unwrap().get(origin).map(|s| s.nc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::auth::Auth;
+    use crate::http::client::HttpClient;
+    use crate::http::multipart::Multipart;
+    use crate::http::request::RequestBuilder;
+    use crate::http::test_server::{MockResponse, RecordedRequest, TestServer};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    const RFC_REALM: &str = "http-auth@example.org";
+    const RFC_NONCE: &str = "7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v";
+    const RFC_CNONCE: &str = "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ";
+
+    fn rfc_input() -> DigestInput<'static> {
+        DigestInput {
+            username: "Mufasa",
+            password: "Circle of Life",
+            method: "GET",
+            uri: "/dir/index.html",
+            body_hash: None,
+        }
+    }
+
+    #[test]
+    fn test_rfc7616_md5_example() {
+        let response = compute_response(
+            DigestAlgorithm::Md5,
+            rfc_input(),
+            RFC_REALM,
+            RFC_NONCE,
+            Some(("auth", 1, RFC_CNONCE)),
+        );
+        assert_eq!(response, "8ca523f5e9506fed4657c9700eebdbec");
+    }
+
+    #[test]
+    fn test_rfc7616_sha256_example() {
+        let response = compute_response(
+            DigestAlgorithm::Sha256,
+            rfc_input(),
+            RFC_REALM,
+            RFC_NONCE,
+            Some(("auth", 1, RFC_CNONCE)),
+        );
+        assert_eq!(
+            response,
+            "753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1"
+        );
+    }
+
+    #[test]
+    fn test_sha512_256_hash() {
+        assert_eq!(
+            DigestAlgorithm::Sha512_256.hash(b"abc"),
+            "53048e2681941ef99b2e29b76b4c7dabe4c2d0c634fc6d46e0e2f13107e7af23"
+        );
+    }
+
+    #[test]
+    fn test_parse_prefers_strongest_digest_challenge() {
+        let header = "Basic realm=\"basic\", Digest realm=\"r\", nonce=\"n1\", \
+                      algorithm=MD5, qop=\"auth,auth-int\", Digest realm=\"r\", \
+                      nonce=\"n2\", algorithm=SHA-256, opaque=\"op\", stale=TRUE";
+        let challenge = DigestChallenge::parse(header).unwrap();
+        assert_eq!(challenge.algorithm, DigestAlgorithm::Sha256);
+        assert_eq!(challenge.nonce, "n2");
+        assert_eq!(challenge.opaque.as_deref(), Some("op"));
+        assert!(challenge.stale);
+        assert!(challenge.qop.is_empty());
+
+        assert!(DigestChallenge::parse("Basic realm=\"x\"").is_none());
+        assert!(
+            DigestChallenge::parse("Digest realm=\"x\", nonce=\"y\", algorithm=SHA-1").is_none()
+        );
+    }
+
+    #[test]
+    fn test_parse_quoted_escapes() {
+        let challenges = parse_challenges(r#"Digest realm="a \"b\", c", nonce=abc"#);
+        assert_eq!(challenges.len(), 1);
+        assert_eq!(challenges[0].1["realm"], "a \"b\", c");
+        assert_eq!(challenges[0].1["nonce"], "abc");
+    }
+
+    #[test]
+    fn test_cache_increments_nonce_count() {
+        let cache = DigestCache::new();
+        assert!(cache.authorize("http://a.test", rfc_input()).is_none());
+        cache.store(
+            "http://a.test",
+            DigestChallenge::parse("Digest realm=\"r\", nonce=\"n\", qop=\"auth\"").unwrap(),
+        );
+        let first = cache.authorize("http://a.test", rfc_input()).unwrap();
+        let second = cache.authorize("http://a.test", rfc_input()).unwrap();
+        assert!(first.contains("nc=00000001"));
+        assert!(second.contains("nc=00000002"));
+        assert_eq!(cache.nonce_count("http://a.test"), Some(2));
+    }
+
+    /// Verifies the client's Authorization against `password`, issuing
+    /// challenges from `nonces` in turn; nonces before the last are answered
+    /// with `stale=true`.
+    fn verify(
+        req: &RecordedRequest,
+        algorithm: &str,
+        qop: &str,
+        nonces: &[&str],
+        issued: &AtomicUsize,
+    ) -> MockResponse {
+        let current = || nonces[issued.load(Ordering::SeqCst).min(nonces.len() - 1)];
+        let challenge = |nonce: &str, stale: bool| {
+            MockResponse::new(401).header(
+                "WWW-Authenticate",
+                &format!(
+                    "Digest realm=\"test\", nonce=\"{}\", qop=\"{}\", algorithm={}, opaque=\"xyz\"{}",
+                    nonce,
+                    qop,
+                    algorithm,
+                    if stale { ", stale=true" } else { "" }
+                ),
+            )
+        };
+
+        let Some(header) = req.header("Authorization") else {
+            return challenge(current(), false);
+        };
+        let (_, params) = parse_challenges(header).remove(0);
+        let qop_param = params.get("qop").map(String::as_str);
+        let nc = params
+            .get("nc")
+            .map(|nc| u32::from_str_radix(nc, 16).unwrap())
+            .unwrap_or(0);
+        let algorithm = DigestAlgorithm::parse(algorithm).unwrap();
+        let expected = compute_response(
+            algorithm,
+            DigestInput {
+                username: "user",
+                password: "secret",
+                method: &req.method,
+                uri: &req.path,
+                body_hash: Some(&algorithm.hash(&req.body)),
+            },
+            "test",
+            &params["nonce"],
+            qop_param.map(|q| (q, nc, params["cnonce"].as_str())),
+        );
+        if params["response"] != expected || params.get("opaque").map(String::as_str) != Some("xyz")
+        {
+            return challenge(current(), false);
+        }
+        if params["nonce"] != current() {
+            return challenge(current(), true);
+        }
+        if issued.load(Ordering::SeqCst) + 1 < nonces.len() {
+            issued.fetch_add(1, Ordering::SeqCst);
+            return challenge(current(), true);
+        }
+        MockResponse::ok(format!("hello nc={}", nc))
+    }
+
+    async fn digest_server(algorithm: &'static str, qop: &'static str) -> TestServer {
+        let issued = Arc::new(AtomicUsize::new(0));
+        TestServer::start(move |req, _| verify(req, algorithm, qop, &["n1"], &issued)).await
+    }
+
+    async fn get(client: &HttpClient, server: &TestServer, password: &str) -> (u16, String) {
+        let response = client
+            .execute(
+                RequestBuilder::get(server.url("/private?x=1"))
+                    .with_auth(Auth::digest("user", password)),
+            )
+            .await
+            .unwrap();
+        (response.status, response.text())
+    }
+
+    #[tokio::test]
+    async fn test_handshake_for_each_algorithm() {
+        for algorithm in ["MD5", "MD5-sess", "SHA-256", "SHA-512-256", "SHA-256-sess"] {
+            let server = digest_server(algorithm, "auth").await;
+            let client = HttpClient::new().unwrap();
+            assert_eq!(
+                get(&client, &server, "secret").await,
+                (200, "hello nc=1".to_string()),
+                "{}",
+                algorithm
+            );
+            assert_eq!(server.request_count(), 2);
+            assert!(server.requests()[0].header("Authorization").is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_auth_int_covers_body() {
+        let server = digest_server("SHA-256", "auth-int").await;
+        let response = HttpClient::new()
+            .unwrap()
+            .execute(
+                RequestBuilder::post(server.url("/upload"))
+                    .text_body("payload")
+                    .with_auth(Auth::digest("user", "secret")),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status, 200);
+        assert!(server.requests()[1]
+            .header("Authorization")
+            .unwrap()
+            .contains("qop=auth-int"));
+    }
+
+    #[tokio::test]
+    async fn test_auth_int_hashes_streamed_multipart_body() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("upload.bin");
+        std::fs::write(&path, vec![7u8; 256 * 1024]).unwrap();
+        let form = Multipart::new().text("kind", "blob").file("file", &path);
+        let length = form.content_length().unwrap();
+        let expected = DigestAlgorithm::Sha256.hash(&form.to_bytes().unwrap());
+        assert_eq!(
+            hash_body(
+                DigestAlgorithm::Sha256,
+                Some(&RequestBody::Multipart(form.clone()))
+            )
+            .await
+            .unwrap(),
+            expected
+        );
+
+        let server = digest_server("SHA-256", "auth-int").await;
+        let response = HttpClient::new()
+            .unwrap()
+            .execute(
+                RequestBuilder::post(server.url("/upload"))
+                    .multipart_body(form)
+                    .with_auth(Auth::digest("user", "secret")),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(server.requests()[1].body.len() as u64, length);
+    }
+
+    #[tokio::test]
+    async fn test_nonce_reused_with_incrementing_count() {
+        let server = digest_server("MD5", "auth").await;
+        let client = HttpClient::new().unwrap();
+
+        assert_eq!(get(&client, &server, "secret").await.1, "hello nc=1");
+        assert_eq!(get(&client, &server, "secret").await.1, "hello nc=2");
+        // The second request answered preemptively, without a new 401.
+        assert_eq!(server.request_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_stale_nonce_is_renewed() {
+        let issued = Arc::new(AtomicUsize::new(0));
+        let server = TestServer::start(move |req, _| {
+            verify(req, "SHA-256", "auth", &["old", "new"], &issued)
+        })
+        .await;
+        let client = HttpClient::new().unwrap();
+
+        assert_eq!(get(&client, &server, "secret").await.0, 200);
+        let nonces: Vec<String> = server
+            .requests()
+            .iter()
+            .filter_map(|r| r.header("Authorization"))
+            .map(|h| parse_challenges(h).remove(0).1["nonce"].clone())
+            .collect();
+        assert_eq!(nonces, vec!["old", "new"]);
+    }
+
+    #[tokio::test]
+    async fn test_wrong_password_returns_401() {
+        let server = digest_server("MD5", "auth").await;
+        let client = HttpClient::new().unwrap();
+        assert_eq!(get(&client, &server, "wrong").await.0, 401);
+        assert_eq!(server.request_count(), 2);
+    }
+}
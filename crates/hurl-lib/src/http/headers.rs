@@ -0,0 +1,216 @@
+//! An ordered, case-insensitive header multimap. Repeated fields such as
+//! `Set-Cookie` or `Link` keep every value, in the order they were added.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Headers {
+    entries: Vec<(String, String)>,
+}
+
+impl Headers {
+    pub fn new() -> Self {
+        Headers {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Adds a value, keeping any existing values for the same name.
+    pub fn append(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.entries.push((name.into(), value.into()));
+    }
+
+    /// Sets a header, replacing every existing value for that name.
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        let name = name.into();
+        match self.position(&name) {
+            Some(index) => {
+                let mut i = 0;
+                self.entries.retain(|(k, _)| {
+                    i += 1;
+                    i - 1 <= index || !k.eq_ignore_ascii_case(&name)
+                });
+                self.entries[index] = (name, value.into());
+            }
+            None => self.entries.push((name, value.into())),
+        }
+    }
+
+    /// Removes every value for `name`.
+    pub fn remove(&mut self, name: &str) {
+        self.entries.retain(|(k, _)| !k.eq_ignore_ascii_case(name));
+    }
+
+    /// The first value for `name`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.position(name).map(|i| self.entries[i].1.as_str())
+    }
+
+    /// Every value for `name`, in order.
+    pub fn get_all(&self, name: &str) -> Vec<&str> {
+        self.entries
+            .iter()
+            .filter(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+            .collect()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.position(name).is_some()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Every value, in order, for rewriting in place.
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut String> {
+        self.entries.iter_mut().map(|(_, v)| v)
+    }
+
+    /// Number of header lines, counting repeated names separately.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|(k, _)| k.eq_ignore_ascii_case(name))
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> FromIterator<(K, V)> for Headers {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut headers = Headers::new();
+        headers.extend(iter);
+        headers
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> Extend<(K, V)> for Headers {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (name, value) in iter {
+            self.append(name, value);
+        }
+    }
+}
+
+impl From<HashMap<String, String>> for Headers {
+    fn from(map: HashMap<String, String>) -> Self {
+        map.into_iter().collect()
+    }
+}
+
+impl From<Vec<(String, String)>> for Headers {
+    fn from(entries: Vec<(String, String)>) -> Self {
+        Headers { entries }
+    }
+}
+
+impl<'a> IntoIterator for &'a Headers {
+    type Item = (&'a str, &'a str);
+    type IntoIter = Box<dyn Iterator<Item = (&'a str, &'a str)> + 'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Box::new(self.iter())
+    }
+}
+
+impl IntoIterator for Headers {
+    type Item = (String, String);
+    type IntoIter = std::vec::IntoIter<(String, String)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+/// Serialized as a list of `[name, value]` pairs; a plain JSON object (the
+/// format used before repeated headers were kept) is still accepted.
+impl Serialize for Headers {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.entries.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Headers {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Pairs(Vec<(String, String)>),
+            Map(HashMap<String, String>),
+        }
+
+        Ok(match Repr::deserialize(deserializer)? {
+            Repr::Pairs(entries) => Headers::from(entries),
+            Repr::Map(map) => Headers::from(map),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Headers {
+        [
+            ("Set-Cookie", "a=1"),
+            ("Content-Type", "text/html"),
+            ("set-cookie", "b=2"),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    #[test]
+    fn test_repeated_values_are_kept_in_order() {
+        let headers = sample();
+        assert_eq!(headers.len(), 3);
+        assert_eq!(headers.get("SET-COOKIE"), Some("a=1"));
+        assert_eq!(headers.get_all("Set-Cookie"), vec!["a=1", "b=2"]);
+        assert!(headers.get_all("Link").is_empty());
+        assert!(headers.contains("content-type"));
+    }
+
+    #[test]
+    fn test_insert_replaces_all_values_in_place() {
+        let mut headers = sample();
+        headers.insert("SET-COOKIE", "c=3");
+        assert_eq!(
+            headers.iter().collect::<Vec<_>>(),
+            vec![("SET-COOKIE", "c=3"), ("Content-Type", "text/html")]
+        );
+
+        headers.insert("Accept", "*/*");
+        assert_eq!(headers.get("accept"), Some("*/*"));
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut headers = sample();
+        headers.remove("set-cookie");
+        assert_eq!(headers.len(), 1);
+        assert!(!headers.contains("Set-Cookie"));
+    }
+
+    #[test]
+    fn test_serde_round_trip_and_legacy_map() {
+        let headers = sample();
+        let json = serde_json::to_string(&headers).unwrap();
+        assert_eq!(
+            json,
+            r#"[["Set-Cookie","a=1"],["Content-Type","text/html"],["set-cookie","b=2"]]"#
+        );
+        assert_eq!(serde_json::from_str::<Headers>(&json).unwrap(), headers);
+
+        let legacy: Headers = serde_json::from_str(r#"{"Accept": "text/plain"}"#).unwrap();
+        assert_eq!(legacy.get("accept"), Some("text/plain"));
+    }
+}
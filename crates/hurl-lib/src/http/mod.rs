@@ -3,7 +3,19 @@ pub mod chaining;
 pub mod client;
 pub mod compression;
 pub mod cookies;
+pub mod digest;
+pub mod headers;
+pub mod multipart;
+pub mod oauth2;
+pub mod proxy;
+pub mod rate_limit;
+pub mod redirect;
 pub mod request;
 pub mod response;
+pub mod retry;
 pub mod security;
 pub mod tests;
+pub mod tls;
+
+#[cfg(test)]
+pub(crate) mod test_server;
@@ -0,0 +1,349 @@
+//! `multipart/form-data` bodies (RFC 7578). File parts are read from disk in
+//! chunks while the request is sent, so large uploads are never buffered.
+
+use bytes::Bytes;
+use futures_util::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use std::path::{Path, PathBuf};
+use tokio_util::io::ReaderStream;
+
+#[derive(Debug, Clone)]
+pub enum PartSource {
+    Text(String),
+    Bytes(Vec<u8>),
+    File(PathBuf),
+}
+
+#[derive(Debug, Clone)]
+pub struct Part {
+    pub name: String,
+    pub filename: Option<String>,
+    pub content_type: Option<String>,
+    pub source: PartSource,
+}
+
+impl Part {
+    pub fn text(name: impl Into<String>, value: impl Into<String>) -> Self {
+        Part {
+            name: name.into(),
+            filename: None,
+            content_type: None,
+            source: PartSource::Text(value.into()),
+        }
+    }
+
+    pub fn bytes(name: impl Into<String>, bytes: Vec<u8>) -> Self {
+        Part {
+            name: name.into(),
+            filename: None,
+            content_type: Some("application/octet-stream".to_string()),
+            source: PartSource::Bytes(bytes),
+        }
+    }
+
+    /// A file part named after the file, with a content type guessed from
+    /// its extension.
+    pub fn file(name: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        Part {
+            name: name.into(),
+            filename: path.file_name().map(|f| f.to_string_lossy().to_string()),
+            content_type: Some(guess_content_type(&path).to_string()),
+            source: PartSource::File(path),
+        }
+    }
+
+    pub fn file_name(mut self, filename: impl Into<String>) -> Self {
+        self.filename = Some(filename.into());
+        self
+    }
+
+    pub fn mime(mut self, content_type: impl Into<String>) -> Self {
+        self.content_type = Some(content_type.into());
+        self
+    }
+
+    fn headers(&self, boundary: &str) -> String {
+        let mut head = format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"{}\"",
+            boundary,
+            escape(&self.name)
+        );
+        if let Some(filename) = &self.filename {
+            head.push_str(&format!("; filename=\"{}\"", escape(filename)));
+        }
+        head.push_str("\r\n");
+        if let Some(content_type) = &self.content_type {
+            head.push_str(&format!("Content-Type: {}\r\n", content_type));
+        }
+        head.push_str("\r\n");
+        head
+    }
+
+    /// Size of the part's contents, without its headers.
+    pub(crate) fn len(&self) -> crate::Result<u64> {
+        Ok(match &self.source {
+            PartSource::Text(text) => text.len() as u64,
+            PartSource::Bytes(bytes) => bytes.len() as u64,
+            PartSource::File(path) => std::fs::metadata(path)
+                .map_err(|e| file_error(path, e))?
+                .len(),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Multipart {
+    boundary: String,
+    parts: Vec<Part>,
+}
+
+impl Multipart {
+    pub fn new() -> Self {
+        Multipart {
+            boundary: format!("------------------------hurl{:016x}", fastrand::u64(..)),
+            parts: Vec::new(),
+        }
+    }
+
+    pub fn text(self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.part(Part::text(name, value))
+    }
+
+    pub fn file(self, name: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        self.part(Part::file(name, path))
+    }
+
+    pub fn part(mut self, part: Part) -> Self {
+        self.parts.push(part);
+        self
+    }
+
+    pub fn parts(&self) -> &[Part] {
+        &self.parts
+    }
+
+    pub fn boundary(&self) -> &str {
+        &self.boundary
+    }
+
+    pub fn content_type(&self) -> String {
+        format!("multipart/form-data; boundary={}", self.boundary)
+    }
+
+    fn closing(&self) -> String {
+        format!("--{}--\r\n", self.boundary)
+    }
+
+    /// Total encoded size, so the body can be sent with a Content-Length
+    /// instead of chunked encoding.
+    pub fn content_length(&self) -> crate::Result<u64> {
+        let mut total = self.closing().len() as u64;
+        for part in &self.parts {
+            total += part.headers(&self.boundary).len() as u64 + part.len()? + 2;
+        }
+        Ok(total)
+    }
+
+    /// Checks that every file part can be opened before anything is sent.
+    pub fn validate(&self) -> crate::Result<()> {
+        for part in &self.parts {
+            if let PartSource::File(path) = &part.source {
+                std::fs::File::open(path).map_err(|e| file_error(path, e))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Encodes the whole body in memory. Prefer [`Multipart::stream`] for
+    /// sending; this exists for callers that need the exact bytes.
+    pub fn to_bytes(&self) -> crate::Result<Vec<u8>> {
+        let mut out = Vec::new();
+        for part in &self.parts {
+            out.extend_from_slice(part.headers(&self.boundary).as_bytes());
+            match &part.source {
+                PartSource::Text(text) => out.extend_from_slice(text.as_bytes()),
+                PartSource::Bytes(bytes) => out.extend_from_slice(bytes),
+                PartSource::File(path) => {
+                    out.extend(std::fs::read(path).map_err(|e| file_error(path, e))?)
+                }
+            }
+            out.extend_from_slice(b"\r\n");
+        }
+        out.extend_from_slice(self.closing().as_bytes());
+        Ok(out)
+    }
+
+    pub fn stream(&self) -> BoxStream<'static, std::io::Result<Bytes>> {
+        let mut segments: Vec<BoxStream<'static, std::io::Result<Bytes>>> = Vec::new();
+        let chunk = |data: Bytes| stream::once(async move { Ok(data) }).boxed();
+
+        for part in &self.parts {
+            segments.push(chunk(Bytes::from(part.headers(&self.boundary))));
+            segments.push(match &part.source {
+                PartSource::Text(text) => chunk(Bytes::from(text.clone())),
+                PartSource::Bytes(bytes) => chunk(Bytes::from(bytes.clone())),
+                PartSource::File(path) => {
+                    let path = path.clone();
+                    stream::once(async move { tokio::fs::File::open(path).await })
+                        .map_ok(ReaderStream::new)
+                        .try_flatten()
+                        .boxed()
+                }
+            });
+            segments.push(chunk(Bytes::from_static(b"\r\n")));
+        }
+        segments.push(chunk(Bytes::from(self.closing())));
+
+        stream::iter(segments).flatten().boxed()
+    }
+}
+
+impl Default for Multipart {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn file_error(path: &Path, e: std::io::Error) -> crate::Error {
+    crate::Error::Http(format!("cannot read {}: {}", path.display(), e))
+}
+
+/// Quotes a Content-Disposition parameter the way browsers do.
+fn escape(value: &str) -> String {
+    value
+        .replace('"', "%22")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+pub fn guess_content_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("txt") => "text/plain",
+        Some("html" | "htm") => "text/html",
+        Some("css") => "text/css",
+        Some("csv") => "text/csv",
+        Some("js") => "application/javascript",
+        Some("json") => "application/json",
+        Some("xml") => "application/xml",
+        Some("pdf") => "application/pdf",
+        Some("zip") => "application/zip",
+        Some("gz") => "application/gzip",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("svg") => "image/svg+xml",
+        Some("webp") => "image/webp",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::client::HttpClient;
+    use crate::http::request::RequestBuilder;
+    use crate::http::test_server::{MockResponse, TestServer};
+
+    fn temp_file(name: &str, contents: &[u8]) -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(name);
+        std::fs::write(&path, contents).unwrap();
+        (dir, path)
+    }
+
+    #[test]
+    fn test_encoding() {
+        let form = Multipart::new()
+            .text("title", "Holiday")
+            .part(Part::bytes("blob", vec![1, 2]).file_name("a\"b.bin"));
+        let body = String::from_utf8_lossy(&form.to_bytes().unwrap()).to_string();
+        let b = form.boundary();
+
+        assert_eq!(
+            body,
+            format!(
+                "--{b}\r\nContent-Disposition: form-data; name=\"title\"\r\n\r\nHoliday\r\n\
+                 --{b}\r\nContent-Disposition: form-data; name=\"blob\"; filename=\"a%22b.bin\"\r\n\
+                 Content-Type: application/octet-stream\r\n\r\n\u{1}\u{2}\r\n--{b}--\r\n"
+            )
+        );
+        assert_eq!(form.content_length().unwrap(), body.len() as u64);
+    }
+
+    #[test]
+    fn test_file_part_defaults() {
+        let part = Part::file("upload", "/tmp/photo.PNG");
+        assert_eq!(part.filename.as_deref(), Some("photo.PNG"));
+        assert_eq!(part.content_type.as_deref(), Some("image/png"));
+        let part = part.mime("image/x-custom").file_name("renamed.png");
+        assert_eq!(part.content_type.as_deref(), Some("image/x-custom"));
+        assert_eq!(part.filename.as_deref(), Some("renamed.png"));
+    }
+
+    #[test]
+    fn test_missing_file_is_reported() {
+        let form = Multipart::new().file("f", "/nonexistent/upload.bin");
+        assert!(form.validate().is_err());
+        assert!(form.content_length().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stream_matches_buffered_encoding() {
+        let contents: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        let (_dir, path) = temp_file("data.bin", &contents);
+        let form = Multipart::new().text("a", "1").file("file", &path);
+
+        let streamed: Vec<u8> = form
+            .stream()
+            .try_fold(Vec::new(), |mut acc, chunk| async move {
+                acc.extend_from_slice(&chunk);
+                Ok(acc)
+            })
+            .await
+            .unwrap();
+        assert_eq!(streamed, form.to_bytes().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_upload_through_client() {
+        let (_dir, path) = temp_file("notes.txt", b"line one\nline two\n");
+        let server = TestServer::start(|_, _| MockResponse::ok("stored")).await;
+
+        let response = HttpClient::new()
+            .unwrap()
+            .execute(
+                RequestBuilder::post(server.url("/upload")).multipart_body(
+                    Multipart::new()
+                        .text("description", "notes")
+                        .part(Part::file("doc", &path).mime("text/markdown")),
+                ),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.body, "stored");
+
+        let request = &server.requests()[0];
+        let content_type = request.header("Content-Type").unwrap();
+        let boundary = content_type
+            .strip_prefix("multipart/form-data; boundary=")
+            .unwrap();
+        assert_eq!(
+            request
+                .header("Content-Length")
+                .map(|l| l.parse::<usize>().unwrap()),
+            Some(request.body.len())
+        );
+
+        let body = request.body_text();
+        assert!(body.starts_with(&format!("--{}\r\n", boundary)));
+        assert!(body.contains("name=\"description\"\r\n\r\nnotes\r\n"));
+        assert!(body.contains(
+            "name=\"doc\"; filename=\"notes.txt\"\r\nContent-Type: text/markdown\r\n\r\nline one\nline two\n\r\n"
+        ));
+        assert!(body.ends_with(&format!("--{}--\r\n", boundary)));
+    }
+}
@@ -0,0 +1,769 @@
+//! OAuth 2.0 token acquisition (RFC 6749, RFC 8628) with a cached token that
+//! `HttpClient` refreshes on expiry or when the server rejects it.
+
+use super::auth::Auth;
+use super::client::HttpClient;
+use super::request::RequestBuilder;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+/// Tokens this close to expiry are treated as expired, so a request never
+/// leaves with a token that lapses in flight.
+const EXPIRY_LEEWAY_SECS: i64 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OAuth2GrantType {
+    #[default]
+    ClientCredentials,
+    RefreshToken,
+    DeviceCode,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OAuth2Config {
+    pub token_url: String,
+    pub client_id: String,
+    #[serde(default)]
+    pub client_secret: Option<String>,
+    #[serde(default)]
+    pub scope: Option<String>,
+    #[serde(default)]
+    pub grant: OAuth2GrantType,
+    /// Seed token for the `refresh_token` grant.
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+    /// Required for the `device_code` grant.
+    #[serde(default)]
+    pub device_authorization_url: Option<String>,
+}
+
+impl OAuth2Config {
+    pub fn client_credentials(
+        token_url: impl Into<String>,
+        client_id: impl Into<String>,
+        client_secret: impl Into<String>,
+    ) -> Self {
+        OAuth2Config {
+            token_url: token_url.into(),
+            client_id: client_id.into(),
+            client_secret: Some(client_secret.into()),
+            scope: None,
+            grant: OAuth2GrantType::ClientCredentials,
+            refresh_token: None,
+            device_authorization_url: None,
+        }
+    }
+
+    pub fn with_scope(mut self, scope: impl Into<String>) -> Self {
+        self.scope = Some(scope.into());
+        self
+    }
+
+    pub fn validate(&self) -> crate::Result<()> {
+        if self.token_url.is_empty() || self.client_id.is_empty() {
+            return Err(crate::Error::Config(
+                "oauth2 requires token_url and client_id".into(),
+            ));
+        }
+        match self.grant {
+            OAuth2GrantType::RefreshToken if self.refresh_token.is_none() => Err(
+                crate::Error::Config("oauth2 refresh_token grant requires refresh_token".into()),
+            ),
+            OAuth2GrantType::DeviceCode if self.device_authorization_url.is_none() => {
+                Err(crate::Error::Config(
+                    "oauth2 device_code grant requires device_authorization_url".into(),
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OAuth2Token {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub refresh_token: Option<String>,
+}
+
+impl OAuth2Token {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|at| at - ChronoDuration::seconds(EXPIRY_LEEWAY_SECS) <= Utc::now())
+    }
+
+    pub fn to_auth(&self) -> Auth {
+        Auth::OAuth2 {
+            token: self.access_token.clone(),
+            token_type: self.token_type.clone(),
+            expires_in: self
+                .expires_at
+                .map(|at| (at - Utc::now()).num_seconds().max(0) as u64),
+            refresh_token: self.refresh_token.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default = "default_token_type")]
+    token_type: String,
+    expires_in: Option<u64>,
+    refresh_token: Option<String>,
+}
+
+fn default_token_type() -> String {
+    "Bearer".to_string()
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    error: String,
+    error_description: Option<String>,
+}
+
+/// What the user must do to approve a device-code login.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeviceAuthorization {
+    pub device_code: String,
+    pub user_code: String,
+    pub verification_uri: String,
+    #[serde(default)]
+    pub verification_uri_complete: Option<String>,
+    pub expires_in: u64,
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+}
+
+fn default_interval() -> u64 {
+    5
+}
+
+type DevicePrompt = dyn Fn(&DeviceAuthorization) + Send + Sync;
+
+pub struct OAuth2Provider {
+    config: OAuth2Config,
+    /// Only held to read or replace the cached token, never across a request.
+    token: Mutex<Option<OAuth2Token>>,
+    /// Held while a token is fetched, so concurrent callers share one fetch
+    /// (and one device-code prompt) instead of starting their own.
+    acquiring: Mutex<()>,
+    device_prompt: Option<Arc<DevicePrompt>>,
+}
+
+impl std::fmt::Debug for OAuth2Provider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OAuth2Provider")
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
+}
+
+impl OAuth2Provider {
+    pub fn new(config: OAuth2Config) -> crate::Result<Self> {
+        config.validate()?;
+        Ok(OAuth2Provider {
+            config,
+            token: Mutex::new(None),
+            acquiring: Mutex::new(()),
+            device_prompt: None,
+        })
+    }
+
+    /// Called with the user code and verification URL during a device-code login.
+    pub fn with_device_prompt(
+        mut self,
+        prompt: impl Fn(&DeviceAuthorization) + Send + Sync + 'static,
+    ) -> Self {
+        self.device_prompt = Some(Arc::new(prompt));
+        self
+    }
+
+    pub fn config(&self) -> &OAuth2Config {
+        &self.config
+    }
+
+    pub async fn cached_token(&self) -> Option<OAuth2Token> {
+        self.token.lock().await.clone()
+    }
+
+    /// Returns a usable token, refreshing or fetching one if needed. While a
+    /// token is being fetched, which for the device-code grant lasts until
+    /// the user approves, other callers wait for that fetch and share its
+    /// result; `cached_token` and `invalidate` do not wait.
+    pub async fn token(&self, client: &HttpClient) -> crate::Result<OAuth2Token> {
+        if let Some(token) = self.fresh_token().await {
+            return Ok(token);
+        }
+        let _acquiring = self.acquiring.lock().await;
+        // Another caller may have fetched a token while we waited.
+        if let Some(token) = self.fresh_token().await {
+            return Ok(token);
+        }
+
+        let refresh_token = self
+            .token
+            .lock()
+            .await
+            .as_ref()
+            .and_then(|t| t.refresh_token.clone())
+            .or_else(|| self.config.refresh_token.clone());
+        let token = match refresh_token {
+            Some(refresh_token) => match self.refresh(client, &refresh_token).await {
+                Ok(token) => token,
+                // An expired or revoked refresh token falls back to the
+                // configured grant, unless refreshing is all we can do.
+                Err(_) if self.config.grant != OAuth2GrantType::RefreshToken => {
+                    self.acquire(client).await?
+                }
+                Err(e) => return Err(e),
+            },
+            None => self.acquire(client).await?,
+        };
+        *self.token.lock().await = Some(token.clone());
+        Ok(token)
+    }
+
+    async fn fresh_token(&self) -> Option<OAuth2Token> {
+        self.token.lock().await.clone().filter(|t| !t.is_expired())
+    }
+
+    /// Drops `rejected` so the next `token()` call refreshes. A token that was
+    /// already replaced by a concurrent request is left alone.
+    pub async fn invalidate(&self, rejected: &OAuth2Token) {
+        let mut cached = self.token.lock().await;
+        if let Some(token) = cached.as_mut() {
+            if token.access_token == rejected.access_token {
+                token.expires_at = Some(Utc::now() - ChronoDuration::seconds(1));
+            }
+        }
+    }
+
+    async fn acquire(&self, client: &HttpClient) -> crate::Result<OAuth2Token> {
+        match self.config.grant {
+            OAuth2GrantType::ClientCredentials => {
+                let mut form = self.base_form("client_credentials");
+                if let Some(scope) = &self.config.scope {
+                    form.insert("scope".into(), scope.clone());
+                }
+                Ok(self.request_token(client, form).await?)
+            }
+            OAuth2GrantType::RefreshToken => Err(crate::Error::Http(
+                "oauth2 refresh token was rejected; a new one is required".into(),
+            )),
+            OAuth2GrantType::DeviceCode => self.device_flow(client).await,
+        }
+    }
+
+    async fn refresh(
+        &self,
+        client: &HttpClient,
+        refresh_token: &str,
+    ) -> crate::Result<OAuth2Token> {
+        let mut form = self.base_form("refresh_token");
+        form.insert("refresh_token".into(), refresh_token.to_string());
+        let mut token = self.request_token(client, form).await?;
+        // Servers may omit the refresh token when it does not rotate.
+        if token.refresh_token.is_none() {
+            token.refresh_token = Some(refresh_token.to_string());
+        }
+        Ok(token)
+    }
+
+    async fn device_flow(&self, client: &HttpClient) -> crate::Result<OAuth2Token> {
+        let url = self
+            .config
+            .device_authorization_url
+            .as_deref()
+            .unwrap_or_default();
+        let mut form = HashMap::new();
+        form.insert("client_id".to_string(), self.config.client_id.clone());
+        if let Some(scope) = &self.config.scope {
+            form.insert("scope".into(), scope.clone());
+        }
+        let response = client.send_once(&self.client_request(url, form)).await?;
+        if !response.is_success() {
+            return Err(token_error(&response.text(), response.status).into());
+        }
+        let device: DeviceAuthorization = response.parse_json()?;
+        if let Some(prompt) = &self.device_prompt {
+            prompt(&device);
+        }
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(device.expires_in);
+        let mut interval = Duration::from_secs(device.interval);
+        loop {
+            tokio::time::sleep(interval).await;
+            if std::time::Instant::now() >= deadline {
+                return Err(crate::Error::Http("oauth2 device code expired".into()));
+            }
+
+            let mut form = self.base_form("urn:ietf:params:oauth:grant-type:device_code");
+            form.insert("device_code".into(), device.device_code.clone());
+            match self.request_token(client, form).await {
+                Ok(token) => return Ok(token),
+                Err(TokenError::Refused { code, .. }) if code == "authorization_pending" => {}
+                Err(TokenError::Refused { code, .. }) if code == "slow_down" => {
+                    interval += Duration::from_secs(5);
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    fn base_form(&self, grant_type: &str) -> HashMap<String, String> {
+        let mut form = HashMap::new();
+        form.insert("grant_type".to_string(), grant_type.to_string());
+        if self.config.client_secret.is_none() {
+            form.insert("client_id".to_string(), self.config.client_id.clone());
+        }
+        form
+    }
+
+    /// Confidential clients authenticate with HTTP Basic (RFC 6749 2.3.1).
+    fn client_request(&self, url: &str, form: HashMap<String, String>) -> RequestBuilder {
+        let request = RequestBuilder::post(url)
+            .header("Accept", "application/json")
+            .form_body(form);
+        match &self.config.client_secret {
+            Some(secret) => request.basic_auth(&self.config.client_id, secret),
+            None => request,
+        }
+    }
+
+    async fn request_token(
+        &self,
+        client: &HttpClient,
+        form: HashMap<String, String>,
+    ) -> Result<OAuth2Token, TokenError> {
+        let response = client
+            .send_once(&self.client_request(&self.config.token_url, form))
+            .await?;
+        if !response.is_success() {
+            return Err(token_error(&response.text(), response.status));
+        }
+        let body: TokenResponse = response.parse_json()?;
+        Ok(OAuth2Token {
+            access_token: body.access_token,
+            token_type: body.token_type,
+            expires_at: body
+                .expires_in
+                .map(|secs| Utc::now() + ChronoDuration::seconds(secs as i64)),
+            refresh_token: body.refresh_token,
+        })
+    }
+}
+
+/// A failed token request. `Refused` is an RFC 6749 section 5.2 error
+/// response, with its `error` code kept for callers that act on it.
+enum TokenError {
+    Refused { code: String, error: crate::Error },
+    Failed(crate::Error),
+}
+
+impl From<crate::Error> for TokenError {
+    fn from(error: crate::Error) -> Self {
+        TokenError::Failed(error)
+    }
+}
+
+impl From<TokenError> for crate::Error {
+    fn from(error: TokenError) -> Self {
+        match error {
+            TokenError::Refused { error, .. } | TokenError::Failed(error) => error,
+        }
+    }
+}
+
+fn token_error(body: &str, status: u16) -> TokenError {
+    match serde_json::from_str::<ErrorResponse>(body) {
+        Ok(e) => TokenError::Refused {
+            error: crate::Error::Http(format!(
+                "oauth2 token request failed: {}{}",
+                e.error,
+                e.error_description
+                    .map(|d| format!(" ({})", d))
+                    .unwrap_or_default()
+            )),
+            code: e.error,
+        },
+        Err(_) => TokenError::Failed(crate::Error::Http(format!(
+            "oauth2 token request failed with HTTP {}",
+            status
+        ))),
+    }
+}
+
+/// True when a 401's `WWW-Authenticate` says the bearer token itself was bad
+/// (RFC 6750 section 3.1), as opposed to missing or insufficient.
+pub fn is_invalid_token(www_authenticate: &str) -> bool {
+    super::digest::parse_challenges(www_authenticate)
+        .iter()
+        .any(|(scheme, params)| {
+            scheme.eq_ignore_ascii_case("Bearer")
+                && params.get("error").is_some_and(|e| e == "invalid_token")
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::client::ClientConfig;
+    use crate::http::test_server::{MockResponse, RecordedRequest, TestServer};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn form(req: &RecordedRequest) -> HashMap<String, String> {
+        serde_urlencoded::from_bytes(&req.body).unwrap()
+    }
+
+    fn token_json(token: &str, expires_in: i64, refresh: Option<&str>) -> MockResponse {
+        let mut body = serde_json::json!({
+            "access_token": token,
+            "token_type": "Bearer",
+            "expires_in": expires_in,
+        });
+        if let Some(refresh) = refresh {
+            body["refresh_token"] = refresh.into();
+        }
+        MockResponse::ok(body.to_string()).header("Content-Type", "application/json")
+    }
+
+    fn client_with(provider: OAuth2Provider) -> (HttpClient, Arc<OAuth2Provider>) {
+        let provider = Arc::new(provider);
+        let client = HttpClient::with_config(ClientConfig {
+            max_retries: 0,
+            oauth2: Some(provider.clone()),
+            ..ClientConfig::default()
+        })
+        .unwrap();
+        (client, provider)
+    }
+
+    #[test]
+    fn test_invalid_token_detection() {
+        assert!(is_invalid_token(
+            r#"Bearer realm="api", error="invalid_token", error_description="expired""#
+        ));
+        assert!(!is_invalid_token(r#"Bearer realm="api""#));
+        assert!(!is_invalid_token(r#"Bearer error="insufficient_scope""#));
+    }
+
+    #[test]
+    fn test_config_validation() {
+        let mut config = OAuth2Config::client_credentials("http://t", "id", "secret");
+        assert!(config.validate().is_ok());
+        config.grant = OAuth2GrantType::DeviceCode;
+        assert!(config.validate().is_err());
+        config.grant = OAuth2GrantType::RefreshToken;
+        assert!(OAuth2Provider::new(config).is_err());
+    }
+
+    #[test]
+    fn test_config_from_json() {
+        let config: OAuth2Config = serde_json::from_str(
+            r#"{"token_url": "https://idp/token", "client_id": "cli", "grant": "device_code",
+                "device_authorization_url": "https://idp/device"}"#,
+        )
+        .unwrap();
+        assert_eq!(config.grant, OAuth2GrantType::DeviceCode);
+        assert!(config.client_secret.is_none());
+    }
+
+    #[test]
+    fn test_token_expiry_with_leeway() {
+        let token = OAuth2Token {
+            access_token: "a".into(),
+            token_type: "Bearer".into(),
+            expires_at: Some(Utc::now() + ChronoDuration::seconds(10)),
+            refresh_token: None,
+        };
+        assert!(token.is_expired());
+        let fresh = OAuth2Token {
+            expires_at: Some(Utc::now() + ChronoDuration::seconds(3600)),
+            ..token
+        };
+        assert!(!fresh.is_expired());
+        assert_eq!(fresh.to_auth().header_value().unwrap(), "Bearer a");
+    }
+
+    #[tokio::test]
+    async fn test_client_credentials_token_is_cached() {
+        let idp = TestServer::sequence(vec![token_json("tok-1", 3600, None)]).await;
+        let api = TestServer::start(|_, _| MockResponse::ok("data")).await;
+        let (client, _) = client_with(
+            OAuth2Provider::new(
+                OAuth2Config::client_credentials(idp.url("/token"), "app", "s3cret")
+                    .with_scope("read"),
+            )
+            .unwrap(),
+        );
+
+        for _ in 0..2 {
+            let response = client
+                .execute(RequestBuilder::get(api.url("/things")))
+                .await
+                .unwrap();
+            assert_eq!(response.body, "data");
+        }
+
+        assert_eq!(idp.request_count(), 1);
+        let token_request = &idp.requests()[0];
+        assert_eq!(
+            token_request.header("Authorization"),
+            Some("Basic YXBwOnMzY3JldA==")
+        );
+        let params = form(token_request);
+        assert_eq!(params["grant_type"], "client_credentials");
+        assert_eq!(params["scope"], "read");
+        assert!(api
+            .requests()
+            .iter()
+            .all(|r| r.header("Authorization") == Some("Bearer tok-1")));
+    }
+
+    #[tokio::test]
+    async fn test_expired_token_is_refreshed() {
+        let idp = TestServer::start(|req, _| {
+            let params = form(req);
+            match params["grant_type"].as_str() {
+                "client_credentials" => token_json("short", 5, Some("r1")),
+                _ => {
+                    assert_eq!(params["refresh_token"], "r1");
+                    token_json("renewed", 3600, None)
+                }
+            }
+        })
+        .await;
+        let api = TestServer::start(|_, _| MockResponse::ok("ok")).await;
+        let (client, provider) = client_with(
+            OAuth2Provider::new(OAuth2Config::client_credentials(
+                idp.url("/token"),
+                "app",
+                "s3cret",
+            ))
+            .unwrap(),
+        );
+
+        // The first token expires inside the leeway, so the next request
+        // refreshes it.
+        for _ in 0..2 {
+            client
+                .execute(RequestBuilder::get(api.url("/")))
+                .await
+                .unwrap();
+        }
+        assert_eq!(idp.request_count(), 2);
+        assert_eq!(
+            api.requests()[0].header("Authorization"),
+            Some("Bearer short")
+        );
+        assert_eq!(
+            api.requests()[1].header("Authorization"),
+            Some("Bearer renewed")
+        );
+
+        let cached = provider.cached_token().await.unwrap();
+        assert_eq!(cached.refresh_token.as_deref(), Some("r1"));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_token_response_triggers_refresh() {
+        let issued = Arc::new(AtomicUsize::new(0));
+        let counter = issued.clone();
+        let idp = TestServer::start(move |_, _| {
+            let n = counter.fetch_add(1, Ordering::SeqCst) + 1;
+            token_json(&format!("tok-{}", n), 3600, None)
+        })
+        .await;
+        let api = TestServer::start(|req, _| match req.header("Authorization") {
+            Some("Bearer tok-2") => MockResponse::ok("fresh"),
+            _ => MockResponse::new(401).header(
+                "WWW-Authenticate",
+                "Bearer realm=\"api\", error=\"invalid_token\"",
+            ),
+        })
+        .await;
+        let (client, _) = client_with(
+            OAuth2Provider::new(OAuth2Config::client_credentials(
+                idp.url("/token"),
+                "app",
+                "s3cret",
+            ))
+            .unwrap(),
+        );
+
+        let response = client
+            .execute(RequestBuilder::get(api.url("/")))
+            .await
+            .unwrap();
+        assert_eq!(response.body, "fresh");
+        assert_eq!(issued.load(Ordering::SeqCst), 2);
+        assert_eq!(api.request_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_explicit_auth_and_other_origins_skip_provider() {
+        let idp = TestServer::sequence(vec![token_json("tok", 3600, None)]).await;
+        let other = TestServer::start(|_, _| MockResponse::ok("other")).await;
+        let location = other.url("/landing");
+        let api = TestServer::start(move |req, _| match req.path.as_str() {
+            "/redirect" => MockResponse::new(302).header("Location", &location),
+            _ => MockResponse::ok("api"),
+        })
+        .await;
+        let (client, _) = client_with(
+            OAuth2Provider::new(OAuth2Config::client_credentials(
+                idp.url("/token"),
+                "app",
+                "s3cret",
+            ))
+            .unwrap(),
+        );
+
+        client
+            .execute(RequestBuilder::get(api.url("/")).bearer_auth("mine"))
+            .await
+            .unwrap();
+        assert_eq!(
+            api.requests()[0].header("Authorization"),
+            Some("Bearer mine")
+        );
+        assert_eq!(idp.request_count(), 0);
+
+        client
+            .execute(RequestBuilder::get(api.url("/redirect")))
+            .await
+            .unwrap();
+        assert_eq!(
+            api.requests()[1].header("Authorization"),
+            Some("Bearer tok")
+        );
+        assert!(other.requests()[0].header("Authorization").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_refresh_token_grant() {
+        let idp = TestServer::sequence(vec![token_json("tok", 3600, Some("r2"))]).await;
+        let mut config = OAuth2Config::client_credentials(idp.url("/token"), "app", "s3cret");
+        config.grant = OAuth2GrantType::RefreshToken;
+        config.refresh_token = Some("seed".into());
+        let (client, provider) = client_with(OAuth2Provider::new(config).unwrap());
+
+        let token = provider.token(&client).await.unwrap();
+        assert_eq!(token.access_token, "tok");
+        assert_eq!(token.refresh_token.as_deref(), Some("r2"));
+        assert_eq!(form(&idp.requests()[0])["refresh_token"], "seed");
+    }
+
+    #[tokio::test]
+    async fn test_device_code_grant_polls_until_approved() {
+        let idp = TestServer::start(|req, n| {
+            if req.path == "/device" {
+                assert_eq!(form(req)["client_id"], "cli");
+                return MockResponse::ok(
+                    r#"{"device_code": "dev-1", "user_code": "ABCD-EFGH",
+                        "verification_uri": "https://idp/activate", "expires_in": 60,
+                        "interval": 0}"#,
+                );
+            }
+            let params = form(req);
+            assert_eq!(params["device_code"], "dev-1");
+            assert_eq!(params["client_id"], "cli");
+            if n < 3 {
+                MockResponse::new(400)
+                    .body(
+                        r#"{"error": "authorization_pending", "error_description": "no slow_down"}"#,
+                    )
+                    .delay(Duration::from_millis(100))
+            } else {
+                token_json("device-token", 3600, None)
+            }
+        })
+        .await;
+
+        let prompted = Arc::new(std::sync::Mutex::new(None));
+        let seen = prompted.clone();
+        let config = OAuth2Config {
+            token_url: idp.url("/token"),
+            client_id: "cli".into(),
+            client_secret: None,
+            scope: None,
+            grant: OAuth2GrantType::DeviceCode,
+            refresh_token: None,
+            device_authorization_url: Some(idp.url("/device")),
+        };
+        let provider = OAuth2Provider::new(config)
+            .unwrap()
+            .with_device_prompt(move |d| *seen.lock().unwrap() = Some(d.user_code.clone()));
+        let (client, provider) = client_with(provider);
+
+        let polling = {
+            let provider = provider.clone();
+            tokio::spawn(async move { provider.token(&client).await })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        // The cached token stays readable while the device flow polls.
+        let cached = tokio::time::timeout(Duration::from_millis(20), provider.cached_token());
+        assert!(cached.await.unwrap().is_none());
+
+        let token = polling.await.unwrap().unwrap();
+        assert_eq!(token.access_token, "device-token");
+        assert_eq!(prompted.lock().unwrap().as_deref(), Some("ABCD-EFGH"));
+        assert_eq!(idp.request_count(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_device_code_polling_matches_the_error_code() {
+        let idp = TestServer::start(|req, _| {
+            if req.path == "/device" {
+                return MockResponse::ok(
+                    r#"{"device_code": "dev-1", "user_code": "ABCD-EFGH",
+                        "verification_uri": "https://idp/activate", "expires_in": 60,
+                        "interval": 0}"#,
+                );
+            }
+            MockResponse::new(400).body(
+                r#"{"error": "access_denied", "error_description": "authorization_pending was declined"}"#,
+            )
+        })
+        .await;
+        let config = OAuth2Config {
+            token_url: idp.url("/token"),
+            client_id: "cli".into(),
+            client_secret: None,
+            scope: None,
+            grant: OAuth2GrantType::DeviceCode,
+            refresh_token: None,
+            device_authorization_url: Some(idp.url("/device")),
+        };
+        let (client, provider) = client_with(OAuth2Provider::new(config).unwrap());
+
+        let err = provider.token(&client).await.unwrap_err();
+        assert!(err.to_string().contains("access_denied"));
+        assert_eq!(idp.request_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_token_error_is_reported() {
+        let idp = TestServer::sequence(vec![MockResponse::new(401)
+            .body(r#"{"error": "invalid_client", "error_description": "bad secret"}"#)])
+        .await;
+        let (client, provider) = client_with(
+            OAuth2Provider::new(OAuth2Config::client_credentials(
+                idp.url("/token"),
+                "app",
+                "wrong",
+            ))
+            .unwrap(),
+        );
+        let err = provider.token(&client).await.unwrap_err();
+        assert!(err.to_string().contains("invalid_client (bad secret)"));
+    }
+}
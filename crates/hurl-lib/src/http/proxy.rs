@@ -0,0 +1,280 @@
+//! Per-request proxy selection: an explicit [`ProxyConfig`] wins, otherwise the
+//! conventional `*_proxy` environment variables are consulted.
+
+use super::security::{ProxyConfig, ProxyType};
+use ipnet::IpNet;
+use std::net::IpAddr;
+use url::Url;
+
+/// Snapshot of the proxy environment variables.
+#[derive(Debug, Clone, Default)]
+pub struct EnvProxies {
+    pub http: Option<String>,
+    pub https: Option<String>,
+    pub all: Option<String>,
+    pub no_proxy: Vec<String>,
+}
+
+impl EnvProxies {
+    pub fn from_env() -> Self {
+        Self::from_lookup(|name| std::env::var(name).ok())
+    }
+
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let get = |names: &[&str]| {
+            names
+                .iter()
+                .filter_map(|name| lookup(name))
+                .map(|v| v.trim().to_string())
+                .find(|v| !v.is_empty() && reqwest::Proxy::all(v.as_str()).is_ok())
+        };
+        // Under CGI, HTTP_PROXY can be injected through a request header
+        // ("httpoxy"), so only the lowercase form is trusted there.
+        let http_names: &[&str] = if lookup("REQUEST_METHOD").is_some() {
+            &["http_proxy"]
+        } else {
+            &["http_proxy", "HTTP_PROXY"]
+        };
+
+        EnvProxies {
+            http: get(http_names),
+            https: get(&["https_proxy", "HTTPS_PROXY"]),
+            all: get(&["all_proxy", "ALL_PROXY"]),
+            no_proxy: ["no_proxy", "NO_PROXY"]
+                .iter()
+                .find_map(|name| lookup(name))
+                .map(|list| split_list(&list))
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ProxyResolver {
+    config: ProxyConfig,
+    env: EnvProxies,
+}
+
+impl ProxyResolver {
+    pub fn new(config: ProxyConfig, env: EnvProxies) -> Self {
+        ProxyResolver { config, env }
+    }
+
+    /// Returns the proxy URL to use for `url`, or `None` to connect directly.
+    pub fn resolve(&self, url: &Url) -> Option<String> {
+        let host = url.host_str()?;
+        if self.config.should_bypass(host) {
+            return None;
+        }
+
+        let scheme = url.scheme();
+        if let Some(proxy) = &self.config.proxy {
+            return match proxy {
+                ProxyType::Http { url } if scheme == "http" => Some(url.clone()),
+                ProxyType::Https { url } if scheme == "https" => Some(url.clone()),
+                ProxyType::Socks5 { url } | ProxyType::All { url } => Some(url.clone()),
+                _ => None,
+            };
+        }
+
+        if self
+            .env
+            .no_proxy
+            .iter()
+            .any(|entry| no_proxy_matches(entry, host))
+        {
+            return None;
+        }
+        match scheme {
+            "http" => self.env.http.clone(),
+            "https" => self.env.https.clone(),
+            _ => None,
+        }
+        .or_else(|| self.env.all.clone())
+    }
+}
+
+/// Splits a comma-separated `no_proxy` list.
+pub fn split_list(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Matches a host against one `no_proxy` entry. Supports `*`, CIDR blocks,
+/// literal IPs, and domains, where `example.com`, `.example.com` and
+/// `*.example.com` all cover subdomains.
+pub fn no_proxy_matches(entry: &str, host: &str) -> bool {
+    let entry = entry.trim().to_ascii_lowercase();
+    let host = host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_ascii_lowercase();
+
+    if entry.is_empty() {
+        return false;
+    }
+    if entry == "*" {
+        return true;
+    }
+
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        if let Ok(net) = entry.parse::<IpNet>() {
+            return net.contains(&ip);
+        }
+        return entry.parse::<IpAddr>().is_ok_and(|e| e == ip);
+    }
+
+    let domain = entry
+        .strip_prefix("*.")
+        .or_else(|| entry.strip_prefix('.'))
+        .unwrap_or(&entry);
+    host == domain
+        || host
+            .strip_suffix(domain)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::client::{ClientConfig, HttpClient};
+    use crate::http::request::RequestBuilder;
+    use crate::http::test_server::{MockResponse, TestServer};
+    use std::collections::HashMap;
+
+    fn env(vars: &[(&str, &str)]) -> EnvProxies {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        EnvProxies::from_lookup(|name| vars.get(name).cloned())
+    }
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_no_proxy_matching() {
+        assert!(no_proxy_matches("*", "anything.test"));
+        assert!(no_proxy_matches("example.com", "example.com"));
+        assert!(no_proxy_matches("example.com", "api.example.com"));
+        assert!(!no_proxy_matches("example.com", "badexample.com"));
+        assert!(no_proxy_matches("*.internal", "db.internal"));
+        assert!(no_proxy_matches(".Internal", "DB.internal"));
+        assert!(no_proxy_matches("10.0.0.0/8", "10.1.2.3"));
+        assert!(!no_proxy_matches("10.0.0.0/8", "11.1.2.3"));
+        assert!(no_proxy_matches("::1", "[::1]"));
+        assert!(no_proxy_matches("fd00::/8", "[fd12::1]"));
+        assert!(!no_proxy_matches("10.0.0.0/8", "ten.example"));
+    }
+
+    #[test]
+    fn test_env_proxies_by_scheme() {
+        let resolver = ProxyResolver::new(
+            ProxyConfig::new(),
+            env(&[
+                ("HTTP_PROXY", "http://plain:3128"),
+                ("https_proxy", "http://secure:3128"),
+                ("NO_PROXY", "localhost, .corp"),
+            ]),
+        );
+        assert_eq!(
+            resolver.resolve(&url("http://a.test/")).as_deref(),
+            Some("http://plain:3128")
+        );
+        assert_eq!(
+            resolver.resolve(&url("https://a.test/")).as_deref(),
+            Some("http://secure:3128")
+        );
+        assert_eq!(resolver.resolve(&url("http://localhost/")), None);
+        assert_eq!(resolver.resolve(&url("https://git.corp/")), None);
+    }
+
+    #[test]
+    fn test_uppercase_http_proxy_ignored_under_cgi() {
+        let proxies = env(&[
+            ("HTTP_PROXY", "http://evil:3128"),
+            ("REQUEST_METHOD", "GET"),
+        ]);
+        assert!(proxies.http.is_none());
+    }
+
+    #[test]
+    fn test_explicit_proxy_overrides_env() {
+        let resolver = ProxyResolver::new(
+            ProxyConfig::http("http://explicit:8080").with_no_proxy(vec!["skip.test".into()]),
+            env(&[("ALL_PROXY", "http://fallback:3128")]),
+        );
+        assert_eq!(
+            resolver.resolve(&url("http://a.test/")).as_deref(),
+            Some("http://explicit:8080")
+        );
+        // An http-only proxy leaves https direct instead of falling back to env.
+        assert_eq!(resolver.resolve(&url("https://a.test/")), None);
+        assert_eq!(resolver.resolve(&url("http://skip.test/")), None);
+    }
+
+    #[test]
+    fn test_no_proxy_wildcard_disables_env() {
+        let resolver = ProxyResolver::new(
+            ProxyConfig::new().with_no_proxy(vec!["*".into()]),
+            env(&[("ALL_PROXY", "http://fallback:3128")]),
+        );
+        assert_eq!(resolver.resolve(&url("https://a.test/")), None);
+    }
+
+    #[tokio::test]
+    async fn test_requests_go_through_proxy_with_credentials() {
+        let proxy = TestServer::sequence(vec![MockResponse::ok("via proxy")]).await;
+        let client = HttpClient::with_config(ClientConfig {
+            max_retries: 0,
+            proxy_config: ProxyConfig::all(proxy.url("")).with_auth("alice", "s3cret"),
+            ..ClientConfig::default()
+        })
+        .unwrap();
+
+        let response = client
+            .execute(RequestBuilder::get("http://origin.test/path?q=1"))
+            .await
+            .unwrap();
+        assert_eq!(response.body, "via proxy");
+
+        let seen = &proxy.requests()[0];
+        assert_eq!(seen.path, "http://origin.test/path?q=1");
+        assert_eq!(
+            seen.header("Proxy-Authorization"),
+            Some("Basic YWxpY2U6czNjcmV0")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_no_proxy_cidr_connects_directly() {
+        let server = TestServer::sequence(vec![MockResponse::ok("direct")]).await;
+        let client = HttpClient::with_config(ClientConfig {
+            max_retries: 0,
+            proxy_config: ProxyConfig::all("http://127.0.0.1:9")
+                .with_no_proxy(vec!["127.0.0.0/8".into()]),
+            ..ClientConfig::default()
+        })
+        .unwrap();
+
+        let response = client
+            .execute(RequestBuilder::get(server.url("/")))
+            .await
+            .unwrap();
+        assert_eq!(response.body, "direct");
+    }
+
+    #[test]
+    fn test_invalid_proxy_url_rejected() {
+        let result = HttpClient::with_config(ClientConfig {
+            proxy_config: ProxyConfig::all("ftp://proxy.test"),
+            ..ClientConfig::default()
+        });
+        assert!(result.is_err());
+    }
+}
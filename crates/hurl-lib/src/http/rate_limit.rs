@@ -0,0 +1,461 @@
+//! Client-side pacing of requests.
+//!
+//! A [`RateLimiter`] holds a token bucket for all traffic and one per
+//! configured host. Every request sent by an [`HttpClient`] takes a token
+//! from each bucket that applies, waiting for it if none is left. Responses
+//! feed back into the limiter: `Retry-After` on a 429 or 503, and the
+//! `RateLimit-*` (or common `X-RateLimit-*`) headers, hold back or slow down
+//! the host that sent them.
+//!
+//! [`HttpClient`]: crate::http::client::HttpClient
+
+use crate::http::response::HttpResponse;
+use crate::http::retry::parse_retry_after;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// `requests` per `per`, e.g. 50 per second.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rate {
+    pub requests: u32,
+    pub per: Duration,
+}
+
+impl Rate {
+    pub fn new(requests: u32, per: Duration) -> Self {
+        Rate { requests, per }
+    }
+
+    /// Parses `N/s`, `N/m` or `N/h`, optionally with a count before the
+    /// unit, as in `100/10s`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let (requests, window) = value.trim().split_once('/')?;
+        let requests: u32 = requests.trim().parse().ok().filter(|&n| n > 0)?;
+        let window = window.trim();
+        let unit_at = window.find(|c: char| c.is_ascii_alphabetic())?;
+        let count: u64 = match &window[..unit_at] {
+            "" => 1,
+            n => n.parse().ok().filter(|&n| n > 0)?,
+        };
+        let unit = match &window[unit_at..] {
+            "s" | "sec" => 1,
+            "m" | "min" => 60,
+            "h" | "hour" => 3600,
+            _ => return None,
+        };
+        Some(Rate::new(requests, Duration::from_secs(count * unit)))
+    }
+
+    fn per_second(&self) -> f64 {
+        self.requests as f64 / self.per.as_secs_f64()
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    rate: Rate,
+    capacity: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(rate: Rate, burst: u32, now: Instant) -> Self {
+        let capacity = burst.max(1) as f64;
+        Bucket {
+            rate,
+            capacity,
+            tokens: capacity,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate.per_second()).min(self.capacity);
+        self.updated = now;
+    }
+
+    /// Takes a token, possibly one that has not been earned yet, and
+    /// returns how long the caller has to wait before it is.
+    fn reserve(&mut self, now: Instant) -> Duration {
+        self.refill(now);
+        self.tokens -= 1.0;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate.per_second())
+        }
+    }
+
+    fn set_rate(&mut self, rate: Rate, now: Instant) {
+        self.refill(now);
+        self.rate = rate;
+    }
+}
+
+#[derive(Debug, Default)]
+struct HostState {
+    configured: Option<Bucket>,
+    /// Paces the host to the quota it last announced.
+    announced: Option<Bucket>,
+    blocked_until: Option<Instant>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    global: Option<Bucket>,
+    hosts: HashMap<String, HostState>,
+}
+
+/// Token-bucket limiter shared by every clone of the client it is set on.
+#[derive(Debug)]
+pub struct RateLimiter {
+    burst: u32,
+    adaptive: bool,
+    state: Mutex<State>,
+}
+
+impl RateLimiter {
+    /// A limiter with no limits configured yet. Buckets hold one token by
+    /// default, so requests are spread evenly rather than sent in bursts.
+    pub fn new() -> Self {
+        RateLimiter {
+            burst: 1,
+            adaptive: true,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Limits all requests together.
+    pub fn with_global(self, rate: Rate) -> Self {
+        self.lock().global = Some(Bucket::new(rate, self.burst, Instant::now()));
+        self
+    }
+
+    /// Limits requests to `host`, on top of any global limit.
+    pub fn with_host(self, host: &str, rate: Rate) -> Self {
+        let bucket = Bucket::new(rate, self.burst, Instant::now());
+        self.lock()
+            .hosts
+            .entry(host.to_ascii_lowercase())
+            .or_default()
+            .configured = Some(bucket);
+        self
+    }
+
+    /// How many requests may be sent back to back before pacing applies.
+    /// Set this before adding limits.
+    pub fn with_burst(mut self, burst: u32) -> Self {
+        self.burst = burst.max(1);
+        self
+    }
+
+    /// Whether response headers adjust the pacing. On by default.
+    pub fn with_adaptive(mut self, adaptive: bool) -> Self {
+        self.adaptive = adaptive;
+        self
+    }
+
+    /// Waits until a request to `url` may be sent.
+    pub async fn acquire(&self, url: &str) {
+        let wait = self.reserve(&host_of(url), Instant::now());
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Adjusts pacing for `url`'s host from the headers of its response.
+    pub fn observe(&self, url: &str, response: &HttpResponse) {
+        if !self.adaptive {
+            return;
+        }
+        let now = Instant::now();
+        let retry_after = matches!(response.status, 429 | 503)
+            .then(|| response.header("Retry-After").and_then(parse_retry_after))
+            .flatten();
+        let quota = announced_quota(response);
+        if retry_after.is_none() && quota.is_none() {
+            return;
+        }
+
+        let mut state = self.lock();
+        let host = state.hosts.entry(host_of(url)).or_default();
+        if let Some(wait) = retry_after {
+            host.blocked_until = host.blocked_until.max(Some(now + wait));
+        }
+        match quota {
+            Some((0, reset)) => {
+                host.blocked_until = host.blocked_until.max(Some(now + reset));
+            }
+            Some((remaining, reset)) if !reset.is_zero() => {
+                let rate = Rate::new(remaining.min(u32::MAX as u64) as u32, reset);
+                match &mut host.announced {
+                    Some(bucket) => bucket.set_rate(rate, now),
+                    None => host.announced = Some(Bucket::new(rate, self.burst, now)),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn reserve(&self, host: &str, now: Instant) -> Duration {
+        let mut state = self.lock();
+        let mut wait = state
+            .global
+            .as_mut()
+            .map(|bucket| bucket.reserve(now))
+            .unwrap_or_default();
+        if let Some(host) = state.hosts.get_mut(host) {
+            for bucket in [&mut host.configured, &mut host.announced]
+                .into_iter()
+                .flatten()
+            {
+                wait = wait.max(bucket.reserve(now));
+            }
+            if let Some(until) = host.blocked_until {
+                wait = wait.max(until.saturating_duration_since(now));
+            }
+        }
+        wait
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn host_of(url: &str) -> String {
+    url::Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_ascii_lowercase))
+        .unwrap_or_default()
+}
+
+/// Remaining requests and time until the quota resets, from the combined
+/// `RateLimit` header, `RateLimit-Remaining`/`-Reset`, or the
+/// `X-RateLimit-*` variants. A reset far larger than any window is taken
+/// as a Unix timestamp, as some APIs send.
+fn announced_quota(response: &HttpResponse) -> Option<(u64, Duration)> {
+    let (remaining, reset) = if let Some(combined) = response.header("RateLimit") {
+        let field = |names: &[&str]| {
+            combined.split([',', ';']).find_map(|item| {
+                let (key, value) = item.split_once('=')?;
+                names
+                    .contains(&key.trim())
+                    .then(|| value.trim().parse::<u64>().ok())?
+            })
+        };
+        (field(&["remaining", "r"])?, field(&["reset", "t"])?)
+    } else {
+        ["RateLimit-", "X-RateLimit-"].iter().find_map(|prefix| {
+            let number = |name: &str| {
+                response
+                    .header(&format!("{}{}", prefix, name))
+                    .and_then(|v| v.trim().parse::<u64>().ok())
+            };
+            Some((number("Remaining")?, number("Reset")?))
+        })?
+    };
+
+    let reset = if reset > 1_000_000_000 {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        reset.saturating_sub(now)
+    } else {
+        reset
+    };
+    Some((remaining, Duration::from_secs(reset)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::client::{ClientConfig, HttpClient};
+    use crate::http::request::RequestBuilder;
+    use crate::http::test_server::{MockResponse, TestServer};
+    use std::sync::Arc;
+
+    fn response(status: u16, headers: &[(&str, &str)]) -> HttpResponse {
+        let headers: Vec<(String, String)> = headers
+            .iter()
+            .map(|(n, v)| (n.to_string(), v.to_string()))
+            .collect();
+        HttpResponse::new(status, headers, "")
+    }
+
+    #[test]
+    fn test_parse_rate() {
+        assert_eq!(
+            Rate::parse("50/s"),
+            Some(Rate::new(50, Duration::from_secs(1)))
+        );
+        assert_eq!(
+            Rate::parse("100/10s"),
+            Some(Rate::new(100, Duration::from_secs(10)))
+        );
+        assert_eq!(
+            Rate::parse("5/min"),
+            Some(Rate::new(5, Duration::from_secs(60)))
+        );
+        assert_eq!(
+            Rate::parse("1000/h"),
+            Some(Rate::new(1000, Duration::from_secs(3600)))
+        );
+        assert_eq!(Rate::parse("0/s"), None);
+        assert_eq!(Rate::parse("5/fortnight"), None);
+        assert_eq!(Rate::parse("fast"), None);
+    }
+
+    #[test]
+    fn test_bucket_paces_after_burst() {
+        let now = Instant::now();
+        let mut bucket = Bucket::new(Rate::new(10, Duration::from_secs(1)), 2, now);
+        assert_eq!(bucket.reserve(now), Duration::ZERO);
+        assert_eq!(bucket.reserve(now), Duration::ZERO);
+        assert_eq!(bucket.reserve(now), Duration::from_millis(100));
+        assert_eq!(bucket.reserve(now), Duration::from_millis(200));
+
+        let later = now + Duration::from_secs(10);
+        assert_eq!(bucket.reserve(later), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_host_limits_stack_with_global() {
+        let limiter = RateLimiter::new()
+            .with_global(Rate::new(100, Duration::from_secs(1)))
+            .with_host("API.example.com", Rate::new(2, Duration::from_secs(1)));
+        let now = Instant::now();
+
+        assert_eq!(limiter.reserve("api.example.com", now), Duration::ZERO);
+        assert_eq!(
+            limiter.reserve("api.example.com", now),
+            Duration::from_millis(500)
+        );
+        // Other hosts only share the global bucket.
+        assert_eq!(
+            limiter.reserve("other.example.com", now),
+            Duration::from_millis(20)
+        );
+    }
+
+    #[test]
+    fn test_observe_retry_after_and_quota_headers() {
+        let limiter = RateLimiter::new();
+        let url = "https://api.example.com/items";
+        limiter.observe(url, &response(429, &[("Retry-After", "3")]));
+        let wait = limiter.reserve("api.example.com", Instant::now());
+        assert!(wait > Duration::from_millis(2900) && wait <= Duration::from_secs(3));
+
+        let limiter = RateLimiter::new();
+        limiter.observe(
+            url,
+            &response(
+                200,
+                &[("RateLimit-Remaining", "0"), ("RateLimit-Reset", "5")],
+            ),
+        );
+        assert!(limiter.reserve("api.example.com", Instant::now()) > Duration::from_secs(4));
+
+        let limiter = RateLimiter::new();
+        limiter.observe(
+            url,
+            &response(200, &[("RateLimit", "limit=100, remaining=4, reset=2")]),
+        );
+        let now = Instant::now();
+        assert_eq!(limiter.reserve("api.example.com", now), Duration::ZERO);
+        assert_eq!(
+            limiter.reserve("api.example.com", now),
+            Duration::from_millis(500)
+        );
+
+        let ignored = RateLimiter::new().with_adaptive(false);
+        ignored.observe(url, &response(429, &[("Retry-After", "3")]));
+        assert_eq!(
+            ignored.reserve("api.example.com", Instant::now()),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn test_announced_quota_variants() {
+        let reset_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            + 60;
+        let github = response(
+            200,
+            &[
+                ("X-RateLimit-Remaining", "10"),
+                ("X-RateLimit-Reset", &reset_at.to_string()),
+            ],
+        );
+        let (remaining, reset) = announced_quota(&github).unwrap();
+        assert_eq!(remaining, 10);
+        assert!(reset > Duration::from_secs(58) && reset <= Duration::from_secs(60));
+
+        let draft = response(200, &[("RateLimit", "\"default\";r=7;t=30")]);
+        assert_eq!(announced_quota(&draft), Some((7, Duration::from_secs(30))));
+        assert_eq!(announced_quota(&response(200, &[])), None);
+    }
+
+    #[tokio::test]
+    async fn test_client_waits_for_limiter() {
+        let server = TestServer::sequence(vec![MockResponse::ok("ok")]).await;
+        let limiter =
+            Arc::new(RateLimiter::new().with_global(Rate::new(10, Duration::from_secs(1))));
+        let client = HttpClient::with_config(ClientConfig {
+            rate_limiter: Some(limiter),
+            ..ClientConfig::default()
+        })
+        .unwrap();
+
+        let start = Instant::now();
+        for _ in 0..4 {
+            client
+                .execute(RequestBuilder::get(server.url("/")))
+                .await
+                .unwrap();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(300));
+        assert_eq!(server.request_count(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_client_honours_retry_after_across_requests() {
+        let server = TestServer::sequence(vec![
+            MockResponse::new(429).header("Retry-After", "1"),
+            MockResponse::ok("ok"),
+        ])
+        .await;
+        let client = HttpClient::with_config(ClientConfig {
+            max_retries: 0,
+            rate_limiter: Some(Arc::new(RateLimiter::new())),
+            ..ClientConfig::default()
+        })
+        .unwrap();
+
+        let first = client
+            .execute(RequestBuilder::get(server.url("/")))
+            .await
+            .unwrap();
+        assert_eq!(first.status, 429);
+        let start = Instant::now();
+        let second = client
+            .execute(RequestBuilder::get(server.url("/")))
+            .await
+            .unwrap();
+        assert_eq!(second.status, 200);
+        assert!(start.elapsed() >= Duration::from_millis(900));
+    }
+}
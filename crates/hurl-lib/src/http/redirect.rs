@@ -0,0 +1,234 @@
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RedirectHop {
+    pub method: String,
+    pub url: String,
+    pub status: u16,
+    pub location: String,
+}
+
+pub fn is_redirect(status: u16) -> bool {
+    matches!(status, 301 | 302 | 303 | 307 | 308)
+}
+
+/// Returns the method for the next hop and whether the request body is kept.
+///
+/// 303 always becomes GET (except HEAD); 301 and 302 rewrite POST to GET as
+/// browsers and curl do; 307 and 308 replay the original method and body.
+pub fn redirect_method(status: u16, method: &str) -> (String, bool) {
+    match status {
+        303 if !method.eq_ignore_ascii_case("HEAD") => ("GET".to_string(), false),
+        301 | 302 if method.eq_ignore_ascii_case("POST") => ("GET".to_string(), false),
+        _ => (method.to_string(), true),
+    }
+}
+
+pub fn resolve_location(base: &str, location: &str) -> crate::Result<String> {
+    let base = Url::parse(base).map_err(|e| crate::Error::Http(format!("bad url: {}", e)))?;
+    base.join(location)
+        .map(|u| u.to_string())
+        .map_err(|e| crate::Error::Http(format!("bad redirect location '{}': {}", location, e)))
+}
+
+pub fn same_origin(a: &str, b: &str) -> bool {
+    match (Url::parse(a), Url::parse(b)) {
+        (Ok(a), Ok(b)) => a.origin() == b.origin(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::client::{ClientConfig, HttpClient};
+    use crate::http::request::RequestBuilder;
+    use crate::http::test_server::{MockResponse, TestServer};
+
+    fn client(max_redirects: u32) -> HttpClient {
+        HttpClient::with_config(ClientConfig {
+            max_redirects,
+            max_retries: 0,
+            ..ClientConfig::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_redirect_method_rewriting() {
+        assert_eq!(redirect_method(301, "POST"), ("GET".to_string(), false));
+        assert_eq!(redirect_method(302, "POST"), ("GET".to_string(), false));
+        assert_eq!(redirect_method(302, "PUT"), ("PUT".to_string(), true));
+        assert_eq!(redirect_method(303, "PUT"), ("GET".to_string(), false));
+        assert_eq!(redirect_method(303, "HEAD"), ("HEAD".to_string(), true));
+        assert_eq!(redirect_method(307, "POST"), ("POST".to_string(), true));
+        assert_eq!(redirect_method(308, "PATCH"), ("PATCH".to_string(), true));
+    }
+
+    #[test]
+    fn test_resolve_location() {
+        assert_eq!(
+            resolve_location("http://a.test/x/y?q=1", "/z").unwrap(),
+            "http://a.test/z"
+        );
+        assert_eq!(
+            resolve_location("http://a.test/x/y", "w").unwrap(),
+            "http://a.test/x/w"
+        );
+        assert_eq!(
+            resolve_location("http://a.test/", "https://b.test/").unwrap(),
+            "https://b.test/"
+        );
+    }
+
+    #[test]
+    fn test_same_origin() {
+        assert!(same_origin("http://a.test/x", "http://a.test:80/y"));
+        assert!(!same_origin("http://a.test/", "https://a.test/"));
+        assert!(!same_origin("http://a.test/", "http://a.test:8080/"));
+        assert!(!same_origin("http://a.test/", "http://b.test/"));
+    }
+
+    #[tokio::test]
+    async fn test_follows_redirect_chain() {
+        let server = TestServer::start(|req, _| match req.path.as_str() {
+            "/start" => MockResponse::new(301).header("Location", "/middle"),
+            "/middle" => MockResponse::new(302).header("Location", "/end"),
+            _ => MockResponse::ok("arrived"),
+        })
+        .await;
+
+        let response = client(5)
+            .execute(RequestBuilder::get(server.url("/start")))
+            .await
+            .unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, "arrived");
+        assert_eq!(response.redirects.len(), 2);
+        assert_eq!(response.redirects[0].status, 301);
+        assert_eq!(response.redirects[0].url, server.url("/start"));
+        assert_eq!(response.redirects[1].location, "/end");
+    }
+
+    #[tokio::test]
+    async fn test_post_303_becomes_get() {
+        let server = TestServer::start(|req, _| match req.path.as_str() {
+            "/submit" => MockResponse::new(303).header("Location", "/result"),
+            _ => MockResponse::ok(req.method.clone()),
+        })
+        .await;
+
+        let response = client(5)
+            .execute(
+                RequestBuilder::post(server.url("/submit"))
+                    .header("Content-Type", "text/plain")
+                    .text_body("data"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.body, "GET");
+        let second = &server.requests()[1];
+        assert!(second.body.is_empty());
+        assert!(second.header("Content-Type").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_307_preserves_method_and_body() {
+        let server = TestServer::start(|req, _| match req.path.as_str() {
+            "/old" => MockResponse::new(307).header("Location", "/new"),
+            _ => MockResponse::ok(format!("{} {}", req.method, req.body_text())),
+        })
+        .await;
+
+        let response = client(5)
+            .execute(RequestBuilder::put(server.url("/old")).text_body("payload"))
+            .await
+            .unwrap();
+        assert_eq!(response.body, "PUT payload");
+    }
+
+    #[tokio::test]
+    async fn test_cross_origin_drops_authorization_and_cookie() {
+        let target = TestServer::sequence(vec![MockResponse::ok("other")]).await;
+        let location = target.url("/landing");
+        let origin =
+            TestServer::start(move |_, _| MockResponse::new(302).header("Location", &location))
+                .await;
+
+        client(5)
+            .execute(
+                RequestBuilder::get(origin.url("/"))
+                    .basic_auth("user", "pass")
+                    .header("Cookie", "session=secret")
+                    .header("X-Keep", "1"),
+            )
+            .await
+            .unwrap();
+
+        assert!(origin.requests()[0].header("Authorization").is_some());
+        assert_eq!(
+            origin.requests()[0].header("Cookie"),
+            Some("session=secret")
+        );
+        let forwarded = &target.requests()[0];
+        assert!(forwarded.header("Authorization").is_none());
+        assert!(forwarded.header("Cookie").is_none());
+        assert_eq!(forwarded.header("X-Keep"), Some("1"));
+    }
+
+    #[tokio::test]
+    async fn test_same_origin_keeps_authorization() {
+        let server = TestServer::start(|req, _| match req.path.as_str() {
+            "/a" => MockResponse::new(308).header("Location", "/b"),
+            _ => MockResponse::ok(""),
+        })
+        .await;
+
+        client(5)
+            .execute(
+                RequestBuilder::get(server.url("/a"))
+                    .bearer_auth("tok")
+                    .header("Cookie", "session=1"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            server.requests()[1].header("Authorization"),
+            Some("Bearer tok")
+        );
+        assert_eq!(server.requests()[1].header("Cookie"), Some("session=1"));
+    }
+
+    #[tokio::test]
+    async fn test_max_redirects_exceeded() {
+        let server =
+            TestServer::start(|_, _| MockResponse::new(302).header("Location", "/loop")).await;
+
+        let err = client(3)
+            .execute(RequestBuilder::get(server.url("/loop")))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Maximum redirects exceeded"));
+        assert_eq!(server.request_count(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_redirects_not_followed_when_disabled() {
+        let server =
+            TestServer::start(|_, _| MockResponse::new(301).header("Location", "/next")).await;
+        let client = HttpClient::with_config(ClientConfig {
+            follow_redirects: false,
+            ..ClientConfig::default()
+        })
+        .unwrap();
+
+        let response = client
+            .execute(RequestBuilder::get(server.url("/")))
+            .await
+            .unwrap();
+        assert_eq!(response.status, 301);
+        assert!(response.redirects.is_empty());
+        assert_eq!(server.request_count(), 1);
+    }
+}
@@ -1,4 +1,6 @@
 use super::auth::Auth;
+use super::headers::Headers;
+use super::multipart::Multipart;
 use std::collections::HashMap;
 use std::time::Duration;
 
@@ -6,7 +8,7 @@ use std::time::Duration;
 pub struct RequestBuilder {
     url: String,
     method: String,
-    headers_map: HashMap<String, String>,
+    headers_map: Headers,
     query_params: HashMap<String, String>,
     body_data: Option<RequestBody>,
     auth_data: Auth,
@@ -19,6 +21,7 @@ pub enum RequestBody {
     Form(HashMap<String, String>),
     Text(String),
     Binary(Vec<u8>),
+    Multipart(Multipart),
 }
 
 impl RequestBody {
@@ -32,15 +35,17 @@ impl RequestBody {
                 .map_err(|e| crate::Error::Http(format!("Form: {}", e))),
             RequestBody::Text(t) => Ok(t.as_bytes().to_vec()),
             RequestBody::Binary(b) => Ok(b.clone()),
+            RequestBody::Multipart(m) => m.to_bytes(),
         }
     }
 
-    pub fn content_type(&self) -> &'static str {
+    pub fn content_type(&self) -> String {
         match self {
-            RequestBody::Json(_) => "application/json",
-            RequestBody::Form(_) => "application/x-www-form-urlencoded",
-            RequestBody::Text(_) => "text/plain",
-            RequestBody::Binary(_) => "application/octet-stream",
+            RequestBody::Json(_) => "application/json".to_string(),
+            RequestBody::Form(_) => "application/x-www-form-urlencoded".to_string(),
+            RequestBody::Text(_) => "text/plain".to_string(),
+            RequestBody::Binary(_) => "application/octet-stream".to_string(),
+            RequestBody::Multipart(m) => m.content_type(),
         }
     }
 }
@@ -50,7 +55,7 @@ impl RequestBuilder {
         RequestBuilder {
             url: url.into(),
             method: method.into(),
-            headers_map: HashMap::new(),
+            headers_map: Headers::new(),
             query_params: HashMap::new(),
             body_data: None,
             auth_data: Auth::None,
@@ -80,8 +85,20 @@ impl RequestBuilder {
         Self::new(url, "OPTIONS")
     }
 
+    /// Sets a header, replacing any earlier values for the same name.
     pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
-        self.headers_map.insert(name.into(), value.into());
+        self.headers_map.insert(name, value);
+        self
+    }
+
+    /// Adds a header line, keeping earlier values for the same name.
+    pub fn append_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers_map.append(name, value);
+        self
+    }
+
+    pub fn remove_header(mut self, name: &str) -> Self {
+        self.headers_map.remove(name);
         self
     }
 
@@ -100,6 +117,21 @@ impl RequestBuilder {
         self
     }
 
+    pub fn text_body(mut self, text: impl Into<String>) -> Self {
+        self.body_data = Some(RequestBody::Text(text.into()));
+        self
+    }
+
+    pub fn binary_body(mut self, bytes: Vec<u8>) -> Self {
+        self.body_data = Some(RequestBody::Binary(bytes));
+        self
+    }
+
+    pub fn multipart_body(mut self, form: Multipart) -> Self {
+        self.body_data = Some(RequestBody::Multipart(form));
+        self
+    }
+
     pub fn basic_auth(mut self, user: impl Into<String>, pass: impl Into<String>) -> Self {
         self.auth_data = Auth::basic(user, pass);
         self
@@ -110,6 +142,28 @@ impl RequestBuilder {
         self
     }
 
+    pub fn with_auth(mut self, auth: Auth) -> Self {
+        self.auth_data = auth;
+        self
+    }
+
+    /// Points the request at a new absolute URL, dropping any query params.
+    pub fn with_url(mut self, url: impl Into<String>) -> Self {
+        self.url = url.into();
+        self.query_params.clear();
+        self
+    }
+
+    pub fn with_method(mut self, method: impl Into<String>) -> Self {
+        self.method = method.into();
+        self
+    }
+
+    pub fn without_body(mut self) -> Self {
+        self.body_data = None;
+        self
+    }
+
     pub fn set_timeout(mut self, dur: Duration) -> Self {
         self.request_timeout = dur;
         self
@@ -143,9 +197,12 @@ impl RequestBuilder {
     pub fn method(&self) -> &str {
         &self.method
     }
-    pub fn headers(&self) -> &HashMap<String, String> {
+    pub fn headers(&self) -> &Headers {
         &self.headers_map
     }
+    pub fn has_header(&self, name: &str) -> bool {
+        self.headers_map.contains(name)
+    }
     pub fn body(&self) -> &Option<RequestBody> {
         &self.body_data
     }
@@ -1,5 +1,8 @@
+use super::headers::Headers;
+use super::redirect::RedirectHop;
+use base64::Engine;
+use bytes::Bytes;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
-use std::collections::HashMap;
 use std::time::Duration;
 
 fn serialize_duration<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
@@ -17,25 +20,74 @@ where
     Ok(Duration::from_millis(millis as u64))
 }
 
+/// Bodies are stored as text when they are valid UTF-8 and as
+/// `{"base64": "..."}` otherwise, so binary payloads survive a round trip.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum SerializedBody {
+    Text(String),
+    Binary { base64: String },
+}
+
+fn serialize_body<S>(body: &Bytes, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match std::str::from_utf8(body) {
+        Ok(text) => SerializedBody::Text(text.to_string()),
+        Err(_) => SerializedBody::Binary {
+            base64: base64::engine::general_purpose::STANDARD.encode(body),
+        },
+    }
+    .serialize(serializer)
+}
+
+fn deserialize_body<'de, D>(deserializer: D) -> Result<Bytes, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match SerializedBody::deserialize(deserializer)? {
+        SerializedBody::Text(text) => Ok(Bytes::from(text)),
+        SerializedBody::Binary { base64 } => base64::engine::general_purpose::STANDARD
+            .decode(base64)
+            .map(Bytes::from)
+            .map_err(serde::de::Error::custom),
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HttpResponse {
     pub status: u16,
-    pub headers: HashMap<String, String>,
-    pub body: String,
+    pub headers: Headers,
+    #[serde(
+        serialize_with = "serialize_body",
+        deserialize_with = "deserialize_body"
+    )]
+    pub body: Bytes,
     #[serde(
         serialize_with = "serialize_duration",
         deserialize_with = "deserialize_duration"
     )]
     pub duration: Duration,
+    #[serde(default = "default_attempts")]
+    pub attempts: u32,
+    #[serde(default)]
+    pub redirects: Vec<RedirectHop>,
+}
+
+fn default_attempts() -> u32 {
+    1
 }
 
 impl HttpResponse {
-    pub fn new(status: u16, headers: HashMap<String, String>, body: String) -> Self {
+    pub fn new(status: u16, headers: impl Into<Headers>, body: impl Into<Bytes>) -> Self {
         HttpResponse {
             status,
-            headers,
-            body,
+            headers: headers.into(),
+            body: body.into(),
             duration: Duration::from_secs(0),
+            attempts: 1,
+            redirects: Vec::new(),
         }
     }
 
@@ -44,11 +96,59 @@ impl HttpResponse {
         self
     }
 
+    pub fn with_attempts(mut self, attempts: u32) -> Self {
+        self.attempts = attempts;
+        self
+    }
+
+    pub fn with_redirects(mut self, redirects: Vec<RedirectHop>) -> Self {
+        self.redirects = redirects;
+        self
+    }
+
+    /// The first value of a header.
     pub fn header(&self, name: &str) -> Option<&str> {
-        self.headers
-            .iter()
-            .find(|(k, _)| k.eq_ignore_ascii_case(name))
-            .map(|(_, v)| v.as_str())
+        self.headers.get(name)
+    }
+
+    /// Every value of a repeated header such as `Set-Cookie`, in order.
+    pub fn header_all(&self, name: &str) -> Vec<&str> {
+        self.headers.get_all(name)
+    }
+
+    /// The `charset` parameter of the Content-Type header, if any.
+    pub fn charset(&self) -> Option<&str> {
+        self.header("Content-Type")?
+            .split(';')
+            .skip(1)
+            .filter_map(|param| param.split_once('='))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("charset"))
+            .map(|(_, value)| value.trim().trim_matches('"'))
+    }
+
+    /// Decodes the body using the response charset, defaulting to UTF-8.
+    /// A byte order mark takes precedence, and malformed sequences become
+    /// U+FFFD.
+    pub fn text(&self) -> String {
+        self.decode().0
+    }
+
+    /// Like [`HttpResponse::text`], but `None` when the body has malformed
+    /// sequences, e.g. because it is binary.
+    pub fn try_text(&self) -> Option<String> {
+        match self.decode() {
+            (text, false) => Some(text),
+            (_, true) => None,
+        }
+    }
+
+    fn decode(&self) -> (String, bool) {
+        let encoding = self
+            .charset()
+            .and_then(|label| encoding_rs::Encoding::for_label(label.as_bytes()))
+            .unwrap_or(encoding_rs::UTF_8);
+        let (text, _, had_errors) = encoding.decode(&self.body);
+        (text.into_owned(), had_errors)
     }
 
     pub fn is_success(&self) -> bool {
@@ -64,11 +164,11 @@ impl HttpResponse {
     }
 
     pub fn parse_json<T: serde::de::DeserializeOwned>(&self) -> crate::Result<T> {
-        serde_json::from_str(&self.body).map_err(|e| crate::Error::Http(format!("JSON: {}", e)))
+        serde_json::from_slice(&self.body).map_err(|e| crate::Error::Http(format!("JSON: {}", e)))
     }
 
     pub fn try_parse_json<T: serde::de::DeserializeOwned>(&self) -> Option<T> {
-        serde_json::from_str(&self.body).ok()
+        serde_json::from_slice(&self.body).ok()
     }
 }
 
@@ -295,4 +395,37 @@ mod tests {
         assert_eq!(c.default_timeout_secs, 30);
         assert!(c.enable_cookies);
     }
+
+    fn with_content_type(content_type: &str, body: Vec<u8>) -> HttpResponse {
+        let mut h = std::collections::HashMap::new();
+        h.insert("Content-Type".to_string(), content_type.to_string());
+        HttpResponse::new(200, h, body)
+    }
+
+    #[test]
+    fn test_text_uses_charset() {
+        let r = with_content_type(
+            "text/plain; charset=\"ISO-8859-1\"",
+            vec![0x63, 0x61, 0x66, 0xe9],
+        );
+        assert_eq!(r.charset(), Some("ISO-8859-1"));
+        assert_eq!(r.text(), "café");
+
+        let r = with_content_type("text/plain", "café".as_bytes().to_vec());
+        assert_eq!(r.charset(), None);
+        assert_eq!(r.text(), "café");
+    }
+
+    #[test]
+    fn test_binary_body_serde_round_trip() {
+        let binary = with_content_type("image/png", vec![0x89, b'P', b'N', b'G', 0xff, 0x00]);
+        let json = serde_json::to_value(&binary).unwrap();
+        assert_eq!(json["body"]["base64"], "iVBOR/8A");
+        let back: HttpResponse = serde_json::from_value(json).unwrap();
+        assert_eq!(back.body, binary.body);
+
+        let text = with_content_type("text/plain", b"hello".to_vec());
+        let json = serde_json::to_value(&text).unwrap();
+        assert_eq!(json["body"], "hello");
+    }
 }
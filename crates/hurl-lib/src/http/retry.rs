@@ -0,0 +1,283 @@
+use super::response::HttpResponse;
+use std::time::Duration;
+
+pub const DEFAULT_RETRY_STATUSES: [u16; 4] = [429, 502, 503, 504];
+
+const IDEMPOTENT_METHODS: [&str; 6] = ["GET", "HEAD", "OPTIONS", "PUT", "DELETE", "TRACE"];
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub retry_on_status: Vec<u16>,
+    pub retry_non_idempotent: bool,
+}
+
+impl RetryPolicy {
+    pub fn new(max_retries: u32, base_delay: Duration) -> Self {
+        RetryPolicy {
+            max_retries,
+            base_delay,
+            max_delay: Duration::from_secs(30),
+            retry_on_status: DEFAULT_RETRY_STATUSES.to_vec(),
+            retry_non_idempotent: false,
+        }
+    }
+
+    pub fn disabled() -> Self {
+        Self::new(0, Duration::from_millis(0))
+    }
+
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    pub fn with_statuses(mut self, statuses: Vec<u16>) -> Self {
+        self.retry_on_status = statuses;
+        self
+    }
+
+    pub fn with_non_idempotent(mut self, enabled: bool) -> Self {
+        self.retry_non_idempotent = enabled;
+        self
+    }
+
+    pub fn allows_method(&self, method: &str) -> bool {
+        self.retry_non_idempotent || is_idempotent(method)
+    }
+
+    pub fn should_retry_status(&self, status: u16) -> bool {
+        self.retry_on_status.contains(&status)
+    }
+
+    /// Connection failures and timeouts are transient; everything else is final.
+    pub fn should_retry_error(error: &crate::Error) -> bool {
+        match error {
+            crate::Error::Request(e) => e.is_connect() || e.is_timeout(),
+            _ => false,
+        }
+    }
+
+    /// Exponential backoff for the given 1-based attempt, with "equal jitter":
+    /// half of the delay is fixed and the other half is random.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exp = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+        let half = exp / 2;
+        half + half.mul_f64(fastrand::f64())
+    }
+
+    /// Delay before the next attempt, preferring the server's `Retry-After`.
+    pub fn delay_for(&self, attempt: u32, response: Option<&HttpResponse>) -> Duration {
+        response
+            .and_then(|r| r.header("Retry-After"))
+            .and_then(parse_retry_after)
+            .map(|d| d.min(self.max_delay))
+            .unwrap_or_else(|| self.backoff(attempt))
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(3, Duration::from_millis(100))
+    }
+}
+
+pub fn is_idempotent(method: &str) -> bool {
+    IDEMPOTENT_METHODS
+        .iter()
+        .any(|m| m.eq_ignore_ascii_case(method))
+}
+
+/// Parses `Retry-After` as either delta-seconds or an HTTP-date.
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let wait = date.signed_duration_since(chrono::Utc::now());
+    Some(wait.to_std().unwrap_or(Duration::from_secs(0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::client::{ClientConfig, HttpClient};
+    use crate::http::request::RequestBuilder;
+    use crate::http::test_server::{MockResponse, TestServer};
+
+    fn client(max_retries: u32) -> HttpClient {
+        HttpClient::with_config(ClientConfig {
+            max_retries,
+            retry_delay_ms: 1,
+            ..ClientConfig::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_idempotent_methods() {
+        assert!(is_idempotent("GET"));
+        assert!(is_idempotent("put"));
+        assert!(!is_idempotent("POST"));
+        assert!(!is_idempotent("PATCH"));
+    }
+
+    #[test]
+    fn test_policy_method_gate() {
+        let policy = RetryPolicy::default();
+        assert!(policy.allows_method("DELETE"));
+        assert!(!policy.allows_method("POST"));
+        assert!(policy.with_non_idempotent(true).allows_method("POST"));
+    }
+
+    #[test]
+    fn test_backoff_grows_and_is_capped() {
+        let policy =
+            RetryPolicy::new(5, Duration::from_millis(100)).with_max_delay(Duration::from_secs(1));
+        for _ in 0..20 {
+            let first = policy.backoff(1);
+            assert!(first >= Duration::from_millis(50) && first <= Duration::from_millis(100));
+            let third = policy.backoff(3);
+            assert!(third >= Duration::from_millis(200) && third <= Duration::from_millis(400));
+            assert!(policy.backoff(10) <= Duration::from_secs(1));
+        }
+    }
+
+    #[test]
+    fn test_parse_retry_after_seconds() {
+        assert_eq!(parse_retry_after("7"), Some(Duration::from_secs(7)));
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    #[test]
+    fn test_parse_retry_after_http_date() {
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:49:37 GMT"),
+            Some(Duration::from_secs(0))
+        );
+        let future = (chrono::Utc::now() + chrono::Duration::seconds(120))
+            .format("%a, %d %b %Y %H:%M:%S GMT")
+            .to_string();
+        let wait = parse_retry_after(&future).unwrap();
+        assert!(wait > Duration::from_secs(100) && wait <= Duration::from_secs(120));
+    }
+
+    #[test]
+    fn test_delay_prefers_retry_after() {
+        let policy = RetryPolicy::new(3, Duration::from_secs(10));
+        let mut headers = std::collections::HashMap::new();
+        headers.insert("Retry-After".to_string(), "0".to_string());
+        let response = HttpResponse::new(503, headers, String::new());
+        assert_eq!(policy.delay_for(1, Some(&response)), Duration::from_secs(0));
+    }
+
+    #[tokio::test]
+    async fn test_retries_until_success() {
+        let server = TestServer::sequence(vec![
+            MockResponse::new(503),
+            MockResponse::new(429).header("Retry-After", "0"),
+            MockResponse::ok("done"),
+        ])
+        .await;
+
+        let response = client(3)
+            .execute(RequestBuilder::get(server.url("/")))
+            .await
+            .unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.attempts, 3);
+        assert_eq!(server.request_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_retries() {
+        let server = TestServer::sequence(vec![MockResponse::new(502)]).await;
+
+        let response = client(2)
+            .execute(RequestBuilder::get(server.url("/")))
+            .await
+            .unwrap();
+        assert_eq!(response.status, 502);
+        assert_eq!(response.attempts, 3);
+        assert_eq!(server.request_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_non_retryable_status_is_returned() {
+        let server = TestServer::sequence(vec![MockResponse::new(500)]).await;
+
+        let response = client(3)
+            .execute(RequestBuilder::get(server.url("/")))
+            .await
+            .unwrap();
+        assert_eq!(response.attempts, 1);
+        assert_eq!(server.request_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_post_not_retried_by_default() {
+        let server = TestServer::sequence(vec![MockResponse::new(503)]).await;
+
+        let response = client(3)
+            .execute(RequestBuilder::post(server.url("/")).text_body("x"))
+            .await
+            .unwrap();
+        assert_eq!(response.attempts, 1);
+        assert_eq!(server.request_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_post_retried_when_enabled() {
+        let server =
+            TestServer::sequence(vec![MockResponse::new(503), MockResponse::new(201)]).await;
+        let client = HttpClient::with_config(ClientConfig {
+            max_retries: 1,
+            retry_delay_ms: 1,
+            retry_non_idempotent: true,
+            ..ClientConfig::default()
+        })
+        .unwrap();
+
+        let response = client
+            .execute(RequestBuilder::post(server.url("/")).text_body("x"))
+            .await
+            .unwrap();
+        assert_eq!(response.status, 201);
+        assert_eq!(server.requests()[1].body_text(), "x");
+    }
+
+    #[tokio::test]
+    async fn test_retries_on_timeout() {
+        let server = TestServer::sequence(vec![
+            MockResponse::ok("slow").delay(Duration::from_millis(500)),
+            MockResponse::ok("fast"),
+        ])
+        .await;
+
+        let response = client(1)
+            .execute(RequestBuilder::get(server.url("/")).set_timeout(Duration::from_millis(100)))
+            .await
+            .unwrap();
+        assert_eq!(response.body, "fast");
+        assert_eq!(response.attempts, 2);
+    }
+
+    #[tokio::test]
+    async fn test_connect_error_exhausts_retries() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        drop(listener);
+
+        let err = client(2)
+            .execute(RequestBuilder::get(url))
+            .await
+            .unwrap_err();
+        assert!(RetryPolicy::should_retry_error(&err));
+    }
+}
@@ -13,6 +13,7 @@ pub struct TlsConfig {
     pub cert_pinning: Option<Vec<String>>,
     pub client_cert: Option<PathBuf>,
     pub client_key: Option<PathBuf>,
+    pub client_cert_password: Option<String>,
     pub min_tls_version: TlsVersion,
     pub ciphers: Vec<String>,
 }
@@ -32,6 +33,7 @@ impl TlsConfig {
             cert_pinning: None,
             client_cert: None,
             client_key: None,
+            client_cert_password: None,
             min_tls_version: TlsVersion::TLS1_2,
             ciphers: vec![],
         }
@@ -66,13 +68,34 @@ impl TlsConfig {
         self
     }
 
+    /// Uses a PKCS#12 (`.p12`/`.pfx`) archive holding both certificate and key.
+    pub fn with_client_pkcs12(mut self, archive: PathBuf, password: Option<String>) -> Self {
+        self.client_cert = Some(archive);
+        self.client_key = None;
+        self.client_cert_password = password;
+        self
+    }
+
+    pub fn with_ciphers(mut self, ciphers: Vec<String>) -> Self {
+        self.ciphers = ciphers;
+        self
+    }
+
+    pub fn is_pkcs12(&self) -> bool {
+        self.client_cert
+            .as_ref()
+            .and_then(|p| p.extension())
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case("p12") || e.eq_ignore_ascii_case("pfx"))
+    }
+
     pub fn with_min_tls_version(mut self, version: TlsVersion) -> Self {
         self.min_tls_version = version;
         self
     }
 
     pub fn validate(&self) -> crate::Result<()> {
-        if self.client_cert.is_some() && self.client_key.is_none() {
+        if self.client_cert.is_some() && self.client_key.is_none() && !self.is_pkcs12() {
             return Err(crate::Error::Config(
                 "client certificate requires key".into(),
             ));
@@ -94,9 +117,19 @@ impl Default for TlsConfig {
 
 #[derive(Debug, Clone)]
 pub enum ProxyType {
-    Http { url: String },
-    Https { url: String },
-    Socks5 { url: String },
+    Http {
+        url: String,
+    },
+    Https {
+        url: String,
+    },
+    Socks5 {
+        url: String,
+    },
+    /// Used for every scheme, like curl's `-x`.
+    All {
+        url: String,
+    },
 }
 
 #[derive(Debug, Clone)]
@@ -142,6 +175,13 @@ impl ProxyConfig {
         }
     }
 
+    pub fn all(url: impl Into<String>) -> Self {
+        ProxyConfig {
+            proxy: Some(ProxyType::All { url: url.into() }),
+            ..Self::new()
+        }
+    }
+
     pub fn with_no_proxy(mut self, domains: Vec<String>) -> Self {
         self.no_proxy = domains;
         self
@@ -156,23 +196,30 @@ impl ProxyConfig {
     }
 
     pub fn should_bypass(&self, host: &str) -> bool {
-        self.no_proxy.iter().any(|np| {
-            if np.starts_with('.') {
-                host.ends_with(np) || host.ends_with(&np[1..])
-            } else {
-                host == np
-            }
-        })
+        self.no_proxy
+            .iter()
+            .any(|entry| super::proxy::no_proxy_matches(entry, host))
+    }
+
+    pub fn url(&self) -> Option<&str> {
+        match &self.proxy {
+            Some(
+                ProxyType::Http { url }
+                | ProxyType::Https { url }
+                | ProxyType::Socks5 { url }
+                | ProxyType::All { url },
+            ) => Some(url),
+            None => None,
+        }
     }
 
     pub fn validate(&self) -> crate::Result<()> {
-        if let Some(
-            ProxyType::Http { url } | ProxyType::Https { url } | ProxyType::Socks5 { url },
-        ) = &self.proxy
-        {
+        if let Some(url) = self.url() {
             if url.is_empty() {
                 return Err(crate::Error::Config("proxy URL cannot be empty".into()));
             }
+            reqwest::Proxy::all(url)
+                .map_err(|e| crate::Error::Config(format!("invalid proxy URL '{}': {}", url, e)))?;
         }
         Ok(())
     }
@@ -1,4 +1,5 @@
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
     use crate::http::auth::Auth;
     use crate::http::compression::{CompressionCodec, CompressionConfig};
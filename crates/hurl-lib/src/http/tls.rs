@@ -0,0 +1,544 @@
+//! Builds the rustls client configuration that backs `HttpClient` from a
+//! [`TlsConfig`]: trust roots, client identity, protocol versions, cipher
+//! suites and public key pinning.
+
+use super::security::{CertificateValidation, TlsConfig, TlsVersion};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+use rustls::{Certificate, OwnedTrustAnchor, PrivateKey, RootCertStore, ServerName};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+const PIN_PREFIX: &str = "sha256//";
+
+pub fn build_rustls_config(tls: &TlsConfig) -> crate::Result<rustls::ClientConfig> {
+    let suites = cipher_suites(&tls.ciphers)?;
+    let versions: &[&rustls::SupportedProtocolVersion] = match tls.min_tls_version {
+        TlsVersion::TLS1_3 => &[&rustls::version::TLS13],
+        // rustls never negotiates anything older than TLS 1.2.
+        _ => &[&rustls::version::TLS13, &rustls::version::TLS12],
+    };
+
+    let verifier = PinnedVerifier {
+        inner: match &tls.validation {
+            CertificateValidation::Strict => Some(WebPkiVerifier::new(system_roots(), None)),
+            CertificateValidation::Custom { ca_bundle } => {
+                Some(WebPkiVerifier::new(load_ca_bundle(ca_bundle)?, None))
+            }
+            CertificateValidation::Permissive => None,
+        },
+        pins: parse_pins(tls.cert_pinning.as_deref().unwrap_or_default())?,
+    };
+
+    let builder = rustls::ClientConfig::builder()
+        .with_cipher_suites(&suites)
+        .with_safe_default_kx_groups()
+        .with_protocol_versions(versions)
+        .map_err(|e| crate::Error::Config(format!("unsupported TLS settings: {}", e)))?
+        .with_custom_certificate_verifier(Arc::new(verifier));
+
+    let mut config = match load_identity(tls)? {
+        Some((certs, key)) => builder
+            .with_client_auth_cert(certs, key)
+            .map_err(|e| crate::Error::Config(format!("invalid client certificate: {}", e)))?,
+        None => builder.with_no_client_auth(),
+    };
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+/// Base64 SHA-256 of the certificate's SubjectPublicKeyInfo, the value curl's
+/// `--pinnedpubkey sha256//...` expects.
+pub fn spki_sha256(cert_der: &[u8]) -> crate::Result<String> {
+    let spki = extract_spki(cert_der)
+        .ok_or_else(|| crate::Error::Parse("malformed X.509 certificate".into()))?;
+    Ok(STANDARD.encode(Sha256::digest(spki)))
+}
+
+struct PinnedVerifier {
+    inner: Option<WebPkiVerifier>,
+    pins: Vec<String>,
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if let Some(inner) = &self.inner {
+            inner.verify_server_cert(
+                end_entity,
+                intermediates,
+                server_name,
+                scts,
+                ocsp_response,
+                now,
+            )?;
+        }
+
+        if !self.pins.is_empty() {
+            let actual =
+                spki_sha256(&end_entity.0).map_err(|e| rustls::Error::General(e.to_string()))?;
+            if !self.pins.contains(&actual) {
+                return Err(rustls::Error::General(format!(
+                    "public key pin mismatch: server presented {}{}",
+                    PIN_PREFIX, actual
+                )));
+            }
+        }
+
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+fn parse_pins(pins: &[String]) -> crate::Result<Vec<String>> {
+    pins.iter()
+        .map(|pin| {
+            let value = pin.trim();
+            let value = value.strip_prefix(PIN_PREFIX).unwrap_or(value);
+            match STANDARD.decode(value) {
+                Ok(bytes) if bytes.len() == 32 => Ok(value.to_string()),
+                _ => Err(crate::Error::Config(format!(
+                    "invalid public key pin '{}': expected sha256//<base64 digest>",
+                    pin
+                ))),
+            }
+        })
+        .collect()
+}
+
+fn cipher_suites(names: &[String]) -> crate::Result<Vec<rustls::SupportedCipherSuite>> {
+    if names.is_empty() {
+        return Ok(rustls::DEFAULT_CIPHER_SUITES.to_vec());
+    }
+    names
+        .iter()
+        .map(|name| {
+            rustls::ALL_CIPHER_SUITES
+                .iter()
+                .find(|s| format!("{:?}", s.suite()).eq_ignore_ascii_case(name.trim()))
+                .copied()
+                .ok_or_else(|| crate::Error::Config(format!("unknown cipher suite '{}'", name)))
+        })
+        .collect()
+}
+
+fn system_roots() -> RootCertStore {
+    let mut roots = RootCertStore::empty();
+    roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+        OwnedTrustAnchor::from_subject_spki_name_constraints(
+            ta.subject,
+            ta.spki,
+            ta.name_constraints,
+        )
+    }));
+    if let Ok(native) = rustls_native_certs::load_native_certs() {
+        let der: Vec<Vec<u8>> = native.into_iter().map(|c| c.0).collect();
+        roots.add_parsable_certificates(&der);
+    }
+    roots
+}
+
+fn load_ca_bundle(path: &Path) -> crate::Result<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+    let (added, _) = roots.add_parsable_certificates(&read_pem_certs(path)?);
+    if added == 0 {
+        return Err(crate::Error::Config(format!(
+            "no CA certificates found in {}",
+            path.display()
+        )));
+    }
+    Ok(roots)
+}
+
+fn read_pem_certs(path: &Path) -> crate::Result<Vec<Vec<u8>>> {
+    let data = std::fs::read(path)?;
+    rustls_pemfile::certs(&mut data.as_slice())
+        .map_err(|e| crate::Error::Parse(format!("bad PEM in {}: {}", path.display(), e)))
+}
+
+fn read_pem_key(path: &Path) -> crate::Result<PrivateKey> {
+    let data = std::fs::read(path)?;
+    let items = rustls_pemfile::read_all(&mut data.as_slice())
+        .map_err(|e| crate::Error::Parse(format!("bad PEM in {}: {}", path.display(), e)))?;
+    items
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or_else(|| crate::Error::Config(format!("no private key in {}", path.display())))
+}
+
+type Identity = (Vec<Certificate>, PrivateKey);
+
+fn load_identity(tls: &TlsConfig) -> crate::Result<Option<Identity>> {
+    let Some(cert_path) = &tls.client_cert else {
+        return Ok(None);
+    };
+    if tls.is_pkcs12() {
+        return load_pkcs12(cert_path, tls.client_cert_password.as_deref()).map(Some);
+    }
+
+    let certs: Vec<Certificate> = read_pem_certs(cert_path)?
+        .into_iter()
+        .map(Certificate)
+        .collect();
+    if certs.is_empty() {
+        return Err(crate::Error::Config(format!(
+            "no certificates found in {}",
+            cert_path.display()
+        )));
+    }
+    let key = read_pem_key(tls.client_key.as_deref().unwrap_or(cert_path))?;
+    Ok(Some((certs, key)))
+}
+
+fn load_pkcs12(path: &Path, password: Option<&str>) -> crate::Result<Identity> {
+    let bad = |e: openssl::error::ErrorStack| {
+        crate::Error::Config(format!("cannot read PKCS#12 {}: {}", path.display(), e))
+    };
+    let data = std::fs::read(path)?;
+    let parsed = openssl::pkcs12::Pkcs12::from_der(&data)
+        .map_err(bad)?
+        .parse2(password.unwrap_or(""))
+        .map_err(bad)?;
+
+    let (Some(cert), Some(key)) = (parsed.cert, parsed.pkey) else {
+        return Err(crate::Error::Config(format!(
+            "PKCS#12 {} must contain a certificate and a private key",
+            path.display()
+        )));
+    };
+    let mut chain = vec![Certificate(cert.to_der().map_err(bad)?)];
+    for ca in parsed.ca.into_iter().flatten() {
+        chain.push(Certificate(ca.to_der().map_err(bad)?));
+    }
+    Ok((chain, PrivateKey(key.private_key_to_pkcs8().map_err(bad)?)))
+}
+
+struct Tlv<'a> {
+    contents: &'a [u8],
+    element: &'a [u8],
+    rest: &'a [u8],
+}
+
+/// Reads one DER element from the front of `input`.
+fn read_tlv(input: &[u8]) -> Option<Tlv<'_>> {
+    let first = *input.get(1)? as usize;
+    let (len, header) = if first < 0x80 {
+        (first, 2)
+    } else {
+        let n = first & 0x7f;
+        if n == 0 || n > 4 {
+            return None;
+        }
+        let bytes = input.get(2..2 + n)?;
+        (
+            bytes.iter().fold(0usize, |acc, b| (acc << 8) | *b as usize),
+            2 + n,
+        )
+    };
+    let end = header.checked_add(len)?;
+    Some(Tlv {
+        contents: input.get(header..end)?,
+        element: &input[..end],
+        rest: &input[end..],
+    })
+}
+
+fn extract_spki(cert_der: &[u8]) -> Option<&[u8]> {
+    let certificate = read_tlv(cert_der)?.contents;
+    let mut rest = read_tlv(certificate)?.contents;
+    if rest.first() == Some(&0xa0) {
+        rest = read_tlv(rest)?.rest;
+    }
+    // serialNumber, signature, issuer, validity, subject
+    for _ in 0..5 {
+        rest = read_tlv(rest)?.rest;
+    }
+    Some(read_tlv(rest)?.element)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::client::{ClientConfig, HttpClient};
+    use crate::http::request::RequestBuilder;
+    use crate::http::test_server::{MockResponse, TestServer};
+    use rcgen::{BasicConstraints, CertificateParams, IsCa};
+    use std::path::PathBuf;
+
+    struct Pki {
+        ca: rcgen::Certificate,
+        dir: PathBuf,
+    }
+
+    impl Pki {
+        fn new() -> Self {
+            let mut params = CertificateParams::new(vec![]);
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            let dir = std::env::temp_dir().join(format!("hurl-tls-{}", uuid::Uuid::new_v4()));
+            std::fs::create_dir_all(&dir).unwrap();
+            let pki = Pki {
+                ca: rcgen::Certificate::from_params(params).unwrap(),
+                dir,
+            };
+            std::fs::write(pki.path("ca.pem"), pki.ca.serialize_pem().unwrap()).unwrap();
+            pki
+        }
+
+        fn path(&self, name: &str) -> PathBuf {
+            self.dir.join(name)
+        }
+
+        fn issue(&self, name: &str) -> (rcgen::Certificate, Vec<u8>) {
+            let cert = rcgen::generate_simple_self_signed(vec![name.to_string()]).unwrap();
+            let der = cert.serialize_der_with_signer(&self.ca).unwrap();
+            (cert, der)
+        }
+
+        fn server(&self, versions: &[&'static rustls::SupportedProtocolVersion]) -> ServerSetup {
+            let (cert, der) = self.issue("localhost");
+            ServerSetup {
+                chain: vec![Certificate(der)],
+                key: PrivateKey(cert.serialize_private_key_der()),
+                versions: versions.to_vec(),
+                client_ca: None,
+            }
+        }
+
+        /// Writes a client identity as PEM files and returns (cert, key) paths.
+        fn client_pem(&self) -> (PathBuf, PathBuf) {
+            let (cert, der) = self.issue("client");
+            let pem = pem_encode("CERTIFICATE", &der);
+            std::fs::write(self.path("client.pem"), pem).unwrap();
+            std::fs::write(self.path("client.key"), cert.serialize_private_key_pem()).unwrap();
+            (self.path("client.pem"), self.path("client.key"))
+        }
+
+        fn client_p12(&self, password: &str) -> PathBuf {
+            let (cert, der) = self.issue("client");
+            let x509 = openssl::x509::X509::from_der(&der).unwrap();
+            let key = openssl::pkey::PKey::private_key_from_der(&cert.serialize_private_key_der())
+                .unwrap();
+            let archive = openssl::pkcs12::Pkcs12::builder()
+                .name("client")
+                .pkey(&key)
+                .cert(&x509)
+                .build2(password)
+                .unwrap();
+            std::fs::write(self.path("client.p12"), archive.to_der().unwrap()).unwrap();
+            self.path("client.p12")
+        }
+    }
+
+    impl Drop for Pki {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    struct ServerSetup {
+        chain: Vec<Certificate>,
+        key: PrivateKey,
+        versions: Vec<&'static rustls::SupportedProtocolVersion>,
+        client_ca: Option<Vec<u8>>,
+    }
+
+    impl ServerSetup {
+        fn require_client_cert(mut self, pki: &Pki) -> Self {
+            self.client_ca = Some(pki.ca.serialize_der().unwrap());
+            self
+        }
+
+        async fn start(self) -> TestServer {
+            let builder = rustls::ServerConfig::builder()
+                .with_safe_default_cipher_suites()
+                .with_safe_default_kx_groups()
+                .with_protocol_versions(&self.versions)
+                .unwrap();
+            let builder = match self.client_ca {
+                Some(ca) => {
+                    let mut roots = RootCertStore::empty();
+                    roots.add(&Certificate(ca)).unwrap();
+                    builder.with_client_cert_verifier(
+                        rustls::server::AllowAnyAuthenticatedClient::new(roots).boxed(),
+                    )
+                }
+                None => builder.with_no_client_auth(),
+            };
+            let config = builder.with_single_cert(self.chain, self.key).unwrap();
+            TestServer::start_tls(config, |_, _| MockResponse::ok("secure")).await
+        }
+    }
+
+    fn pem_encode(label: &str, der: &[u8]) -> String {
+        let body = STANDARD.encode(der);
+        let lines: Vec<&str> = body
+            .as_bytes()
+            .chunks(64)
+            .map(|c| std::str::from_utf8(c).unwrap())
+            .collect();
+        format!(
+            "-----BEGIN {label}-----\n{}\n-----END {label}-----\n",
+            lines.join("\n")
+        )
+    }
+
+    async fn fetch(server: &TestServer, tls: TlsConfig) -> crate::Result<String> {
+        let client = HttpClient::with_config(ClientConfig {
+            max_retries: 0,
+            tls_config: tls,
+            ..ClientConfig::default()
+        })?;
+        let url = format!("https://localhost:{}/", server.addr().port());
+        Ok(client.execute(RequestBuilder::get(url)).await?.text())
+    }
+
+    fn error_chain(err: &crate::Error) -> String {
+        let mut message = err.to_string();
+        let mut source = std::error::Error::source(err);
+        while let Some(e) = source {
+            message.push_str(&format!(": {}", e));
+            source = e.source();
+        }
+        message
+    }
+
+    #[test]
+    fn test_spki_hash_matches_key() {
+        let pki = Pki::new();
+        let (cert, der) = pki.issue("localhost");
+        let expected = STANDARD.encode(Sha256::digest(cert.get_key_pair().public_key_der()));
+        assert_eq!(spki_sha256(&der).unwrap(), expected);
+        assert!(spki_sha256(b"not a certificate").is_err());
+    }
+
+    #[test]
+    fn test_parse_pins() {
+        let digest = STANDARD.encode([7u8; 32]);
+        let pins = parse_pins(&[format!("sha256//{}", digest), digest.clone()]).unwrap();
+        assert_eq!(pins, vec![digest.clone(), digest]);
+        assert!(parse_pins(&["sha256//short".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_unknown_cipher_suite() {
+        let tls = TlsConfig::new().with_ciphers(vec!["TLS_NULL_WITH_NULL_NULL".to_string()]);
+        assert!(build_rustls_config(&tls).is_err());
+        let tls = TlsConfig::new().with_ciphers(vec!["tls13_aes_128_gcm_sha256".to_string()]);
+        assert!(build_rustls_config(&tls).is_ok());
+    }
+
+    #[test]
+    fn test_missing_ca_bundle_fails_client_creation() {
+        let result = HttpClient::with_config(ClientConfig {
+            tls_config: TlsConfig::with_ca_bundle(PathBuf::from("/nonexistent/ca.pem")),
+            ..ClientConfig::default()
+        });
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_custom_ca_bundle() {
+        let pki = Pki::new();
+        let server = pki.server(&[&rustls::version::TLS13]).start().await;
+
+        assert!(fetch(&server, TlsConfig::strict()).await.is_err());
+        let body = fetch(&server, TlsConfig::with_ca_bundle(pki.path("ca.pem")))
+            .await
+            .unwrap();
+        assert_eq!(body, "secure");
+    }
+
+    #[tokio::test]
+    async fn test_permissive_accepts_unknown_issuer() {
+        let pki = Pki::new();
+        let server = pki.server(&[&rustls::version::TLS13]).start().await;
+        assert_eq!(
+            fetch(&server, TlsConfig::permissive()).await.unwrap(),
+            "secure"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pinned_public_key() {
+        let pki = Pki::new();
+        let setup = pki.server(&[&rustls::version::TLS13]);
+        let pin = spki_sha256(&setup.chain[0].0).unwrap();
+        let server = setup.start().await;
+
+        let good = TlsConfig::with_ca_bundle(pki.path("ca.pem"))
+            .with_cert_pinning(vec![format!("sha256//{}", pin)]);
+        assert_eq!(fetch(&server, good).await.unwrap(), "secure");
+
+        let wrong = STANDARD.encode([0u8; 32]);
+        let bad = TlsConfig::with_ca_bundle(pki.path("ca.pem")).with_cert_pinning(vec![wrong]);
+        let err = fetch(&server, bad).await.unwrap_err();
+        assert!(error_chain(&err).contains("pin mismatch"));
+    }
+
+    #[tokio::test]
+    async fn test_min_tls_version_enforced() {
+        let pki = Pki::new();
+        let server = pki.server(&[&rustls::version::TLS12]).start().await;
+
+        let tls12 = TlsConfig::with_ca_bundle(pki.path("ca.pem"));
+        assert_eq!(fetch(&server, tls12).await.unwrap(), "secure");
+
+        let tls13 =
+            TlsConfig::with_ca_bundle(pki.path("ca.pem")).with_min_tls_version(TlsVersion::TLS1_3);
+        assert!(fetch(&server, tls13).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_client_certificate_pem() {
+        let pki = Pki::new();
+        let server = pki
+            .server(&[&rustls::version::TLS13])
+            .require_client_cert(&pki)
+            .start()
+            .await;
+
+        let anonymous = TlsConfig::with_ca_bundle(pki.path("ca.pem"));
+        assert!(fetch(&server, anonymous).await.is_err());
+
+        let (cert, key) = pki.client_pem();
+        let tls = TlsConfig::with_ca_bundle(pki.path("ca.pem")).with_client_cert(cert, key);
+        assert_eq!(fetch(&server, tls).await.unwrap(), "secure");
+    }
+
+    #[tokio::test]
+    async fn test_client_certificate_pkcs12() {
+        let pki = Pki::new();
+        let server = pki
+            .server(&[&rustls::version::TLS13])
+            .require_client_cert(&pki)
+            .start()
+            .await;
+        let archive = pki.client_p12("secret");
+
+        let tls = TlsConfig::with_ca_bundle(pki.path("ca.pem"))
+            .with_client_pkcs12(archive.clone(), Some("secret".to_string()));
+        assert!(tls.validate().is_ok());
+        assert_eq!(fetch(&server, tls).await.unwrap(), "secure");
+
+        let wrong = TlsConfig::with_ca_bundle(pki.path("ca.pem"))
+            .with_client_pkcs12(archive, Some("nope".to_string()));
+        assert!(HttpClient::with_config(ClientConfig {
+            tls_config: wrong,
+            ..ClientConfig::default()
+        })
+        .is_err());
+    }
+}
@@ -1,4 +1,5 @@
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod integration_tests {
     use crate::batch::{BatchExecutor, BatchRequest, BatchStats};
     use crate::http::chaining::{
@@ -84,6 +85,7 @@ mod integration_tests {
             max_entries: 10,
             cache_by_method: true,
             cache_successful_only: false,
+            ..CachePolicies::default()
         };
         let mut cache = ResponseCache::new(policies);
         let response = create_test_response(500);
@@ -206,11 +208,13 @@ mod integration_tests {
 
     #[test]
     fn test_batch_stats_calculations() {
-        let mut stats = BatchStats::default();
-        stats.total = 20;
-        stats.succeeded = 18;
-        stats.failed = 2;
-        stats.duration = Duration::from_secs(2);
+        let stats = BatchStats {
+            total: 20,
+            succeeded: 18,
+            failed: 2,
+            duration: Duration::from_secs(2),
+            ..Default::default()
+        };
 
         assert_eq!(stats.success_rate(), 90.0);
         assert_eq!(stats.error_rate(), 10.0);
@@ -312,7 +316,7 @@ mod integration_tests {
 
     #[test]
     fn test_extraction_rule_builders() {
-        let rules = vec![
+        let rules = [
             ExtractionRule::json_path("id", "data.id"),
             ExtractionRule::header("token", "x-token"),
             ExtractionRule::status("code"),
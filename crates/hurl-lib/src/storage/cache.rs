@@ -1,7 +1,13 @@
+use crate::http::digest::hex;
+use crate::http::headers::Headers;
+use crate::http::request::RequestBuilder;
 use crate::http::response::HttpResponse;
+use crate::utils::parse_http_date;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
 use std::collections::HashMap;
-use std::time::{Duration, Instant};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CachedResponse {
@@ -22,17 +28,40 @@ impl CachedResponse {
 #[derive(Debug)]
 pub struct ResponseCache {
     cache: HashMap<String, (HttpResponse, Instant, Duration)>,
+    entries: HashMap<String, Vec<CacheEntry>>,
     hits: usize,
     misses: usize,
+    revalidated: usize,
     policies: CachePolicies,
 }
 
+/// How [`ResponseCache::lookup`] and [`ResponseCache::complete`] decide
+/// what to store and for how long.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CacheMode {
+    /// Every successful response lives for `default_ttl`, whatever its
+    /// headers say.
+    #[default]
+    Fixed,
+    /// RFC 9111: freshness comes from `Cache-Control`, `Expires`, `Date`
+    /// and `Age`, variants are kept per `Vary`, and stale entries are
+    /// revalidated with `If-None-Match`/`If-Modified-Since`.
+    Http,
+}
+
 #[derive(Debug, Clone)]
 pub struct CachePolicies {
     pub default_ttl: Duration,
     pub max_entries: usize,
     pub cache_by_method: bool,
     pub cache_successful_only: bool,
+    pub mode: CacheMode,
+    /// Upper bound on the total size of stored bodies in
+    /// [`CacheMode::Http`]; the least recently used entries go first.
+    pub max_bytes: u64,
+    /// Behave as a shared cache: honor `s-maxage` and never store
+    /// `private` responses. A client-side cache is private by default.
+    pub shared: bool,
 }
 
 impl Default for CachePolicies {
@@ -42,16 +71,274 @@ impl Default for CachePolicies {
             max_entries: 1000,
             cache_by_method: true,
             cache_successful_only: true,
+            mode: CacheMode::Fixed,
+            max_bytes: 256 * 1024 * 1024,
+            shared: false,
         }
     }
 }
 
+/// The `Cache-Control` directives the cache acts on. Unknown directives
+/// are ignored; qualified forms such as `no-cache="Set-Cookie"` are
+/// treated like their unqualified form.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheControl {
+    pub max_age: Option<u64>,
+    pub s_maxage: Option<u64>,
+    pub no_store: bool,
+    pub no_cache: bool,
+    pub private: bool,
+    pub public: bool,
+    pub must_revalidate: bool,
+}
+
+impl CacheControl {
+    /// Parses every `Cache-Control` value of a message.
+    pub fn parse<'a>(values: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut cc = CacheControl::default();
+        for directive in values.into_iter().flat_map(|v| v.split(',')) {
+            let (name, arg) = match directive.split_once('=') {
+                Some((name, arg)) => (name, Some(arg.trim().trim_matches('"'))),
+                None => (directive, None),
+            };
+            let seconds = || arg.and_then(|a| a.parse::<u64>().ok());
+            match name.trim().to_ascii_lowercase().as_str() {
+                "max-age" => cc.max_age = seconds().or(Some(0)),
+                "s-maxage" => cc.s_maxage = seconds().or(Some(0)),
+                "no-store" => cc.no_store = true,
+                "no-cache" => cc.no_cache = true,
+                "private" => cc.private = true,
+                "public" => cc.public = true,
+                "must-revalidate" => cc.must_revalidate = true,
+                _ => {}
+            }
+        }
+        cc
+    }
+
+    fn of(headers: &Headers) -> Self {
+        Self::parse(headers.get_all("Cache-Control"))
+    }
+}
+
+/// A stored response together with what is needed to age it and to match
+/// it against later requests.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub response: HttpResponse,
+    /// Request header values named by the response's `Vary`, lowercased
+    /// names with `None` for headers the request did not send.
+    pub vary: Vec<(String, Option<String>)>,
+    /// Unix time the request was sent.
+    pub request_time: u64,
+    /// Unix time the response arrived.
+    pub response_time: u64,
+    /// Hex SHA-256 of the body; the disk store names its body blobs by it.
+    #[serde(default)]
+    pub digest: String,
+    /// Body length in bytes.
+    #[serde(default)]
+    pub size: u64,
+    /// Unix time the entry was last stored, served or revalidated.
+    #[serde(default)]
+    pub last_access: u64,
+}
+
+impl CacheEntry {
+    fn new(request: &RequestBuilder, response: HttpResponse, request_time: u64) -> Self {
+        let vary = vary_names(&response.headers)
+            .into_iter()
+            .map(|name| {
+                let value = vary_value(request.headers(), &name);
+                (name, value)
+            })
+            .collect();
+        let response_time = now_secs().max(request_time);
+        CacheEntry {
+            digest: hex(&Sha256::digest(&response.body)),
+            size: response.body.len() as u64,
+            response,
+            vary,
+            request_time,
+            response_time,
+            last_access: response_time,
+        }
+    }
+
+    fn matches(&self, request: &RequestBuilder) -> bool {
+        self.vary
+            .iter()
+            .all(|(name, value)| vary_value(request.headers(), name) == *value)
+    }
+
+    fn date(&self) -> u64 {
+        self.response
+            .header("Date")
+            .and_then(parse_http_date)
+            .map(|t| t.max(0) as u64)
+            .unwrap_or(self.response_time)
+    }
+
+    /// Freshness lifetime in seconds (RFC 9111 4.2.1), falling back to the
+    /// 10% heuristic on `Last-Modified` for heuristically cacheable codes.
+    pub fn freshness_lifetime(&self, shared: bool) -> u64 {
+        let cc = CacheControl::of(&self.response.headers);
+        if shared {
+            if let Some(s_maxage) = cc.s_maxage {
+                return s_maxage;
+            }
+        }
+        if let Some(max_age) = cc.max_age {
+            return max_age;
+        }
+        if let Some(expires) = self.response.header("Expires") {
+            // An invalid Expires, such as "0", means already expired.
+            return parse_http_date(expires)
+                .map(|t| (t.max(0) as u64).saturating_sub(self.date()))
+                .unwrap_or(0);
+        }
+        if HEURISTIC_STATUSES.contains(&self.response.status) {
+            if let Some(modified) = self
+                .response
+                .header("Last-Modified")
+                .and_then(parse_http_date)
+            {
+                return self.date().saturating_sub(modified.max(0) as u64) / 10;
+            }
+        }
+        0
+    }
+
+    /// Current age in seconds (RFC 9111 4.2.3).
+    pub fn current_age(&self, now: u64) -> u64 {
+        let age_value = self
+            .response
+            .header("Age")
+            .and_then(|a| a.trim().parse::<u64>().ok())
+            .unwrap_or(0);
+        let apparent_age = self.response_time.saturating_sub(self.date());
+        let response_delay = self.response_time.saturating_sub(self.request_time);
+        let corrected_initial_age = apparent_age.max(age_value + response_delay);
+        corrected_initial_age + now.saturating_sub(self.response_time)
+    }
+
+    pub fn is_fresh(&self, now: u64, shared: bool) -> bool {
+        self.current_age(now) < self.freshness_lifetime(shared)
+    }
+
+    /// Conditional request headers built from the entry's validators.
+    fn validators(&self) -> Vec<(String, String)> {
+        let mut validators = Vec::new();
+        if let Some(etag) = self.response.header("ETag") {
+            validators.push(("If-None-Match".to_string(), etag.to_string()));
+        }
+        if let Some(modified) = self.response.header("Last-Modified") {
+            validators.push(("If-Modified-Since".to_string(), modified.to_string()));
+        }
+        validators
+    }
+
+    /// Applies the headers of a 304 to the stored response (RFC 9111 4.3.4).
+    fn refresh(&mut self, not_modified: &HttpResponse, request_time: u64) {
+        let mut names: Vec<&str> = Vec::new();
+        for (name, _) in not_modified.headers.iter() {
+            if !name.eq_ignore_ascii_case("Content-Length")
+                && !names.iter().any(|n| n.eq_ignore_ascii_case(name))
+            {
+                names.push(name);
+            }
+        }
+        for name in names {
+            self.response.headers.remove(name);
+            for value in not_modified.header_all(name) {
+                self.response.headers.append(name, value);
+            }
+        }
+        self.request_time = request_time;
+        self.response_time = now_secs().max(request_time);
+        self.last_access = self.response_time;
+    }
+}
+
+/// What the cache can do for a request.
+#[derive(Debug, Clone)]
+pub enum CacheLookup {
+    /// A fresh stored response that can be used as is.
+    Fresh(HttpResponse),
+    /// A stale entry; send the request with these extra headers and hand
+    /// the result to [`ResponseCache::complete`].
+    Stale(Vec<(String, String)>),
+    /// Nothing usable is stored.
+    Miss,
+}
+
+/// Status codes that may be cached without explicit freshness (RFC 9110 15.1).
+const HEURISTIC_STATUSES: &[u16] = &[200, 203, 204, 300, 301, 308, 404, 405, 410, 414, 501];
+
+/// Request headers that make the caller responsible for conditional or
+/// partial semantics, so the cache stays out of the way.
+const BYPASS_HEADERS: &[&str] = &[
+    "If-None-Match",
+    "If-Modified-Since",
+    "If-Match",
+    "If-Unmodified-Since",
+    "If-Range",
+    "Range",
+];
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn vary_names(headers: &Headers) -> Vec<String> {
+    headers
+        .get_all("Vary")
+        .into_iter()
+        .flat_map(|v| v.split(','))
+        .map(|name| name.trim().to_ascii_lowercase())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+fn vary_value(headers: &Headers, name: &str) -> Option<String> {
+    let values = headers.get_all(name);
+    if values.is_empty() {
+        return None;
+    }
+    Some(
+        values
+            .iter()
+            .map(|v| v.split_whitespace().collect::<Vec<_>>().join(" "))
+            .collect::<Vec<_>>()
+            .join(", "),
+    )
+}
+
+fn request_cache_control(request: &RequestBuilder) -> CacheControl {
+    let mut cc = CacheControl::of(request.headers());
+    if !request.has_header("Cache-Control")
+        && request
+            .headers()
+            .get_all("Pragma")
+            .iter()
+            .any(|p| p.trim().eq_ignore_ascii_case("no-cache"))
+    {
+        cc.no_cache = true;
+    }
+    cc
+}
+
 impl ResponseCache {
     pub fn new(policies: CachePolicies) -> Self {
         ResponseCache {
             cache: HashMap::new(),
+            entries: HashMap::new(),
             hits: 0,
             misses: 0,
+            revalidated: 0,
             policies,
         }
     }
@@ -115,20 +402,268 @@ impl ResponseCache {
         self.cache.insert(key, (response, Instant::now(), ttl));
     }
 
+    /// Looks a request up according to the configured [`CacheMode`].
+    pub fn lookup(&mut self, request: &RequestBuilder) -> CacheLookup {
+        let Ok(url) = request.build_url() else {
+            return CacheLookup::Miss;
+        };
+        if !request.method().eq_ignore_ascii_case("GET") {
+            return CacheLookup::Miss;
+        }
+        if self.policies.mode == CacheMode::Fixed {
+            return match self.get(&url, request.method()) {
+                Some(response) => CacheLookup::Fresh(response),
+                None => CacheLookup::Miss,
+            };
+        }
+
+        let request_cc = request_cache_control(request);
+        if request_cc.no_store || BYPASS_HEADERS.iter().any(|h| request.has_header(h)) {
+            self.misses += 1;
+            return CacheLookup::Miss;
+        }
+
+        let now = now_secs();
+        let shared = self.policies.shared;
+        let key = self.generate_key(&url, request.method());
+        let entry = self
+            .entries
+            .get_mut(&key)
+            .and_then(|variants| variants.iter_mut().find(|e| e.matches(request)));
+        let Some(entry) = entry else {
+            self.misses += 1;
+            return CacheLookup::Miss;
+        };
+
+        let response_cc = CacheControl::of(&entry.response.headers);
+        let age = entry.current_age(now);
+        let usable = entry.is_fresh(now, shared)
+            && !response_cc.no_cache
+            && !request_cc.no_cache
+            && request_cc.max_age.is_none_or(|max| age <= max);
+        if usable {
+            entry.last_access = now;
+            let mut response = entry.response.clone();
+            response.headers.insert("Age", age.to_string());
+            self.hits += 1;
+            return CacheLookup::Fresh(response);
+        }
+
+        self.misses += 1;
+        let validators = entry.validators();
+        if validators.is_empty() {
+            CacheLookup::Miss
+        } else {
+            CacheLookup::Stale(validators)
+        }
+    }
+
+    /// Hands the network response for `request` to the cache and returns
+    /// the response to give the caller. In [`CacheMode::Http`] a 304 that
+    /// answers a revalidation refreshes the stored entry and returns it, a
+    /// storable response is stored under its `Vary` variant, and a
+    /// successful unsafe method invalidates what is stored for the URL.
+    /// `request_time` is the Unix time the request was sent.
+    pub fn complete(
+        &mut self,
+        request: &RequestBuilder,
+        response: HttpResponse,
+        request_time: u64,
+    ) -> HttpResponse {
+        let Ok(url) = request.build_url() else {
+            return response;
+        };
+        let method = request.method().to_ascii_uppercase();
+        if self.policies.mode == CacheMode::Fixed {
+            if method == "GET" {
+                self.put(&url, &method, response.clone());
+            }
+            return response;
+        }
+
+        if !matches!(method.as_str(), "GET" | "HEAD" | "OPTIONS" | "TRACE") {
+            if (200..400).contains(&response.status) {
+                self.entries.remove(&self.generate_key(&url, "GET"));
+            }
+            return response;
+        }
+        if method != "GET" {
+            return response;
+        }
+
+        let key = self.generate_key(&url, &method);
+        if response.status == 304 {
+            let entry = self
+                .entries
+                .get_mut(&key)
+                .and_then(|variants| variants.iter_mut().find(|e| e.matches(request)));
+            return match entry {
+                Some(entry) => {
+                    entry.refresh(&response, request_time);
+                    self.revalidated += 1;
+                    entry
+                        .response
+                        .clone()
+                        .with_duration(response.duration)
+                        .with_attempts(response.attempts)
+                }
+                None => response,
+            };
+        }
+
+        if self.is_storable(request, &response) {
+            let entry = CacheEntry::new(request, response.clone(), request_time);
+            self.store_entry(key, entry);
+        }
+        response
+    }
+
+    fn is_storable(&self, request: &RequestBuilder, response: &HttpResponse) -> bool {
+        if self.policies.cache_successful_only && !response.is_success() {
+            return false;
+        }
+        if response.status == 206 || !response.redirects.is_empty() {
+            return false;
+        }
+        let request_cc = request_cache_control(request);
+        let cc = CacheControl::of(&response.headers);
+        if request_cc.no_store || cc.no_store || (cc.private && self.policies.shared) {
+            return false;
+        }
+        if self.policies.shared
+            && request.has_header("Authorization")
+            && !(cc.public || cc.must_revalidate || cc.s_maxage.is_some())
+        {
+            return false;
+        }
+        if vary_names(&response.headers).iter().any(|v| v == "*") {
+            return false;
+        }
+        let explicit = cc.max_age.is_some()
+            || (self.policies.shared && cc.s_maxage.is_some())
+            || response.header("Expires").is_some();
+        let heuristic = HEURISTIC_STATUSES.contains(&response.status)
+            && response.header("Last-Modified").is_some();
+        explicit || heuristic || response.header("ETag").is_some()
+    }
+
+    fn store_entry(&mut self, key: String, entry: CacheEntry) {
+        let variants = self.entries.entry(key).or_default();
+        variants.retain(|e| e.vary != entry.vary);
+        variants.push(entry);
+        self.evict();
+    }
+
+    /// Drops least recently used entries until both `max_entries` and
+    /// `max_bytes` hold.
+    fn evict(&mut self) {
+        while self.entry_count() > self.policies.max_entries
+            || self.entry_bytes() > self.policies.max_bytes
+        {
+            let oldest = self
+                .entries
+                .iter()
+                .flat_map(|(k, variants)| {
+                    variants
+                        .iter()
+                        .enumerate()
+                        .map(move |(i, e)| (e.last_access, k.clone(), i))
+                })
+                .min();
+            let Some((_, key, index)) = oldest else {
+                break;
+            };
+            if let Some(variants) = self.entries.get_mut(&key) {
+                variants.remove(index);
+                if variants.is_empty() {
+                    self.entries.remove(&key);
+                }
+            }
+        }
+    }
+
+    fn entry_bytes(&self) -> u64 {
+        self.entries.values().flatten().map(|e| e.size).sum()
+    }
+
+    /// The stored variant that `request` would be served from.
+    pub(crate) fn find(&self, request: &RequestBuilder) -> Option<&CacheEntry> {
+        let url = request.build_url().ok()?;
+        self.entries
+            .get(&self.generate_key(&url, request.method()))?
+            .iter()
+            .find(|e| e.matches(request))
+    }
+
+    /// Takes back a `Fresh` lookup of `request` whose stored body turned out
+    /// to be lost: the entry is dropped and the lookup counts as a miss.
+    pub(crate) fn discard_hit(&mut self, request: &RequestBuilder) {
+        let Ok(url) = request.build_url() else {
+            return;
+        };
+        let key = self.generate_key(&url, request.method());
+        if let Some(variants) = self.entries.get_mut(&key) {
+            variants.retain(|e| !e.matches(request));
+            if variants.is_empty() {
+                self.entries.remove(&key);
+            }
+        }
+        self.hits = self.hits.saturating_sub(1);
+        self.misses += 1;
+    }
+
+    /// Rebuilds a cache from entries and counters kept elsewhere.
+    pub(crate) fn from_parts(
+        policies: CachePolicies,
+        entries: Vec<(String, CacheEntry)>,
+        counters: (usize, usize, usize),
+    ) -> Self {
+        let mut cache = ResponseCache::new(policies);
+        for (key, entry) in entries {
+            cache.entries.entry(key).or_default().push(entry);
+        }
+        (cache.hits, cache.misses, cache.revalidated) = counters;
+        cache
+    }
+
+    pub(crate) fn into_parts(self) -> (Vec<(String, CacheEntry)>, (usize, usize, usize)) {
+        let entries = self
+            .entries
+            .into_iter()
+            .flat_map(|(k, variants)| variants.into_iter().map(move |e| (k.clone(), e)))
+            .collect();
+        (entries, (self.hits, self.misses, self.revalidated))
+    }
+
+    fn entry_count(&self) -> usize {
+        self.entries.values().map(Vec::len).sum()
+    }
+
+    /// Every entry stored in [`CacheMode::Http`], keyed like `put`.
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &CacheEntry)> {
+        self.entries
+            .iter()
+            .flat_map(|(k, variants)| variants.iter().map(move |e| (k.as_str(), e)))
+    }
+
     pub fn invalidate(&mut self, url: &str, method: Option<&str>) {
         if let Some(m) = method {
             let key = self.generate_key(url, m);
             self.cache.remove(&key);
+            self.entries.remove(&key);
         } else {
-            self.cache
-                .retain(|k, _| !k.ends_with(&format!("::{}", url)));
+            let suffix = format!("::{}", url);
+            self.cache.retain(|k, _| !k.ends_with(&suffix));
+            self.entries.retain(|k, _| !k.ends_with(&suffix));
         }
     }
 
     pub fn clear(&mut self) {
         self.cache.clear();
+        self.entries.clear();
         self.hits = 0;
         self.misses = 0;
+        self.revalidated = 0;
     }
 
     pub fn stats(&self) -> CacheStats {
@@ -144,12 +679,14 @@ impl ResponseCache {
             misses: self.misses,
             total_requests: total,
             hit_rate,
-            size: self.cache.len(),
+            revalidated: self.revalidated,
+            size: self.size(),
+            bytes: self.entry_bytes(),
         }
     }
 
     pub fn size(&self) -> usize {
-        self.cache.len()
+        self.cache.len() + self.entry_count()
     }
 
     pub fn set_policies(&mut self, policies: CachePolicies) {
@@ -161,8 +698,10 @@ impl Clone for ResponseCache {
     fn clone(&self) -> Self {
         ResponseCache {
             cache: self.cache.clone(),
+            entries: self.entries.clone(),
             hits: self.hits,
             misses: self.misses,
+            revalidated: self.revalidated,
             policies: self.policies.clone(),
         }
     }
@@ -174,5 +713,262 @@ pub struct CacheStats {
     pub misses: usize,
     pub total_requests: usize,
     pub hit_rate: f64,
+    /// Stale entries refreshed by a 304.
+    pub revalidated: usize,
     pub size: usize,
+    /// Total body bytes held in [`CacheMode::Http`] entries.
+    pub bytes: u64,
+}
+
+/// A response cache [`HttpClient`](crate::http::client::HttpClient) can
+/// consult for GET requests; see [`ResponseCache::lookup`] and
+/// [`ResponseCache::complete`].
+pub trait CacheStore: Send + Sync {
+    fn lookup(&self, request: &RequestBuilder) -> crate::Result<CacheLookup>;
+
+    fn complete(
+        &self,
+        request: &RequestBuilder,
+        response: HttpResponse,
+        request_time: u64,
+    ) -> crate::Result<HttpResponse>;
+}
+
+impl CacheStore for Mutex<ResponseCache> {
+    fn lookup(&self, request: &RequestBuilder) -> crate::Result<CacheLookup> {
+        Ok(self
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .lookup(request))
+    }
+
+    fn complete(
+        &self,
+        request: &RequestBuilder,
+        response: HttpResponse,
+        request_time: u64,
+    ) -> crate::Result<HttpResponse> {
+        Ok(self
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .complete(request, response, request_time))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::client::{ClientConfig, HttpClient};
+    use crate::http::test_server::{MockResponse, TestServer};
+    use std::sync::Arc;
+
+    const URL: &str = "https://api.example.com/items";
+
+    fn http_cache() -> ResponseCache {
+        ResponseCache::new(CachePolicies {
+            mode: CacheMode::Http,
+            ..CachePolicies::default()
+        })
+    }
+
+    fn response(headers: &[(&str, &str)], body: &str) -> HttpResponse {
+        let headers: Headers = headers.iter().copied().collect();
+        HttpResponse::new(200, headers, body.to_string())
+    }
+
+    fn http_date(offset_secs: i64) -> String {
+        (chrono::Utc::now() + chrono::Duration::seconds(offset_secs)).to_rfc2822()
+    }
+
+    fn store(cache: &mut ResponseCache, request: &RequestBuilder, response: HttpResponse) {
+        cache.complete(request, response, now_secs());
+    }
+
+    #[test]
+    fn test_parse_cache_control() {
+        let cc = CacheControl::parse([
+            "public, max-age=60",
+            "S-MAXAGE=\"120\", must-revalidate, no-cache=\"Set-Cookie\"",
+        ]);
+        assert_eq!(cc.max_age, Some(60));
+        assert_eq!(cc.s_maxage, Some(120));
+        assert!(cc.public && cc.must_revalidate && cc.no_cache);
+        assert!(!cc.no_store && !cc.private);
+    }
+
+    #[test]
+    fn test_freshness_from_max_age_expires_and_heuristic() {
+        let request = RequestBuilder::get(URL);
+        let now = now_secs();
+        let entry =
+            |headers: &[(&str, &str)]| CacheEntry::new(&request, response(headers, ""), now);
+
+        let fresh = entry(&[("Cache-Control", "max-age=60")]);
+        assert_eq!(fresh.freshness_lifetime(false), 60);
+        assert!(fresh.is_fresh(now, false));
+
+        let aged = entry(&[("Cache-Control", "max-age=60"), ("Age", "120")]);
+        assert!(aged.current_age(now) >= 120);
+        assert!(!aged.is_fresh(now, false));
+
+        let shared = entry(&[("Cache-Control", "max-age=0, s-maxage=30")]);
+        assert_eq!(shared.freshness_lifetime(false), 0);
+        assert_eq!(shared.freshness_lifetime(true), 30);
+
+        let date = http_date(0);
+        let expires = http_date(300);
+        let expiring = entry(&[("Date", &date), ("Expires", &expires)]);
+        assert!((299..=301).contains(&expiring.freshness_lifetime(false)));
+        assert_eq!(entry(&[("Expires", "0")]).freshness_lifetime(false), 0);
+
+        let modified = http_date(-1000);
+        let heuristic = entry(&[("Date", &date), ("Last-Modified", &modified)]);
+        assert!((99..=101).contains(&heuristic.freshness_lifetime(false)));
+    }
+
+    #[test]
+    fn test_fresh_hit_sets_age_and_no_store_is_skipped() {
+        let mut cache = http_cache();
+        let request = RequestBuilder::get(URL);
+        store(
+            &mut cache,
+            &request,
+            response(&[("Cache-Control", "max-age=60")], "cached"),
+        );
+
+        match cache.lookup(&request) {
+            CacheLookup::Fresh(hit) => {
+                assert_eq!(hit.text(), "cached");
+                assert_eq!(hit.header("Age"), Some("0"));
+            }
+            other => panic!("expected a fresh hit, got {:?}", other),
+        }
+
+        let no_cache = RequestBuilder::get(URL).header("Cache-Control", "no-cache");
+        assert!(matches!(cache.lookup(&no_cache), CacheLookup::Miss));
+
+        let other = RequestBuilder::get("https://api.example.com/secret");
+        store(
+            &mut cache,
+            &other,
+            response(&[("Cache-Control", "no-store, max-age=60")], ""),
+        );
+        assert!(matches!(cache.lookup(&other), CacheLookup::Miss));
+        assert_eq!(cache.size(), 1);
+    }
+
+    #[test]
+    fn test_vary_keeps_one_entry_per_variant() {
+        let mut cache = http_cache();
+        let json = RequestBuilder::get(URL).header("Accept", "application/json");
+        let xml = RequestBuilder::get(URL).header("Accept", "application/xml");
+        let headers = [("Cache-Control", "max-age=60"), ("Vary", "Accept")];
+        store(&mut cache, &json, response(&headers, "{}"));
+        store(&mut cache, &xml, response(&headers, "<x/>"));
+        assert_eq!(cache.size(), 2);
+
+        let CacheLookup::Fresh(hit) = cache.lookup(&xml) else {
+            panic!("expected xml variant");
+        };
+        assert_eq!(hit.text(), "<x/>");
+        let plain = RequestBuilder::get(URL);
+        assert!(matches!(cache.lookup(&plain), CacheLookup::Miss));
+
+        let star = RequestBuilder::get("https://api.example.com/any");
+        store(
+            &mut cache,
+            &star,
+            response(&[("Cache-Control", "max-age=60"), ("Vary", "*")], ""),
+        );
+        assert_eq!(cache.size(), 2);
+    }
+
+    #[test]
+    fn test_stale_entry_is_revalidated_by_304() {
+        let mut cache = http_cache();
+        let request = RequestBuilder::get(URL);
+        let modified = http_date(-3600);
+        store(
+            &mut cache,
+            &request,
+            response(
+                &[
+                    ("Cache-Control", "max-age=0"),
+                    ("ETag", "\"v1\""),
+                    ("Last-Modified", &modified),
+                    ("X-Version", "1"),
+                ],
+                "body",
+            ),
+        );
+
+        let CacheLookup::Stale(validators) = cache.lookup(&request) else {
+            panic!("expected a stale entry");
+        };
+        assert!(validators.contains(&("If-None-Match".to_string(), "\"v1\"".to_string())));
+        assert!(validators.contains(&("If-Modified-Since".to_string(), modified.clone())));
+
+        let not_modified = HttpResponse::new(
+            304,
+            Headers::from_iter([("Cache-Control", "max-age=60"), ("X-Version", "2")]),
+            "",
+        );
+        let refreshed = cache.complete(&request, not_modified, now_secs());
+        assert_eq!(refreshed.status, 200);
+        assert_eq!(refreshed.text(), "body");
+        assert_eq!(refreshed.header("X-Version"), Some("2"));
+        assert!(matches!(cache.lookup(&request), CacheLookup::Fresh(_)));
+        assert_eq!(cache.stats().revalidated, 1);
+    }
+
+    #[test]
+    fn test_unsafe_method_invalidates() {
+        let mut cache = http_cache();
+        let request = RequestBuilder::get(URL);
+        store(
+            &mut cache,
+            &request,
+            response(&[("Cache-Control", "max-age=60")], ""),
+        );
+        cache.complete(
+            &RequestBuilder::post(URL),
+            HttpResponse::new(201, Headers::new(), ""),
+            now_secs(),
+        );
+        assert_eq!(cache.size(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_client_serves_and_revalidates_from_cache() {
+        let server = TestServer::sequence(vec![
+            MockResponse::ok("fresh").header("Cache-Control", "max-age=60"),
+            MockResponse::ok("v1")
+                .header("Cache-Control", "no-cache")
+                .header("ETag", "\"v1\""),
+            MockResponse::new(304).header("ETag", "\"v1\""),
+        ])
+        .await;
+        let cache = Arc::new(Mutex::new(http_cache()));
+        let client = HttpClient::with_config(ClientConfig {
+            cache: Some(cache.clone()),
+            ..ClientConfig::default()
+        })
+        .unwrap();
+
+        let first = client.get(server.url("/fresh")).await.unwrap();
+        let second = client.get(server.url("/fresh")).await.unwrap();
+        assert_eq!(first.text(), "fresh");
+        assert_eq!(second.text(), "fresh");
+
+        client.get(server.url("/etag")).await.unwrap();
+        let revalidated = client.get(server.url("/etag")).await.unwrap();
+        assert_eq!(revalidated.status, 200);
+        assert_eq!(revalidated.text(), "v1");
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[2].header("If-None-Match"), Some("\"v1\""));
+        let stats = cache.lock().unwrap().stats();
+        assert_eq!((stats.hits, stats.revalidated), (1, 1));
+    }
 }
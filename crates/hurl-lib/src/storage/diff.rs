@@ -0,0 +1,426 @@
+//! Structured comparison of a replayed response against a recorded one.
+
+use crate::http::headers::Headers;
+use crate::http::response::HttpResponse;
+use crate::storage::history::{redact_headers, HistoryEntry};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Headers that differ on every response and would only add noise.
+pub const VOLATILE_HEADERS: &[&str] = &["Date", "Age"];
+
+/// Above this many line pairs a text body diff is not computed; the
+/// bodies are reported as replaced wholesale.
+const MAX_LINE_PAIRS: usize = 4_000_000;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ResponseDiff {
+    pub status_before: u16,
+    pub status_after: u16,
+    pub headers: Vec<HeaderChange>,
+    pub body: BodyDiff,
+    pub duration_before_ms: u128,
+    pub duration_after_ms: u128,
+}
+
+impl ResponseDiff {
+    /// Compares `after` with the response recorded in `before`. Sensitive
+    /// headers are redacted first, as they were when recorded, and
+    /// `VOLATILE_HEADERS` are ignored.
+    pub fn between(before: &HistoryEntry, after: &HttpResponse) -> Self {
+        let mut redacted = after.headers.clone();
+        redact_headers(&mut redacted);
+
+        ResponseDiff {
+            status_before: before.status,
+            status_after: after.status,
+            headers: diff_headers(&before.response_headers, &redacted),
+            body: match before.response_body_size {
+                Some(_) => BodyDiff::NotRecorded,
+                None => BodyDiff::between(&before.response_body, &after.text()),
+            },
+            duration_before_ms: before.duration_ms,
+            duration_after_ms: after.duration.as_millis(),
+        }
+    }
+
+    pub fn status_changed(&self) -> bool {
+        self.status_before != self.status_after
+    }
+
+    /// New latency minus recorded latency, in milliseconds.
+    pub fn latency_delta_ms(&self) -> i128 {
+        self.duration_after_ms as i128 - self.duration_before_ms as i128
+    }
+
+    /// True when status, headers and body all match. Latency is not
+    /// considered.
+    pub fn is_empty(&self) -> bool {
+        !self.status_changed() && self.headers.is_empty() && self.body.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum HeaderChange {
+    Added {
+        name: String,
+        value: String,
+    },
+    Removed {
+        name: String,
+        value: String,
+    },
+    Changed {
+        name: String,
+        before: String,
+        after: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", content = "changes", rename_all = "snake_case")]
+pub enum BodyDiff {
+    Same,
+    /// Both bodies are JSON; changes are listed per path.
+    Json(Vec<JsonChange>),
+    /// Line-based diff of the text bodies.
+    Text(Vec<LineChange>),
+    /// The recorded body was too large or not text, so there is nothing to
+    /// compare against.
+    NotRecorded,
+}
+
+impl BodyDiff {
+    pub fn between(before: &str, after: &str) -> Self {
+        if before == after {
+            return BodyDiff::Same;
+        }
+        if let (Ok(a), Ok(b)) = (
+            serde_json::from_str::<Value>(before),
+            serde_json::from_str::<Value>(after),
+        ) {
+            let mut changes = Vec::new();
+            diff_json("$", &a, &b, &mut changes);
+            return if changes.is_empty() {
+                BodyDiff::Same
+            } else {
+                BodyDiff::Json(changes)
+            };
+        }
+        BodyDiff::Text(diff_lines(before, after))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        matches!(self, BodyDiff::Same)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum JsonChange {
+    Added {
+        path: String,
+        value: Value,
+    },
+    Removed {
+        path: String,
+        value: Value,
+    },
+    Changed {
+        path: String,
+        before: Value,
+        after: Value,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "change", content = "line", rename_all = "snake_case")]
+pub enum LineChange {
+    Added(String),
+    Removed(String),
+}
+
+fn diff_headers(before: &Headers, after: &Headers) -> Vec<HeaderChange> {
+    fn group(headers: &Headers) -> BTreeMap<String, (String, Vec<String>)> {
+        let mut grouped: BTreeMap<String, (String, Vec<String>)> = BTreeMap::new();
+        for (name, value) in headers.iter() {
+            if VOLATILE_HEADERS
+                .iter()
+                .any(|v| v.eq_ignore_ascii_case(name))
+            {
+                continue;
+            }
+            grouped
+                .entry(name.to_ascii_lowercase())
+                .or_insert_with(|| (name.to_string(), Vec::new()))
+                .1
+                .push(value.to_string());
+        }
+        grouped
+    }
+
+    let before = group(before);
+    let after = group(after);
+    let mut changes = Vec::new();
+    for (key, (name, values)) in &before {
+        match after.get(key) {
+            None => changes.push(HeaderChange::Removed {
+                name: name.clone(),
+                value: values.join(", "),
+            }),
+            Some((_, new_values)) if new_values != values => changes.push(HeaderChange::Changed {
+                name: name.clone(),
+                before: values.join(", "),
+                after: new_values.join(", "),
+            }),
+            Some(_) => {}
+        }
+    }
+    for (key, (name, values)) in &after {
+        if !before.contains_key(key) {
+            changes.push(HeaderChange::Added {
+                name: name.clone(),
+                value: values.join(", "),
+            });
+        }
+    }
+    changes
+}
+
+fn diff_json(path: &str, before: &Value, after: &Value, changes: &mut Vec<JsonChange>) {
+    match (before, after) {
+        (Value::Object(a), Value::Object(b)) => {
+            for (key, value) in a {
+                let child = child_path(path, key);
+                match b.get(key) {
+                    Some(other) => diff_json(&child, value, other, changes),
+                    None => changes.push(JsonChange::Removed {
+                        path: child,
+                        value: value.clone(),
+                    }),
+                }
+            }
+            for (key, value) in b {
+                if !a.contains_key(key) {
+                    changes.push(JsonChange::Added {
+                        path: child_path(path, key),
+                        value: value.clone(),
+                    });
+                }
+            }
+        }
+        (Value::Array(a), Value::Array(b)) => {
+            for i in 0..a.len().max(b.len()) {
+                let child = format!("{}[{}]", path, i);
+                match (a.get(i), b.get(i)) {
+                    (Some(x), Some(y)) => diff_json(&child, x, y, changes),
+                    (Some(x), None) => changes.push(JsonChange::Removed {
+                        path: child,
+                        value: x.clone(),
+                    }),
+                    (None, Some(y)) => changes.push(JsonChange::Added {
+                        path: child,
+                        value: y.clone(),
+                    }),
+                    (None, None) => {}
+                }
+            }
+        }
+        (a, b) if a != b => changes.push(JsonChange::Changed {
+            path: path.to_string(),
+            before: a.clone(),
+            after: b.clone(),
+        }),
+        _ => {}
+    }
+}
+
+fn child_path(path: &str, key: &str) -> String {
+    let plain = !key.is_empty()
+        && !key.starts_with(|c: char| c.is_ascii_digit())
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if plain {
+        format!("{}.{}", path, key)
+    } else {
+        format!("{}[{}]", path, Value::String(key.to_string()))
+    }
+}
+
+/// Longest-common-subsequence line diff.
+fn diff_lines(before: &str, after: &str) -> Vec<LineChange> {
+    let a: Vec<&str> = before.lines().collect();
+    let b: Vec<&str> = after.lines().collect();
+    if a.len().saturating_mul(b.len()) > MAX_LINE_PAIRS {
+        return a
+            .iter()
+            .map(|l| LineChange::Removed(l.to_string()))
+            .chain(b.iter().map(|l| LineChange::Added(l.to_string())))
+            .collect();
+    }
+
+    // lcs[i][j]: LCS length of a[i..] and b[j..].
+    let mut lcs = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut changes = Vec::new();
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            i += 1;
+            j += 1;
+        } else if j < b.len() && (i == a.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            changes.push(LineChange::Added(b[j].to_string()));
+            j += 1;
+        } else {
+            changes.push(LineChange::Removed(a[i].to_string()));
+            i += 1;
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::request::RequestBuilder;
+    use crate::storage::history::MAX_RECORDED_BODY;
+    use serde_json::json;
+    use std::time::Duration;
+
+    fn recorded(headers: &[(&str, &str)], body: &str) -> HistoryEntry {
+        let headers: Headers = headers.iter().copied().collect();
+        let response = HttpResponse::new(200, headers, body.to_string())
+            .with_duration(Duration::from_millis(100));
+        let mut entry = HistoryEntry::from_request_response(
+            &RequestBuilder::get("http://a.test/"),
+            &response,
+            vec![],
+        )
+        .unwrap();
+        entry.redact();
+        entry
+    }
+
+    #[test]
+    fn test_identical_responses_have_no_diff() {
+        let before = recorded(&[("Date", "Mon"), ("Set-Cookie", "a=1")], "same");
+        let after = HttpResponse::new(
+            200,
+            Headers::from_iter([("Date", "Tue"), ("Set-Cookie", "a=2")]),
+            "same",
+        )
+        .with_duration(Duration::from_millis(250));
+
+        let diff = ResponseDiff::between(&before, &after);
+        assert!(diff.is_empty());
+        assert_eq!(diff.latency_delta_ms(), 150);
+    }
+
+    #[test]
+    fn test_status_headers_and_json_body() {
+        let before = recorded(
+            &[
+                ("Content-Type", "application/json"),
+                ("X-Old", "1"),
+                ("Vary", "Accept"),
+            ],
+            r#"{"user": {"name": "ann", "roles": ["a", "b"]}, "flag": true}"#,
+        );
+        let mut after = HttpResponse::new(
+            500,
+            Headers::from_iter([
+                ("content-type", "application/json"),
+                ("X-New", "2"),
+                ("Vary", "Accept"),
+                ("Vary", "Origin"),
+            ]),
+            r#"{"user": {"name": "bob", "roles": ["a"]}, "odd key": 1}"#,
+        );
+        after.duration = Duration::from_millis(40);
+
+        let diff = ResponseDiff::between(&before, &after);
+        assert!(diff.status_changed());
+        assert_eq!(diff.latency_delta_ms(), -60);
+        assert_eq!(
+            diff.headers,
+            vec![
+                HeaderChange::Changed {
+                    name: "Vary".into(),
+                    before: "Accept".into(),
+                    after: "Accept, Origin".into()
+                },
+                HeaderChange::Removed {
+                    name: "X-Old".into(),
+                    value: "1".into()
+                },
+                HeaderChange::Added {
+                    name: "X-New".into(),
+                    value: "2".into()
+                },
+            ]
+        );
+        assert_eq!(
+            diff.body,
+            BodyDiff::Json(vec![
+                JsonChange::Removed {
+                    path: "$.flag".into(),
+                    value: json!(true)
+                },
+                JsonChange::Changed {
+                    path: "$.user.name".into(),
+                    before: json!("ann"),
+                    after: json!("bob")
+                },
+                JsonChange::Removed {
+                    path: "$.user.roles[1]".into(),
+                    value: json!("b")
+                },
+                JsonChange::Added {
+                    path: "$[\"odd key\"]".into(),
+                    value: json!(1)
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_json_formatting_alone_is_not_a_change() {
+        assert_eq!(
+            BodyDiff::between("{\"a\":1,\"b\":2}", "{\n  \"b\": 2,\n  \"a\": 1\n}"),
+            BodyDiff::Same
+        );
+    }
+
+    #[test]
+    fn test_unrecorded_body_is_reported() {
+        let large = "x".repeat(MAX_RECORDED_BODY + 1);
+        let before = recorded(&[], &large);
+        assert_eq!(before.response_body, "");
+        assert_eq!(before.response_body_size, Some(large.len() as u64));
+
+        let diff = ResponseDiff::between(&before, &HttpResponse::new(200, Headers::new(), large));
+        assert_eq!(diff.body, BodyDiff::NotRecorded);
+        assert!(!diff.is_empty());
+    }
+
+    #[test]
+    fn test_text_body_line_diff() {
+        assert_eq!(
+            BodyDiff::between("a\nb\nc\n", "a\nc\nd\n"),
+            BodyDiff::Text(vec![
+                LineChange::Removed("b".into()),
+                LineChange::Added("d".into()),
+            ])
+        );
+    }
+}
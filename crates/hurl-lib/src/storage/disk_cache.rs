@@ -0,0 +1,378 @@
+//! A [`ResponseCache`] kept on disk so it survives across `hurl` runs.
+//!
+//! The cache directory holds:
+//! - `index.json`: every entry with its body stripped, plus the counters
+//!   behind [`CacheStats`];
+//! - `blobs/<sha256>`: response bodies, content-addressed so identical
+//!   bodies are stored once;
+//! - `lock`: locked exclusively for the whole of each operation, so several
+//!   processes can share one directory.
+
+use crate::http::request::RequestBuilder;
+use crate::http::response::HttpResponse;
+use crate::storage::cache::{
+    CacheEntry, CacheLookup, CacheMode, CachePolicies, CacheStats, CacheStore, ResponseCache,
+};
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+const INDEX: &str = "index.json";
+const BLOBS: &str = "blobs";
+const LOCK: &str = "lock";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Index {
+    #[serde(default)]
+    hits: usize,
+    #[serde(default)]
+    misses: usize,
+    #[serde(default)]
+    revalidated: usize,
+    #[serde(default)]
+    entries: Vec<(String, CacheEntry)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DiskCache {
+    root: PathBuf,
+    policies: CachePolicies,
+}
+
+impl DiskCache {
+    /// Opens (creating if needed) the cache in `root`. Only
+    /// [`CacheMode::Http`] entries can be persisted, so the policies are
+    /// switched to that mode.
+    pub fn open(root: impl Into<PathBuf>, policies: CachePolicies) -> crate::Result<Self> {
+        let root = root.into();
+        fs::create_dir_all(root.join(BLOBS))?;
+        Ok(DiskCache {
+            root,
+            policies: CachePolicies {
+                mode: CacheMode::Http,
+                ..policies
+            },
+        })
+    }
+
+    /// `$XDG_CACHE_HOME/hurl`, or `~/.cache/hurl` when that is unset.
+    pub fn default_dir() -> Option<PathBuf> {
+        std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+            .map(|dir| dir.join("hurl"))
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Like [`ResponseCache::lookup`]. An entry whose body blob has gone
+    /// missing is dropped and the lookup counts as a miss.
+    pub fn lookup(&self, request: &RequestBuilder) -> crate::Result<CacheLookup> {
+        self.transaction(|cache| match cache.lookup(request) {
+            CacheLookup::Fresh(response) => match self.load_body(cache.find(request), response)? {
+                Some(response) => Ok(CacheLookup::Fresh(response)),
+                None => {
+                    cache.discard_hit(request);
+                    Ok(CacheLookup::Miss)
+                }
+            },
+            other => Ok(other),
+        })
+    }
+
+    pub fn complete(
+        &self,
+        request: &RequestBuilder,
+        response: HttpResponse,
+        request_time: u64,
+    ) -> crate::Result<HttpResponse> {
+        self.transaction(|cache| {
+            let not_modified = response.status == 304;
+            let response = cache.complete(request, response, request_time);
+            if not_modified && response.status != 304 {
+                let stored = self.load_body(cache.find(request), response.clone())?;
+                return Ok(stored.unwrap_or(response));
+            }
+            Ok(response)
+        })
+    }
+
+    pub fn stats(&self) -> crate::Result<CacheStats> {
+        let _lock = self.lock()?;
+        Ok(self.read_cache()?.stats())
+    }
+
+    /// Every stored entry, keyed like [`ResponseCache::put`]. Bodies are
+    /// not loaded.
+    pub fn entries(&self) -> crate::Result<Vec<(String, CacheEntry)>> {
+        let _lock = self.lock()?;
+        Ok(self.read_index()?.entries)
+    }
+
+    /// Removes every entry and body and resets the counters.
+    pub fn clear(&self) -> crate::Result<()> {
+        self.transaction(|cache| {
+            cache.clear();
+            Ok(())
+        })
+    }
+
+    /// Runs `f` on the cache loaded from disk while holding the lock, then
+    /// writes the result back.
+    fn transaction<T>(
+        &self,
+        f: impl FnOnce(&mut ResponseCache) -> crate::Result<T>,
+    ) -> crate::Result<T> {
+        let _lock = self.lock()?;
+        let mut cache = self.read_cache()?;
+        let result = f(&mut cache)?;
+        self.write_cache(cache)?;
+        Ok(result)
+    }
+
+    /// Held until the returned file is dropped.
+    fn lock(&self) -> crate::Result<File> {
+        let file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.root.join(LOCK))?;
+        file.lock()?;
+        Ok(file)
+    }
+
+    fn read_index(&self) -> crate::Result<Index> {
+        match fs::read(self.root.join(INDEX)) {
+            // An unreadable index is dropped rather than failing every
+            // request; its blobs are collected on the next write.
+            Ok(content) => Ok(serde_json::from_slice(&content).unwrap_or_default()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Index::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn read_cache(&self) -> crate::Result<ResponseCache> {
+        let index = self.read_index()?;
+        Ok(ResponseCache::from_parts(
+            self.policies.clone(),
+            index.entries,
+            (index.hits, index.misses, index.revalidated),
+        ))
+    }
+
+    fn write_cache(&self, cache: ResponseCache) -> crate::Result<()> {
+        let (mut entries, (hits, misses, revalidated)) = cache.into_parts();
+
+        let mut live = HashSet::new();
+        for (_, entry) in &mut entries {
+            if entry.size > 0 && entry.response.body.len() as u64 == entry.size {
+                let blob = self.blob_path(&entry.digest);
+                if !blob.exists() {
+                    write_atomically(&blob, &entry.response.body)?;
+                }
+            }
+            entry.response.body = Bytes::new();
+            live.insert(entry.digest.clone());
+        }
+
+        let index = Index {
+            hits,
+            misses,
+            revalidated,
+            entries,
+        };
+        write_atomically(&self.root.join(INDEX), &serde_json::to_vec(&index)?)?;
+
+        for blob in fs::read_dir(self.root.join(BLOBS))? {
+            let blob = blob?;
+            if !live.contains(blob.file_name().to_string_lossy().as_ref()) {
+                fs::remove_file(blob.path())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Fills in the body of a response served from `entry`. Returns `None`
+    /// when the blob has gone missing.
+    fn load_body(
+        &self,
+        entry: Option<&CacheEntry>,
+        mut response: HttpResponse,
+    ) -> crate::Result<Option<HttpResponse>> {
+        let Some(entry) = entry.filter(|e| e.size > 0) else {
+            return Ok(Some(response));
+        };
+        match fs::read(self.blob_path(&entry.digest)) {
+            Ok(body) => {
+                response.body = body.into();
+                Ok(Some(response))
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn blob_path(&self, digest: &str) -> PathBuf {
+        self.root.join(BLOBS).join(digest)
+    }
+}
+
+impl CacheStore for DiskCache {
+    fn lookup(&self, request: &RequestBuilder) -> crate::Result<CacheLookup> {
+        DiskCache::lookup(self, request)
+    }
+
+    fn complete(
+        &self,
+        request: &RequestBuilder,
+        response: HttpResponse,
+        request_time: u64,
+    ) -> crate::Result<HttpResponse> {
+        DiskCache::complete(self, request, response, request_time)
+    }
+}
+
+/// Writes through a temporary file and a rename, so readers never see a
+/// partial file.
+fn write_atomically(path: &Path, content: &[u8]) -> crate::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, content)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::headers::Headers;
+    use std::sync::Arc;
+
+    fn policies() -> CachePolicies {
+        CachePolicies::default()
+    }
+
+    fn fresh(body: &str) -> HttpResponse {
+        HttpResponse::new(
+            200,
+            Headers::from_iter([("Cache-Control", "max-age=600")]),
+            body.to_string(),
+        )
+    }
+
+    fn now() -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    fn blobs(dir: &Path) -> usize {
+        fs::read_dir(dir.join(BLOBS)).unwrap().count()
+    }
+
+    #[test]
+    fn test_entries_and_stats_survive_reopening() {
+        let dir = tempfile::tempdir().unwrap();
+        let request = RequestBuilder::get("https://api.example.com/a");
+
+        let cache = DiskCache::open(dir.path(), policies()).unwrap();
+        assert!(matches!(cache.lookup(&request).unwrap(), CacheLookup::Miss));
+        cache.complete(&request, fresh("hello"), now()).unwrap();
+
+        let reopened = DiskCache::open(dir.path(), policies()).unwrap();
+        let CacheLookup::Fresh(hit) = reopened.lookup(&request).unwrap() else {
+            panic!("expected a hit after reopening");
+        };
+        assert_eq!(hit.text(), "hello");
+
+        let stats = reopened.stats().unwrap();
+        assert_eq!((stats.hits, stats.misses, stats.size), (1, 1, 1));
+        assert_eq!(stats.bytes, 5);
+
+        let index = fs::read_to_string(dir.path().join(INDEX)).unwrap();
+        assert!(!index.contains("hello"));
+    }
+
+    #[test]
+    fn test_identical_bodies_share_a_blob_and_lru_evicts_by_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DiskCache::open(
+            dir.path(),
+            CachePolicies {
+                max_bytes: 10,
+                ..policies()
+            },
+        )
+        .unwrap();
+        let a = RequestBuilder::get("https://example.com/a");
+        let b = RequestBuilder::get("https://example.com/b");
+        let c = RequestBuilder::get("https://example.com/c");
+
+        cache.complete(&a, fresh("12345"), now()).unwrap();
+        cache.complete(&b, fresh("12345"), now()).unwrap();
+        assert_eq!(blobs(dir.path()), 1);
+
+        cache.complete(&c, fresh("abcde"), now()).unwrap();
+        let keys: Vec<String> = cache
+            .entries()
+            .unwrap()
+            .into_iter()
+            .map(|(k, _)| k)
+            .collect();
+        assert_eq!(keys.len(), 2);
+        assert_eq!(blobs(dir.path()), 2);
+
+        cache.clear().unwrap();
+        assert_eq!(cache.stats().unwrap().size, 0);
+        assert_eq!(blobs(dir.path()), 0);
+    }
+
+    #[test]
+    fn test_missing_blob_is_a_miss() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DiskCache::open(dir.path(), policies()).unwrap();
+        let request = RequestBuilder::get("https://example.com/");
+        cache.complete(&request, fresh("body"), now()).unwrap();
+
+        for blob in fs::read_dir(dir.path().join(BLOBS)).unwrap() {
+            fs::remove_file(blob.unwrap().path()).unwrap();
+        }
+        assert!(matches!(cache.lookup(&request).unwrap(), CacheLookup::Miss));
+        let stats = cache.stats().unwrap();
+        assert_eq!((stats.hits, stats.misses), (0, 1));
+        assert!(cache.entries().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_concurrent_writers_do_not_lose_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = Arc::new(dir.path().to_path_buf());
+        let writers: Vec<_> = (0..8)
+            .map(|i| {
+                let root = root.clone();
+                std::thread::spawn(move || {
+                    // A separate handle per thread, like separate processes.
+                    let cache = DiskCache::open(root.as_path(), policies()).unwrap();
+                    let request = RequestBuilder::get(format!("https://example.com/{}", i));
+                    cache
+                        .complete(&request, fresh(&i.to_string()), now())
+                        .unwrap();
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let cache = DiskCache::open(dir.path(), policies()).unwrap();
+        assert_eq!(cache.entries().unwrap().len(), 8);
+        assert_eq!(blobs(dir.path()), 8);
+    }
+}
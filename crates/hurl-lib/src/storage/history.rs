@@ -1,20 +1,88 @@
-use crate::http::request::RequestBuilder;
+use crate::http::headers::Headers;
+use crate::http::multipart::Part;
+use crate::http::request::{RequestBody, RequestBuilder};
 use crate::http::response::HttpResponse;
 use crate::Result;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::Bound;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+/// Headers whose values never reach a history file.
+pub const SENSITIVE_HEADERS: &[&str] = &[
+    "Authorization",
+    "Proxy-Authorization",
+    "Cookie",
+    "Set-Cookie",
+    "X-Api-Key",
+    "X-Auth-Token",
+    "X-Csrf-Token",
+];
+
+/// Stands in for the value of a redacted header.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Request and response bodies larger than this are not recorded, only
+/// their size.
+pub const MAX_RECORDED_BODY: usize = 64 * 1024;
+
+/// What the history keeps of one multipart part. The contents of parts,
+/// which may be large files, are never recorded.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct RecordedPart {
+    pub name: String,
+    pub filename: Option<String>,
+    pub content_type: Option<String>,
+    pub size: u64,
+}
+
+impl RecordedPart {
+    fn from_part(part: &Part) -> Result<Self> {
+        Ok(RecordedPart {
+            name: part.name.clone(),
+            filename: part.filename.clone(),
+            content_type: part.content_type.clone(),
+            size: part.len()?,
+        })
+    }
+}
+
+/// Replaces each value of a `SENSITIVE_HEADERS` header with `REDACTED`,
+/// keeping how many there were.
+pub(crate) fn redact_headers(headers: &mut Headers) {
+    for name in SENSITIVE_HEADERS {
+        let count = headers.get_all(name).len();
+        if count > 0 {
+            headers.remove(name);
+            for _ in 0..count {
+                headers.append(*name, REDACTED);
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct HistoryEntry {
     pub id: String,
     pub timestamp: u64,
     pub url: String,
     pub method: String,
-    pub headers: HashMap<String, String>,
+    pub headers: Headers,
     pub body: Option<String>,
+    /// Size of a request body that was sent but not recorded: multipart,
+    /// not UTF-8, or larger than `MAX_RECORDED_BODY`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body_size: Option<u64>,
+    /// The parts of a multipart request body.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub body_parts: Vec<RecordedPart>,
     pub status: u16,
-    pub response_headers: HashMap<String, String>,
+    pub response_headers: Headers,
+    /// Empty when the body was not recorded; see `response_body_size`.
     pub response_body: String,
+    /// Size of a response body that was not recorded: not text, or larger
+    /// than `MAX_RECORDED_BODY`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_body_size: Option<u64>,
     pub duration_ms: u128,
     pub tags: Vec<String>,
 }
@@ -32,107 +100,282 @@ impl HistoryEntry {
             .unwrap_or(Duration::from_secs(0))
             .as_secs();
 
-        let body = if let Some(body) = request.body() {
-            Some(String::from_utf8_lossy(&body.to_bytes()?).to_string())
-        } else {
-            None
+        let (body, body_size, body_parts) = match request.body() {
+            None => (None, None, Vec::new()),
+            Some(RequestBody::Multipart(form)) => {
+                let parts = form
+                    .parts()
+                    .iter()
+                    .map(RecordedPart::from_part)
+                    .collect::<Result<_>>()?;
+                (None, Some(form.content_length()?), parts)
+            }
+            Some(body) => match String::from_utf8(body.to_bytes()?) {
+                Ok(text) if text.len() <= MAX_RECORDED_BODY => (Some(text), None, Vec::new()),
+                Ok(text) => (None, Some(text.len() as u64), Vec::new()),
+                Err(e) => (None, Some(e.as_bytes().len() as u64), Vec::new()),
+            },
+        };
+
+        let size = response.body.len();
+        let text = (size <= MAX_RECORDED_BODY)
+            .then(|| response.try_text())
+            .flatten();
+        let (response_body, response_body_size) = match text {
+            Some(text) => (text, None),
+            None => (String::new(), Some(size as u64)),
         };
 
+        // Record the Content-Type the client derives from the body, so a
+        // replayed multipart request keeps its boundary.
+        let mut headers = request.headers().clone();
+        if let Some(body) = request.body() {
+            if !headers.contains("Content-Type") {
+                headers.append("Content-Type", body.content_type());
+            }
+        }
+
         Ok(HistoryEntry {
             id: uuid::Uuid::new_v4().to_string(),
             timestamp,
             url: request.url().to_string(),
             method: request.method().to_string(),
-            headers: request.headers().clone(),
+            headers,
             body,
+            body_size,
+            body_parts,
             status: response.status,
             response_headers: response.headers.clone(),
-            response_body: response.body.clone(),
+            response_body,
+            response_body_size,
             duration_ms: response.duration.as_millis(),
             tags,
         })
     }
+
+    /// Replaces the values of `SENSITIVE_HEADERS` in both the request and
+    /// the response headers.
+    pub fn redact(&mut self) {
+        redact_headers(&mut self.headers);
+        redact_headers(&mut self.response_headers);
+    }
+
+    /// False when the request had a body that was not recorded, so
+    /// `to_request` cannot reproduce it.
+    pub fn is_replayable(&self) -> bool {
+        self.body_size.is_none()
+    }
+
+    /// Rebuilds the request so it can be sent again. Redacted headers are
+    /// left out; supply credentials again when replaying.
+    pub fn to_request(&self) -> RequestBuilder {
+        let mut request = RequestBuilder::new(&self.url, &self.method);
+        for (name, value) in &self.headers {
+            if value != REDACTED {
+                request = request.append_header(name, value);
+            }
+        }
+        if let Some(body) = &self.body {
+            request = request.text_body(body.clone());
+        }
+        request
+    }
 }
 
+/// Entries in insertion order, indexed for search.
+///
+/// Besides the entries themselves the store keeps an id index (also used
+/// for prefix lookups), a timestamp index for range queries, and an
+/// inverted index from the words of request and response bodies to the
+/// entries containing them, so `SearchQuery::text` does not scan bodies.
 #[derive(Debug)]
 pub struct HistoryStore {
-    entries: Vec<HistoryEntry>,
+    entries: BTreeMap<u64, HistoryEntry>,
+    next_seq: u64,
+    ids: BTreeMap<String, u64>,
+    by_time: BTreeSet<(u64, u64)>,
+    words: HashMap<String, BTreeSet<u64>>,
 }
 
 impl HistoryStore {
     pub fn new() -> Self {
         HistoryStore {
-            entries: Vec::new(),
+            entries: BTreeMap::new(),
+            next_seq: 0,
+            ids: BTreeMap::new(),
+            by_time: BTreeSet::new(),
+            words: HashMap::new(),
         }
     }
 
+    /// Adds an entry. One with the same id replaces the earlier entry.
     pub fn save(&mut self, entry: HistoryEntry) -> String {
+        let words = entry_words(&entry);
+        self.save_indexed(entry, words)
+    }
+
+    /// Adds an entry whose body words are already known, such as one read
+    /// back from a persisted index with its bodies left out.
+    pub(crate) fn save_indexed(&mut self, entry: HistoryEntry, words: BTreeSet<String>) -> String {
         let id = entry.id.clone();
-        self.entries.push(entry);
+        self.delete(&id);
+
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.ids.insert(id.clone(), seq);
+        self.by_time.insert((entry.timestamp, seq));
+        for word in words {
+            self.words.entry(word).or_default().insert(seq);
+        }
+        self.entries.insert(seq, entry);
         id
     }
 
     pub fn get(&self, id: &str) -> Option<&HistoryEntry> {
-        self.entries.iter().find(|e| e.id == id)
+        self.ids.get(id).and_then(|seq| self.entries.get(seq))
+    }
+
+    /// Looks an entry up by its full id or by an unambiguous id prefix.
+    pub fn resolve(&self, id: &str) -> Result<&HistoryEntry> {
+        if let Some(entry) = self.get(id) {
+            return Ok(entry);
+        }
+        let mut matches = self
+            .ids
+            .range::<str, _>((Bound::Included(id), Bound::Unbounded))
+            .take_while(|(candidate, _)| candidate.starts_with(id));
+        match (matches.next(), matches.next()) {
+            (Some((_, seq)), None) if !id.is_empty() => Ok(&self.entries[seq]),
+            (Some(_), Some(_)) => Err(crate::Error::Config(format!(
+                "history id '{}' is ambiguous",
+                id
+            ))),
+            _ => Err(crate::Error::Config(format!(
+                "no history entry with id '{}'",
+                id
+            ))),
+        }
     }
 
+    /// The page of matching entries selected by the query's offset and
+    /// limit.
     pub fn search(&self, query: &SearchQuery) -> Vec<&HistoryEntry> {
-        self.entries
-            .iter()
-            .filter(|e| {
-                let url_match = query
-                    .url
-                    .as_ref()
-                    .map(|u| e.url.contains(u))
-                    .unwrap_or(true);
-                let method_match = query
-                    .method
-                    .as_ref()
-                    .map(|m| e.method.eq_ignore_ascii_case(m))
-                    .unwrap_or(true);
-                let status_match = query.status.map(|s| e.status == s).unwrap_or(true);
-                let tag_match = query
-                    .tags
-                    .as_ref()
-                    .map(|tags| tags.iter().any(|t| e.tags.contains(t)))
-                    .unwrap_or(true);
+        self.search_page(query).entries
+    }
 
-                url_match && method_match && status_match && tag_match
-            })
-            .collect()
+    /// Like [`search`](Self::search), but also reports how many entries
+    /// matched before pagination.
+    pub fn search_page(&self, query: &SearchQuery) -> SearchPage<'_> {
+        let mut matched: Vec<&HistoryEntry> = self
+            .candidates(query)
+            .into_iter()
+            .filter_map(|seq| self.entries.get(&seq))
+            .filter(|entry| query.matches(entry))
+            .collect();
+
+        match query.sort {
+            SortKey::Timestamp => matched.sort_by_key(|e| e.timestamp),
+            SortKey::Duration => matched.sort_by_key(|e| e.duration_ms),
+            SortKey::Status => matched.sort_by_key(|e| e.status),
+            SortKey::Method => matched.sort_by(|a, b| a.method.cmp(&b.method)),
+            SortKey::Url => matched.sort_by(|a, b| a.url.cmp(&b.url)),
+        }
+        if query.descending {
+            matched.reverse();
+        }
+
+        let total = matched.len();
+        let entries = matched
+            .into_iter()
+            .skip(query.offset)
+            .take(query.limit.unwrap_or(usize::MAX))
+            .collect();
+        SearchPage { total, entries }
+    }
+
+    /// Sequence numbers, ascending, of the entries that can match `query`
+    /// according to the indexes. The remaining filters are checked by
+    /// `SearchQuery::matches`.
+    fn candidates(&self, query: &SearchQuery) -> Vec<u64> {
+        let words: BTreeSet<String> = query.text.as_deref().map(words).unwrap_or_default();
+        if !words.is_empty() {
+            let mut postings = Vec::new();
+            for word in &words {
+                match self.words.get(word) {
+                    Some(seqs) => postings.push(seqs),
+                    None => return Vec::new(),
+                }
+            }
+            postings.sort_by_key(|seqs| seqs.len());
+            let (first, rest) = postings.split_first().expect("at least one word");
+            return first
+                .iter()
+                .filter(|seq| rest.iter().all(|seqs| seqs.contains(seq)))
+                .copied()
+                .collect();
+        }
+
+        if query.since.is_some() || query.until.is_some() {
+            let from = (query.since.unwrap_or(0), 0);
+            let to = (query.until.unwrap_or(u64::MAX), u64::MAX);
+            let mut seqs: Vec<u64> = self.by_time.range(from..=to).map(|&(_, seq)| seq).collect();
+            seqs.sort_unstable();
+            return seqs;
+        }
+
+        self.entries.keys().copied().collect()
     }
 
     pub fn delete(&mut self, id: &str) -> bool {
-        if let Some(pos) = self.entries.iter().position(|e| e.id == id) {
-            self.entries.remove(pos);
-            true
-        } else {
-            false
+        let Some(seq) = self.ids.remove(id) else {
+            return false;
+        };
+        let entry = self.entries.remove(&seq).expect("indexed entry");
+        self.by_time.remove(&(entry.timestamp, seq));
+        for word in entry_words(&entry) {
+            if let Some(seqs) = self.words.get_mut(&word) {
+                seqs.remove(&seq);
+                if seqs.is_empty() {
+                    self.words.remove(&word);
+                }
+            }
         }
+        true
     }
 
     pub fn list_all(&self) -> Vec<&HistoryEntry> {
-        self.entries.iter().collect()
+        self.entries.values().collect()
     }
 
     pub fn export_to_json(&self) -> Result<String> {
-        serde_json::to_string_pretty(&self.entries).map_err(crate::Error::Serialization)
+        let entries: Vec<&HistoryEntry> = self.entries.values().collect();
+        serde_json::to_string_pretty(&entries).map_err(crate::Error::Serialization)
     }
 
     pub fn import_from_json(&mut self, json: &str) -> Result<()> {
         let entries: Vec<HistoryEntry> =
             serde_json::from_str(json).map_err(crate::Error::Serialization)?;
-        self.entries = entries;
+        self.clear();
+        for entry in entries {
+            self.save(entry);
+        }
         Ok(())
     }
 
     pub fn clear(&mut self) {
         self.entries.clear();
+        self.ids.clear();
+        self.by_time.clear();
+        self.words.clear();
     }
 
     pub fn len(&self) -> usize {
         self.entries.len()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
 }
 
 impl Default for HistoryStore {
@@ -141,12 +384,99 @@ impl Default for HistoryStore {
     }
 }
 
+/// Lowercased alphanumeric runs of `text`, as stored in the word index.
+fn words(text: &str) -> BTreeSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+pub(crate) fn entry_words(entry: &HistoryEntry) -> BTreeSet<String> {
+    let mut all = words(&entry.response_body);
+    if let Some(body) = &entry.body {
+        all.extend(words(body));
+    }
+    all
+}
+
+/// One page of search results.
+#[derive(Debug)]
+pub struct SearchPage<'a> {
+    /// Number of matching entries across all pages.
+    pub total: usize,
+    pub entries: Vec<&'a HistoryEntry>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortKey {
+    #[default]
+    Timestamp,
+    Duration,
+    Status,
+    Method,
+    Url,
+}
+
+impl SortKey {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "timestamp" | "time" | "date" => Some(SortKey::Timestamp),
+            "duration" => Some(SortKey::Duration),
+            "status" => Some(SortKey::Status),
+            "method" => Some(SortKey::Method),
+            "url" => Some(SortKey::Url),
+            _ => None,
+        }
+    }
+}
+
+/// Matches entries carrying header `name` in the request or the response,
+/// optionally with a value containing `value`.
+#[derive(Debug, Clone)]
+pub struct HeaderFilter {
+    pub name: String,
+    pub value: Option<String>,
+}
+
+impl HeaderFilter {
+    fn matches(&self, entry: &HistoryEntry) -> bool {
+        [&entry.headers, &entry.response_headers]
+            .into_iter()
+            .flat_map(|headers| headers.get_all(&self.name))
+            .any(|value| {
+                self.value
+                    .as_ref()
+                    .is_none_or(|v| value.contains(v.as_str()))
+            })
+    }
+}
+
+/// Filters, ordering and pagination for [`HistoryStore::search`]. Every
+/// filter that is set must match. Results are oldest first unless another
+/// sort key or `descending` is chosen.
 #[derive(Debug, Clone)]
 pub struct SearchQuery {
     pub url: Option<String>,
     pub method: Option<String>,
     pub status: Option<u16>,
+    /// Hundreds digit of the status, e.g. `4` for any 4xx.
+    pub status_class: Option<u16>,
     pub tags: Option<Vec<String>>,
+    /// Earliest `timestamp`, inclusive, in seconds since the epoch.
+    pub since: Option<u64>,
+    /// Latest `timestamp`, inclusive, in seconds since the epoch.
+    pub until: Option<u64>,
+    pub min_duration_ms: Option<u128>,
+    pub max_duration_ms: Option<u128>,
+    pub headers: Vec<HeaderFilter>,
+    /// Words that must all appear in the request or response body,
+    /// compared case-insensitively.
+    pub text: Option<String>,
+    pub sort: SortKey,
+    pub descending: bool,
+    pub offset: usize,
+    pub limit: Option<usize>,
 }
 
 impl SearchQuery {
@@ -155,7 +485,18 @@ impl SearchQuery {
             url: None,
             method: None,
             status: None,
+            status_class: None,
             tags: None,
+            since: None,
+            until: None,
+            min_duration_ms: None,
+            max_duration_ms: None,
+            headers: Vec::new(),
+            text: None,
+            sort: SortKey::default(),
+            descending: false,
+            offset: 0,
+            limit: None,
         }
     }
 
@@ -174,10 +515,102 @@ impl SearchQuery {
         self
     }
 
+    pub fn status_class(mut self, class: u16) -> Self {
+        self.status_class = Some(class);
+        self
+    }
+
     pub fn tags(mut self, tags: Vec<String>) -> Self {
         self.tags = Some(tags);
         self
     }
+
+    pub fn since(mut self, timestamp: u64) -> Self {
+        self.since = Some(timestamp);
+        self
+    }
+
+    pub fn until(mut self, timestamp: u64) -> Self {
+        self.until = Some(timestamp);
+        self
+    }
+
+    pub fn min_duration_ms(mut self, ms: u128) -> Self {
+        self.min_duration_ms = Some(ms);
+        self
+    }
+
+    pub fn max_duration_ms(mut self, ms: u128) -> Self {
+        self.max_duration_ms = Some(ms);
+        self
+    }
+
+    pub fn header(mut self, name: &str, value: Option<&str>) -> Self {
+        self.headers.push(HeaderFilter {
+            name: name.to_string(),
+            value: value.map(str::to_string),
+        });
+        self
+    }
+
+    pub fn text(mut self, text: &str) -> Self {
+        self.text = Some(text.to_string());
+        self
+    }
+
+    pub fn sort_by(mut self, key: SortKey) -> Self {
+        self.sort = key;
+        self
+    }
+
+    pub fn descending(mut self) -> Self {
+        self.descending = true;
+        self
+    }
+
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Checks every filter except `text`, which the store answers from its
+    /// word index.
+    fn matches(&self, e: &HistoryEntry) -> bool {
+        let url_match = self.url.as_ref().map(|u| e.url.contains(u)).unwrap_or(true);
+        let method_match = self
+            .method
+            .as_ref()
+            .map(|m| e.method.eq_ignore_ascii_case(m))
+            .unwrap_or(true);
+        let status_match = self.status.map(|s| e.status == s).unwrap_or(true)
+            && self
+                .status_class
+                .map(|c| e.status / 100 == c)
+                .unwrap_or(true);
+        let tag_match = self
+            .tags
+            .as_ref()
+            .map(|tags| tags.iter().any(|t| e.tags.contains(t)))
+            .unwrap_or(true);
+        let time_match = self.since.is_none_or(|s| e.timestamp >= s)
+            && self.until.is_none_or(|u| e.timestamp <= u);
+        let duration_match = self.min_duration_ms.is_none_or(|m| e.duration_ms >= m)
+            && self.max_duration_ms.is_none_or(|m| e.duration_ms <= m);
+        let header_match = self.headers.iter().all(|h| h.matches(e));
+
+        url_match
+            && method_match
+            && status_match
+            && tag_match
+            && time_match
+            && duration_match
+            && header_match
+    }
 }
 
 impl Default for SearchQuery {
@@ -185,3 +618,211 @@ impl Default for SearchQuery {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::multipart::Multipart;
+
+    fn entry(
+        path: &str,
+        timestamp: u64,
+        status: u16,
+        duration_ms: u128,
+        body: &str,
+    ) -> HistoryEntry {
+        let request = RequestBuilder::post(format!("https://api.example.com{}", path))
+            .header("X-Client", "mobile/2.1")
+            .text_body("query=alpha");
+        let response = HttpResponse::new(
+            status,
+            vec![("Content-Type".to_string(), "application/json".to_string())],
+            body.to_string(),
+        )
+        .with_duration(Duration::from_millis(duration_ms as u64));
+        let mut entry = HistoryEntry::from_request_response(&request, &response, vec![]).unwrap();
+        entry.timestamp = timestamp;
+        entry
+    }
+
+    fn store() -> HistoryStore {
+        let mut store = HistoryStore::new();
+        store.save(entry("/a", 100, 200, 30, r#"{"user": "Ann Smith"}"#));
+        store.save(entry("/b", 200, 404, 900, r#"{"error": "user not found"}"#));
+        store.save(entry("/c", 300, 503, 1500, "upstream timeout"));
+        store.save(entry("/d", 400, 201, 80, r#"{"user": "Bob Smith"}"#));
+        store
+    }
+
+    fn paths(entries: &[&HistoryEntry]) -> Vec<String> {
+        entries
+            .iter()
+            .map(|e| {
+                e.url
+                    .trim_start_matches("https://api.example.com")
+                    .to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_large_and_multipart_bodies_are_summarized() {
+        let dir = tempfile::tempdir().unwrap();
+        let upload = dir.path().join("upload.bin");
+        std::fs::write(&upload, vec![0xffu8; MAX_RECORDED_BODY * 4]).unwrap();
+        let response = HttpResponse::new(201, Headers::new(), "created");
+
+        let form = Multipart::new()
+            .text("title", "holiday")
+            .file("photo", &upload);
+        let request = RequestBuilder::post("https://api.example.com/upload").multipart_body(form);
+        let entry = HistoryEntry::from_request_response(&request, &response, vec![]).unwrap();
+        assert!(entry.body.is_none());
+        assert!(!entry.is_replayable());
+        assert_eq!(
+            entry.body_parts,
+            vec![
+                RecordedPart {
+                    name: "title".into(),
+                    filename: None,
+                    content_type: None,
+                    size: 7,
+                },
+                RecordedPart {
+                    name: "photo".into(),
+                    filename: Some("upload.bin".into()),
+                    content_type: Some("application/octet-stream".into()),
+                    size: (MAX_RECORDED_BODY * 4) as u64,
+                },
+            ]
+        );
+        assert!(entry.body_size.unwrap() > (MAX_RECORDED_BODY * 4) as u64);
+        assert!(serde_json::to_string(&entry).unwrap().len() < 2048);
+
+        let large = "a".repeat(MAX_RECORDED_BODY + 1);
+        let request = RequestBuilder::post("https://api.example.com/").text_body(large);
+        let entry = HistoryEntry::from_request_response(&request, &response, vec![]).unwrap();
+        assert_eq!(entry.body, None);
+        assert_eq!(entry.body_size, Some(MAX_RECORDED_BODY as u64 + 1));
+
+        let request = RequestBuilder::post("https://api.example.com/").text_body("small");
+        let entry = HistoryEntry::from_request_response(&request, &response, vec![]).unwrap();
+        assert_eq!(entry.body.as_deref(), Some("small"));
+        assert!(entry.is_replayable());
+        assert_eq!(entry.response_body, "created");
+        assert_eq!(entry.response_body_size, None);
+    }
+
+    #[test]
+    fn test_large_and_binary_response_bodies_are_summarized() {
+        let request = RequestBuilder::get("https://api.example.com/download");
+        let large = HttpResponse::new(200, Headers::new(), "word ".repeat(MAX_RECORDED_BODY));
+        let entry = HistoryEntry::from_request_response(&request, &large, vec![]).unwrap();
+        assert_eq!(entry.response_body, "");
+        assert_eq!(entry.response_body_size, Some(5 * MAX_RECORDED_BODY as u64));
+        assert!(entry_words(&entry).is_empty());
+        assert!(serde_json::to_string(&entry).unwrap().len() < 2048);
+
+        let binary = HttpResponse::new(200, Headers::new(), vec![0x89u8, b'P', 0x00, 0xc3]);
+        let entry = HistoryEntry::from_request_response(&request, &binary, vec![]).unwrap();
+        assert_eq!(entry.response_body, "");
+        assert_eq!(entry.response_body_size, Some(4));
+    }
+
+    #[test]
+    fn test_time_duration_status_class_and_header_filters() {
+        let store = store();
+        let found = |query: SearchQuery| paths(&store.search(&query));
+
+        assert_eq!(
+            found(SearchQuery::new().since(200).until(300)),
+            ["/b", "/c"]
+        );
+        assert_eq!(found(SearchQuery::new().until(100)), ["/a"]);
+        assert_eq!(found(SearchQuery::new().min_duration_ms(900)), ["/b", "/c"]);
+        assert_eq!(found(SearchQuery::new().max_duration_ms(80)), ["/a", "/d"]);
+        assert_eq!(found(SearchQuery::new().status_class(2)), ["/a", "/d"]);
+        assert_eq!(found(SearchQuery::new().status_class(5).since(250)), ["/c"]);
+        assert_eq!(
+            found(SearchQuery::new().header("x-client", Some("mobile/"))).len(),
+            4
+        );
+        assert_eq!(
+            found(SearchQuery::new().header("content-type", Some("json"))).len(),
+            4
+        );
+        assert!(found(SearchQuery::new().header("X-Client", Some("web"))).is_empty());
+        assert!(found(SearchQuery::new().header("Cookie", None)).is_empty());
+    }
+
+    #[test]
+    fn test_full_text_search_uses_word_index() {
+        let mut store = store();
+        let found =
+            |store: &HistoryStore, text: &str| paths(&store.search(&SearchQuery::new().text(text)));
+
+        assert_eq!(found(&store, "SMITH"), ["/a", "/d"]);
+        assert_eq!(found(&store, "bob smith"), ["/d"]);
+        assert_eq!(found(&store, "user"), ["/a", "/b", "/d"]);
+        assert_eq!(found(&store, "alpha").len(), 4);
+        assert!(found(&store, "smith timeout").is_empty());
+        assert!(found(&store, "nowhere").is_empty());
+        assert_eq!(
+            paths(&store.search(&SearchQuery::new().text("user").status_class(4))),
+            ["/b"]
+        );
+
+        let id = store.list_all()[3].id.clone();
+        assert!(store.delete(&id));
+        assert_eq!(found(&store, "smith"), ["/a"]);
+        assert!(found(&store, "bob").is_empty());
+        assert!(!store.words.contains_key("bob"));
+    }
+
+    #[test]
+    fn test_sorting_and_pagination() {
+        let store = store();
+        let query = SearchQuery::new()
+            .sort_by(SortKey::Duration)
+            .descending()
+            .offset(1)
+            .limit(2);
+        let page = store.search_page(&query);
+        assert_eq!(page.total, 4);
+        assert_eq!(paths(&page.entries), ["/b", "/d"]);
+
+        let newest = SearchQuery::new().descending().limit(1);
+        assert_eq!(paths(&store.search(&newest)), ["/d"]);
+
+        let past_end = store.search_page(&SearchQuery::new().offset(10));
+        assert_eq!(past_end.total, 4);
+        assert!(past_end.entries.is_empty());
+
+        assert_eq!(SortKey::parse("Status"), Some(SortKey::Status));
+        assert_eq!(SortKey::parse("size"), None);
+    }
+
+    #[test]
+    fn test_save_replaces_same_id_and_resolve_prefix() {
+        let mut store = store();
+        let mut updated = store.list_all()[0].clone();
+        updated.status = 500;
+        let id = store.save(updated);
+
+        assert_eq!(store.len(), 4);
+        assert_eq!(store.get(&id).unwrap().status, 500);
+        assert_eq!(store.resolve(&id[..8]).unwrap().id, id);
+        assert!(store.resolve("").is_err());
+        assert!(store.resolve("zzzz").is_err());
+
+        let json = store.export_to_json().unwrap();
+        let mut imported = HistoryStore::new();
+        imported.import_from_json(&json).unwrap();
+        assert_eq!(imported.len(), 4);
+        assert_eq!(
+            paths(&imported.search(&SearchQuery::new().text("smith"))),
+            ["/a", "/d"]
+        );
+        assert_eq!(paths(&imported.list_all()), ["/b", "/c", "/d", "/a"]);
+    }
+}
@@ -0,0 +1,555 @@
+//! A durable, append-only history of requests.
+//!
+//! Entries are written one JSON object per line to `history.jsonl` in the
+//! log directory. Once that file would grow past `max_bytes` it is rotated
+//! to `history.1.jsonl`, older files shift up, and anything beyond
+//! `max_files` rotated files is dropped. A `lock` file is held for every
+//! read and write so several `hurl` processes can share the log.
+//!
+//! Each history file has an index beside it (`history.idx`,
+//! `history.1.idx`, ...) with one line per entry: the entry without its
+//! bodies, the words of those bodies and the entry's position in the file.
+//! `append` extends it, so [`HistoryLog::search`] and
+//! [`HistoryLog::resolve`] never parse bodies and only read the entries
+//! they return. An index that is missing or does not cover its file, say
+//! after a crash between the two writes, is rebuilt on the next read.
+
+use crate::storage::history::{entry_words, HistoryEntry, HistoryStore, SearchQuery, SortKey};
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+const CURRENT: &str = "history.jsonl";
+const LOCK: &str = "lock";
+
+/// One line of a history file's index.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct IndexRecord {
+    offset: u64,
+    len: u64,
+    words: BTreeSet<String>,
+    /// The entry with its request and response bodies left out.
+    entry: HistoryEntry,
+}
+
+impl IndexRecord {
+    fn new(entry: &HistoryEntry, offset: u64, len: u64) -> Self {
+        let words = entry_words(entry);
+        let mut entry = entry.clone();
+        entry.body = None;
+        entry.response_body = String::new();
+        IndexRecord {
+            offset,
+            len,
+            words,
+            entry,
+        }
+    }
+}
+
+/// One page of [`HistoryLog::search`] results.
+#[derive(Debug)]
+pub struct HistoryPage {
+    /// Number of matching entries across all pages.
+    pub total: usize,
+    pub entries: Vec<HistoryEntry>,
+}
+
+#[derive(Debug, Clone)]
+pub struct HistoryLog {
+    dir: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+}
+
+impl HistoryLog {
+    /// Opens (creating if needed) the log in `dir`.
+    pub fn open(dir: impl Into<PathBuf>) -> crate::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(HistoryLog {
+            dir,
+            max_bytes: 64 * 1024 * 1024,
+            max_files: 3,
+        })
+    }
+
+    /// `$XDG_DATA_HOME/hurl`, or `~/.local/share/hurl` when that is unset.
+    pub fn default_dir() -> Option<PathBuf> {
+        std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| {
+                std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share"))
+            })
+            .map(|dir| dir.join("hurl"))
+    }
+
+    /// Size at which the current file is rotated.
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Number of rotated files kept besides the current one.
+    pub fn with_max_files(mut self, max_files: usize) -> Self {
+        self.max_files = max_files;
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Redacts sensitive headers and appends the entry and its index line.
+    pub fn append(&self, entry: &HistoryEntry) -> crate::Result<()> {
+        let mut entry = entry.clone();
+        entry.redact();
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+
+        let _lock = self.lock()?;
+        let current = self.dir.join(CURRENT);
+        let mut size = match fs::metadata(&current) {
+            Ok(meta) => meta.len(),
+            Err(e) if e.kind() == ErrorKind::NotFound => 0,
+            Err(e) => return Err(e.into()),
+        };
+        if size > 0 && size + line.len() as u64 > self.max_bytes {
+            self.rotate()?;
+            size = 0;
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&current)?;
+        file.write_all(&line)?;
+
+        let mut record = serde_json::to_vec(&IndexRecord::new(&entry, size, line.len() as u64))?;
+        record.push(b'\n');
+        let mut index = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(index_path(&current))?;
+        index.write_all(&record)?;
+        Ok(())
+    }
+
+    /// Every entry still on disk, oldest first. This reads every file in
+    /// full; prefer [`search`](Self::search) and
+    /// [`resolve`](Self::resolve) when the bodies of all entries are not
+    /// needed.
+    pub fn load(&self) -> crate::Result<HistoryStore> {
+        let _lock = self.lock()?;
+        let mut store = HistoryStore::new();
+        for path in self.files() {
+            for entry in read_entries(&path)? {
+                store.save(entry);
+            }
+        }
+        Ok(store)
+    }
+
+    /// Runs `query` against the indexes and reads just the entries on the
+    /// requested page.
+    pub fn search(&self, query: &SearchQuery) -> crate::Result<HistoryPage> {
+        let _lock = self.lock()?;
+        let (store, locations) = self.load_index()?;
+        let page = store.search_page(query);
+        let entries = page
+            .entries
+            .iter()
+            .map(|summary| read_entry(&locations[&summary.id]))
+            .collect::<crate::Result<_>>()?;
+        Ok(HistoryPage {
+            total: page.total,
+            entries,
+        })
+    }
+
+    /// The entry with id `id`, or with an unambiguous id prefix `id`.
+    pub fn resolve(&self, id: &str) -> crate::Result<HistoryEntry> {
+        let _lock = self.lock()?;
+        let (store, locations) = self.load_index()?;
+        read_entry(&locations[&store.resolve(id)?.id])
+    }
+
+    /// The most recent `limit` entries, newest first.
+    pub fn recent(&self, limit: usize) -> crate::Result<Vec<HistoryEntry>> {
+        let query = SearchQuery::new()
+            .sort_by(SortKey::Timestamp)
+            .descending()
+            .limit(limit);
+        Ok(self.search(&query)?.entries)
+    }
+
+    /// A store of every entry without its bodies, indexed as usual, and
+    /// where each full entry is kept. The lock must be held.
+    fn load_index(&self) -> crate::Result<(HistoryStore, HashMap<String, Location>)> {
+        let mut store = HistoryStore::new();
+        let mut locations = HashMap::new();
+        for path in self.files() {
+            for record in file_index(&path)? {
+                let location = Location {
+                    path: path.clone(),
+                    offset: record.offset,
+                    len: record.len,
+                };
+                locations.insert(record.entry.id.clone(), location);
+                store.save_indexed(record.entry, record.words);
+            }
+        }
+        Ok((store, locations))
+    }
+
+    /// Removes the entries with the given ids. Returns how many were found.
+    pub fn delete(&self, ids: &[&str]) -> crate::Result<usize> {
+        let _lock = self.lock()?;
+        let mut removed = 0;
+        for path in self.files() {
+            let entries = read_entries(&path)?;
+            let before = entries.len();
+            let kept: Vec<_> = entries
+                .into_iter()
+                .filter(|e| !ids.contains(&e.id.as_str()))
+                .collect();
+            if kept.len() != before {
+                removed += before - kept.len();
+                rewrite(&path, &kept)?;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Removes every history file.
+    pub fn clear(&self) -> crate::Result<()> {
+        let _lock = self.lock()?;
+        for path in self.files() {
+            remove_if_exists(&index_path(&path))?;
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    /// Existing history files, oldest first.
+    fn files(&self) -> Vec<PathBuf> {
+        (1..=self.max_files)
+            .rev()
+            .map(|n| self.rotated(n))
+            .chain(std::iter::once(self.dir.join(CURRENT)))
+            .filter(|path| path.exists())
+            .collect()
+    }
+
+    fn rotated(&self, n: usize) -> PathBuf {
+        self.dir.join(format!("history.{}.jsonl", n))
+    }
+
+    /// Shifts every file up one place, together with its index.
+    fn rotate(&self) -> crate::Result<()> {
+        let current = self.dir.join(CURRENT);
+        if self.max_files == 0 {
+            remove_if_exists(&index_path(&current))?;
+            fs::remove_file(current)?;
+            return Ok(());
+        }
+        let oldest = self.rotated(self.max_files);
+        remove_if_exists(&index_path(&oldest))?;
+        remove_if_exists(&oldest)?;
+        for n in (1..self.max_files).rev() {
+            let from = self.rotated(n);
+            if from.exists() {
+                let to = self.rotated(n + 1);
+                rename_if_exists(&index_path(&from), &index_path(&to))?;
+                fs::rename(from, to)?;
+            }
+        }
+        let to = self.rotated(1);
+        rename_if_exists(&index_path(&current), &index_path(&to))?;
+        fs::rename(current, to)?;
+        Ok(())
+    }
+
+    /// Held until the returned file is dropped.
+    fn lock(&self) -> crate::Result<File> {
+        let file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.dir.join(LOCK))?;
+        file.lock()?;
+        Ok(file)
+    }
+}
+
+/// Reads one history file. Lines that do not parse, such as one cut short
+/// by a crash, are skipped.
+fn read_entries(path: &Path) -> crate::Result<Vec<HistoryEntry>> {
+    let content = fs::read_to_string(path)?;
+    Ok(content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Replaces a history file with `entries` and rebuilds its index.
+fn rewrite(path: &Path, entries: &[HistoryEntry]) -> crate::Result<Vec<IndexRecord>> {
+    let mut content = Vec::new();
+    let mut records = Vec::new();
+    for entry in entries {
+        let start = content.len();
+        serde_json::to_writer(&mut content, entry)?;
+        content.push(b'\n');
+        records.push(IndexRecord::new(
+            entry,
+            start as u64,
+            (content.len() - start) as u64,
+        ));
+    }
+    write_atomically(path, &content)?;
+
+    let mut index = Vec::new();
+    for record in &records {
+        serde_json::to_writer(&mut index, record)?;
+        index.push(b'\n');
+    }
+    write_atomically(&index_path(path), &index)?;
+    Ok(records)
+}
+
+fn write_atomically(path: &Path, content: &[u8]) -> crate::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, content)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// `history.jsonl` is indexed by `history.idx`, `history.1.jsonl` by
+/// `history.1.idx`.
+fn index_path(path: &Path) -> PathBuf {
+    path.with_extension("idx")
+}
+
+/// The index of one history file. One that is missing, unreadable, or
+/// whose records do not tile the file exactly is rebuilt, which also drops
+/// lines that do not parse.
+fn file_index(path: &Path) -> crate::Result<Vec<IndexRecord>> {
+    let size = fs::metadata(path)?.len();
+    if let Some(records) = read_index(&index_path(path))? {
+        let mut end = 0;
+        let tiled = records.iter().all(|record| {
+            let next = record.offset == end;
+            end = record.offset + record.len;
+            next
+        });
+        if tiled && end == size {
+            return Ok(records);
+        }
+    }
+    rewrite(path, &read_entries(path)?)
+}
+
+fn read_index(path: &Path) -> crate::Result<Option<Vec<IndexRecord>>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(content
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<_, _>>()
+        .ok())
+}
+
+/// Where a full entry is kept.
+#[derive(Debug)]
+struct Location {
+    path: PathBuf,
+    offset: u64,
+    len: u64,
+}
+
+fn read_entry(location: &Location) -> crate::Result<HistoryEntry> {
+    let mut file = File::open(&location.path)?;
+    file.seek(SeekFrom::Start(location.offset))?;
+    let mut line = vec![0; location.len as usize];
+    file.read_exact(&mut line)?;
+    Ok(serde_json::from_slice(&line)?)
+}
+
+fn remove_if_exists(path: &Path) -> crate::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+fn rename_if_exists(from: &Path, to: &Path) -> crate::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::headers::Headers;
+    use crate::http::request::RequestBuilder;
+    use crate::http::response::HttpResponse;
+    use crate::storage::history::REDACTED;
+
+    fn entry(path: &str) -> HistoryEntry {
+        let request = RequestBuilder::get(format!("https://api.example.com{}", path))
+            .header("Authorization", "Bearer secret")
+            .header("Accept", "application/json");
+        let response = HttpResponse::new(
+            200,
+            vec![("Set-Cookie".to_string(), "sid=abc".to_string())],
+            "{}",
+        );
+        HistoryEntry::from_request_response(&request, &response, vec![]).unwrap()
+    }
+
+    #[test]
+    fn test_append_redacts_and_load_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = HistoryLog::open(dir.path()).unwrap();
+        let first = entry("/a");
+        log.append(&first).unwrap();
+        log.append(&entry("/b")).unwrap();
+
+        let raw = fs::read_to_string(dir.path().join(CURRENT)).unwrap();
+        assert_eq!(raw.lines().count(), 2);
+        assert!(!raw.contains("secret") && !raw.contains("sid=abc"));
+
+        let store = HistoryLog::open(dir.path()).unwrap().load().unwrap();
+        let loaded = store.get(&first.id).unwrap();
+        assert_eq!(loaded.headers.get("Authorization"), Some(REDACTED));
+        assert_eq!(loaded.response_headers.get("Set-Cookie"), Some(REDACTED));
+        assert_eq!(loaded.headers.get("Accept"), Some("application/json"));
+        assert_eq!(store.list_all()[1].url, "https://api.example.com/b");
+    }
+
+    #[test]
+    fn test_rotation_keeps_max_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let line_len = serde_json::to_vec(&{
+            let mut e = entry("/0");
+            e.redact();
+            e
+        })
+        .unwrap()
+        .len() as u64;
+        let log = HistoryLog::open(dir.path())
+            .unwrap()
+            .with_max_bytes(line_len * 2 + 2)
+            .with_max_files(2);
+
+        let entries: Vec<_> = (0..7).map(|i| entry(&format!("/{}", i))).collect();
+        for e in &entries {
+            log.append(e).unwrap();
+        }
+
+        assert!(dir.path().join("history.2.jsonl").exists());
+        assert!(dir.path().join("history.2.idx").exists());
+        assert!(!dir.path().join("history.3.jsonl").exists());
+        assert_eq!(log.search(&SearchQuery::new()).unwrap().total, 5);
+        let urls: Vec<String> = log
+            .load()
+            .unwrap()
+            .list_all()
+            .iter()
+            .map(|e| e.url.clone())
+            .collect();
+        assert_eq!(urls.len(), 5);
+        assert_eq!(urls[0], "https://api.example.com/2");
+        assert_eq!(urls[4], "https://api.example.com/6");
+    }
+
+    #[test]
+    fn test_delete_clear_and_truncated_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = HistoryLog::open(dir.path()).unwrap();
+        let (a, b) = (entry("/a"), entry("/b"));
+        log.append(&a).unwrap();
+        log.append(&b).unwrap();
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(dir.path().join(CURRENT))
+            .unwrap();
+        file.write_all(b"{\"id\": \"cut-sho").unwrap();
+
+        assert_eq!(log.delete(&[&a.id, "missing"]).unwrap(), 1);
+        let store = log.load().unwrap();
+        assert_eq!(store.len(), 1);
+        assert!(store.get(&b.id).is_some());
+
+        log.clear().unwrap();
+        assert!(log.load().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_search_reads_only_the_page_from_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = HistoryLog::open(dir.path()).unwrap();
+        let mut ids = Vec::new();
+        for (path, body) in [("/a", "alpha red"), ("/b", "beta red"), ("/c", "gamma")] {
+            let request =
+                RequestBuilder::post(format!("https://api.example.com{}", path)).text_body(body);
+            let response = HttpResponse::new(200, Headers::new(), "{}");
+            let e = HistoryEntry::from_request_response(&request, &response, vec![]).unwrap();
+            ids.push(e.id.clone());
+            log.append(&e).unwrap();
+        }
+        let index = fs::read_to_string(dir.path().join("history.idx")).unwrap();
+        assert_eq!(index.lines().count(), 3);
+
+        // Garble the middle entry in place. Searches that do not return it
+        // never read it.
+        let raw = fs::read_to_string(dir.path().join(CURRENT)).unwrap();
+        let lines: Vec<&str> = raw.lines().collect();
+        let garbled = format!(
+            "{}\n{}\n{}\n",
+            lines[0],
+            "x".repeat(lines[1].len()),
+            lines[2]
+        );
+        fs::write(dir.path().join(CURRENT), garbled).unwrap();
+
+        let page = log.search(&SearchQuery::new().text("alpha")).unwrap();
+        assert_eq!(page.total, 1);
+        assert_eq!(page.entries[0].body.as_deref(), Some("alpha red"));
+        let page = log
+            .search(&SearchQuery::new().text("red").limit(1))
+            .unwrap();
+        assert_eq!(page.total, 2);
+        assert_eq!(page.entries[0].url, "https://api.example.com/a");
+        assert_eq!(
+            log.resolve(&ids[2][..8]).unwrap().url,
+            "https://api.example.com/c"
+        );
+        assert!(log.resolve(&ids[1]).is_err());
+    }
+
+    #[test]
+    fn test_missing_or_stale_index_is_rebuilt() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = HistoryLog::open(dir.path()).unwrap();
+        let (a, b) = (entry("/a"), entry("/b"));
+        log.append(&a).unwrap();
+        fs::remove_file(dir.path().join("history.idx")).unwrap();
+        log.append(&b).unwrap();
+
+        let page = log.search(&SearchQuery::new()).unwrap();
+        assert_eq!(page.total, 2);
+        assert_eq!(page.entries[1].id, b.id);
+        let index = fs::read_to_string(dir.path().join("history.idx")).unwrap();
+        assert_eq!(index.lines().count(), 2);
+    }
+}
@@ -1,5 +1,10 @@
 pub mod cache;
+pub mod diff;
+pub mod disk_cache;
 pub mod history;
+pub mod history_log;
 
 pub use cache::ResponseCache;
+pub use disk_cache::DiskCache;
 pub use history::HistoryStore;
+pub use history_log::HistoryLog;
@@ -10,10 +10,17 @@ pub enum Assertion {
         max: u16,
     },
     HeaderExists(String),
+    /// Passes when any value of the header equals `expected`.
     HeaderValue {
         name: String,
         expected: String,
     },
+    /// Compares the `index`th (zero-based) value of a repeated header.
+    HeaderNth {
+        name: String,
+        index: usize,
+        expected: String,
+    },
     BodyContains(String),
     BodyRegex(String),
     JsonPath {
@@ -128,6 +135,11 @@ pub fn assert_response(response: &HttpResponse, assertion: &Assertion) -> Assert
         Assertion::StatusRange { min, max } => assert_status_range(response, *min, *max),
         Assertion::HeaderExists(name) => assert_header_exists(response, name),
         Assertion::HeaderValue { name, expected } => assert_header_value(response, name, expected),
+        Assertion::HeaderNth {
+            name,
+            index,
+            expected,
+        } => assert_header_nth(response, name, *index, expected),
         Assertion::BodyContains(text) => assert_body_contains(response, text),
         Assertion::BodyRegex(pattern) => assert_body_regex(response, pattern),
         Assertion::JsonPath { path, expected } => assert_json_path(response, path, expected),
@@ -186,11 +198,13 @@ fn assert_header_exists(response: &HttpResponse, name: &str) -> AssertionResult
 }
 
 fn assert_header_value(response: &HttpResponse, name: &str, expected: &str) -> AssertionResult {
-    let actual = response
-        .header(name)
-        .unwrap_or("header not found")
-        .to_string();
-    let passed = response.header(name) == Some(expected);
+    let values = response.header_all(name);
+    let actual = if values.is_empty() {
+        "header not found".to_string()
+    } else {
+        values.join(", ")
+    };
+    let passed = values.contains(&expected);
 
     let message = format!(
         "Header '{}' value mismatch: expected '{}', got '{}'",
@@ -206,8 +220,32 @@ fn assert_header_value(response: &HttpResponse, name: &str, expected: &str) -> A
     )
 }
 
+fn assert_header_nth(
+    response: &HttpResponse,
+    name: &str,
+    index: usize,
+    expected: &str,
+) -> AssertionResult {
+    let values = response.header_all(name);
+    let actual = match values.get(index) {
+        Some(value) => value.to_string(),
+        None => format!("only {} value(s)", values.len()),
+    };
+
+    AssertionResult::new(
+        values.get(index) == Some(&expected),
+        "header_value".to_string(),
+        expected.to_string(),
+        actual.clone(),
+        format!(
+            "Header '{}'[{}] value mismatch: expected '{}', got '{}'",
+            name, index, expected, actual
+        ),
+    )
+}
+
 fn assert_body_contains(response: &HttpResponse, text: &str) -> AssertionResult {
-    let passed = response.body.contains(text);
+    let passed = response.text().contains(text);
 
     AssertionResult::new(
         passed,
@@ -223,7 +261,7 @@ fn assert_body_regex(response: &HttpResponse, pattern: &str) -> AssertionResult
 
     match regex_result {
         Ok(regex) => {
-            let passed = regex.is_match(&response.body);
+            let passed = regex.is_match(&response.text());
             AssertionResult::new(
                 passed,
                 "body_regex".to_string(),
@@ -243,7 +281,7 @@ fn assert_body_regex(response: &HttpResponse, pattern: &str) -> AssertionResult
 }
 
 fn assert_json_path(response: &HttpResponse, path: &str, expected: &Value) -> AssertionResult {
-    match serde_json::from_str::<Value>(&response.body) {
+    match serde_json::from_slice::<Value>(&response.body) {
         Ok(json) => {
             let actual_value = get_json_value(&json, path);
             let passed = actual_value == Some(expected.clone());
@@ -275,7 +313,7 @@ fn assert_json_type(
     path: &str,
     expected_type: JsonType,
 ) -> AssertionResult {
-    match serde_json::from_str::<Value>(&response.body) {
+    match serde_json::from_slice::<Value>(&response.body) {
         Ok(json) => {
             let value = get_json_value(&json, path);
             let actual_type = value.as_ref().map(determine_json_type);
@@ -443,6 +481,50 @@ mod tests {
         assert!(!result.passed);
     }
 
+    fn create_response_with_cookies() -> HttpResponse {
+        let headers = vec![
+            ("Set-Cookie".to_string(), "a=1".to_string()),
+            ("Content-Type".to_string(), "text/plain".to_string()),
+            ("set-cookie".to_string(), "b=2".to_string()),
+        ];
+        HttpResponse::new(200, headers, "")
+    }
+
+    #[test]
+    fn test_header_value_matches_any_repeated_value() {
+        let response = create_response_with_cookies();
+        let assertion = Assertion::HeaderValue {
+            name: "Set-Cookie".to_string(),
+            expected: "b=2".to_string(),
+        };
+        let result = assert_response(&response, &assertion);
+        assert!(result.passed);
+
+        let assertion = Assertion::HeaderValue {
+            name: "Set-Cookie".to_string(),
+            expected: "c=3".to_string(),
+        };
+        let result = assert_response(&response, &assertion);
+        assert!(!result.passed);
+        assert_eq!(result.actual, "a=1, b=2");
+    }
+
+    #[test]
+    fn test_header_nth_assertion() {
+        let response = create_response_with_cookies();
+        let nth = |index: usize, expected: &str| Assertion::HeaderNth {
+            name: "set-cookie".to_string(),
+            index,
+            expected: expected.to_string(),
+        };
+        assert!(assert_response(&response, &nth(1, "b=2")).passed);
+        assert!(!assert_response(&response, &nth(0, "b=2")).passed);
+
+        let result = assert_response(&response, &nth(2, "c=3"));
+        assert!(!result.passed);
+        assert_eq!(result.actual, "only 2 value(s)");
+    }
+
     #[test]
     fn test_body_contains_assertion_pass() {
         let response = create_response(200, r#"{"status":"ok"}"#);
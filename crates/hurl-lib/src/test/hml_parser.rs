@@ -1,6 +1,8 @@
+use crate::http::chaining::ExtractionRule;
+use crate::http::headers::Headers;
 use crate::test::{Assertion, TestCase, TestRequest};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::path::PathBuf;
 use std::time::Duration;
 
 #[derive(Debug, Clone)]
@@ -24,18 +26,32 @@ pub type ParseResult<T> = Result<T, ParseError>;
 
 pub struct HmlParser {
     lines: Vec<String>,
+    base_dir: Option<PathBuf>,
 }
 
 impl HmlParser {
     pub fn new(content: &str) -> Self {
         HmlParser {
             lines: content.lines().map(|l| l.to_string()).collect(),
+            base_dir: None,
         }
     }
 
+    /// Directory that relative `@file` paths are resolved against, usually
+    /// the one holding the `.hml` file. Defaults to the working directory.
+    pub fn with_base_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.base_dir = Some(dir.into());
+        self
+    }
+
     pub fn parse(&self) -> ParseResult<Vec<TestCase>> {
         let mut test_cases = Vec::new();
         let mut current_test: Option<TestCaseBuilder> = None;
+        // Headers and a body may follow the request line, up to the first
+        // assertion or tag.
+        let mut in_request = false;
+        // `@var` lines before the first test belong to it.
+        let mut leading_vars = Vec::new();
         let mut i = 0;
 
         while i < self.lines.len() {
@@ -46,6 +62,12 @@ impl HmlParser {
                 i += 1;
                 continue;
             }
+            if trimmed.starts_with("assert_")
+                || trimmed.starts_with("capture ")
+                || trimmed.starts_with("@tag:")
+            {
+                in_request = false;
+            }
 
             if trimmed.starts_with("@test") {
                 if let Some(test) = current_test {
@@ -65,7 +87,9 @@ impl HmlParser {
                     });
                 }
 
-                current_test = Some(TestCaseBuilder::new(test_name));
+                let mut test = TestCaseBuilder::new(test_name);
+                test.variables.append(&mut leading_vars);
+                current_test = Some(test);
             } else if trimmed.starts_with("GET")
                 || trimmed.starts_with("POST")
                 || trimmed.starts_with("PUT")
@@ -85,6 +109,7 @@ impl HmlParser {
 
                     test.set_method(parts[0].to_string());
                     test.set_url(parts[1].trim().to_string());
+                    in_request = true;
                 } else {
                     return Err(ParseError {
                         line: i + 1,
@@ -107,10 +132,10 @@ impl HmlParser {
                     let header_spec = trimmed.strip_prefix("assert_header:").unwrap_or("").trim();
                     let parts: Vec<&str> = header_spec.splitn(2, '=').collect();
                     if parts.len() == 2 {
-                        test.add_assertion(Assertion::HeaderValue {
-                            name: parts[0].trim().to_string(),
-                            expected: parts[1].trim().to_string(),
-                        });
+                        test.add_assertion(header_value_assertion(
+                            parts[0].trim(),
+                            parts[1].trim(),
+                        ));
                     } else {
                         test.add_assertion(Assertion::HeaderExists(header_spec.to_string()));
                     }
@@ -133,17 +158,12 @@ impl HmlParser {
                         let path = parts[0].trim().to_string();
                         let value_str = parts[1].trim();
 
-                        let expected_value = match value_str {
-                            "true" => Value::Bool(true),
-                            "false" => Value::Bool(false),
-                            "null" => Value::Null,
-                            s if s.starts_with('"') && s.ends_with('"') => {
-                                Value::String(s[1..s.len() - 1].to_string())
-                            }
-                            s if s.parse::<i64>().is_ok() => {
-                                Value::Number(s.parse::<i64>().unwrap().into())
-                            }
-                            s => Value::String(s.to_string()),
+                        // Templated values are kept as written and parsed
+                        // once the variables are substituted.
+                        let expected_value = if value_str.contains("{{") {
+                            Value::String(value_str.to_string())
+                        } else {
+                            parse_json_literal(value_str)
                         };
 
                         test.add_assertion(Assertion::JsonPath {
@@ -167,6 +187,54 @@ impl HmlParser {
                     let tag = trimmed.strip_prefix("@tag:").unwrap_or("").trim();
                     test.add_tag(tag.to_string());
                 }
+            } else if let Some(declaration) = trimmed.strip_prefix("@var ") {
+                let (name, value) = declaration
+                    .split_once('=')
+                    .map(|(name, value)| (name.trim(), value.trim()))
+                    .filter(|(name, _)| !name.is_empty())
+                    .ok_or_else(|| ParseError {
+                        line: i + 1,
+                        message: "Expected @var name = value".to_string(),
+                        context: line.clone(),
+                    })?;
+                let variable = (name.to_string(), value.to_string());
+                match current_test.as_mut() {
+                    Some(test) => test.variables.push(variable),
+                    None => leading_vars.push(variable),
+                }
+            } else if let Some(spec) = trimmed.strip_prefix("capture ") {
+                let rule = parse_capture(spec).ok_or_else(|| ParseError {
+                    line: i + 1,
+                    message: "Expected capture NAME: jsonpath PATH, header NAME, status, duration or size"
+                        .to_string(),
+                    context: line.clone(),
+                })?;
+                match current_test.as_mut() {
+                    Some(test) => test.captures.push(rule),
+                    None => {
+                        return Err(ParseError {
+                            line: i + 1,
+                            message: "Capture must be inside a @test section".to_string(),
+                            context: line.clone(),
+                        })
+                    }
+                }
+            } else if let Some(path) = trimmed.strip_prefix("@file") {
+                let test = self.request_section(&mut current_test, in_request, true, i)?;
+                test.set_body(self.read_body_file(path.trim_start_matches(':').trim(), i)?);
+            } else if let Some(tag) = trimmed.strip_prefix("<<") {
+                let test = self.request_section(&mut current_test, in_request, true, i)?;
+                let (body, end) = self.parse_heredoc(tag.trim(), i)?;
+                test.set_body(body);
+                i = end;
+            } else if trimmed.starts_with('{') || trimmed.starts_with('[') {
+                let test = self.request_section(&mut current_test, in_request, true, i)?;
+                let (body, end) = self.parse_json_body(i)?;
+                test.set_body(body);
+                i = end;
+            } else if let Some((name, value)) = parse_header_line(trimmed) {
+                self.request_section(&mut current_test, in_request, false, i)?
+                    .add_header(name, value);
             }
 
             i += 1;
@@ -178,17 +246,213 @@ impl HmlParser {
 
         Ok(test_cases)
     }
+
+    /// The test whose request headers and body are being read, or an error
+    /// if line `i` does not belong to a request, or repeats its body.
+    fn request_section<'a>(
+        &self,
+        current_test: &'a mut Option<TestCaseBuilder>,
+        in_request: bool,
+        for_body: bool,
+        i: usize,
+    ) -> ParseResult<&'a mut TestCaseBuilder> {
+        let error = |message: &str| ParseError {
+            line: i + 1,
+            message: message.to_string(),
+            context: self.lines[i].clone(),
+        };
+        let Some(test) = current_test else {
+            return Err(error("Request must be inside a @test section"));
+        };
+        if !in_request {
+            return Err(error(
+                "Headers and body must come after the request line, before any assertion",
+            ));
+        }
+        if for_body && test.body.is_some() {
+            return Err(error(&format!(
+                "Test '{}': request body already given",
+                test.name
+            )));
+        }
+        Ok(test)
+    }
+
+    /// Reads lines after a `<<TAG` line up to a line holding only `TAG`.
+    /// Returns the body and the index of the closing line.
+    fn parse_heredoc(&self, tag: &str, start: usize) -> ParseResult<(String, usize)> {
+        if tag.is_empty() {
+            return Err(ParseError {
+                line: start + 1,
+                message: "Heredoc needs a terminator, e.g. <<EOF".to_string(),
+                context: self.lines[start].clone(),
+            });
+        }
+        let end = (start + 1..self.lines.len())
+            .find(|&j| self.lines[j].trim() == tag)
+            .ok_or_else(|| ParseError {
+                line: start + 1,
+                message: format!("Unterminated body: no closing '{}' line", tag),
+                context: self.lines[start].clone(),
+            })?;
+        Ok((self.lines[start + 1..end].join("\n"), end))
+    }
+
+    /// Reads a JSON body starting at line `start`, across as many lines as
+    /// it takes to form a complete value once `{{name}}` placeholders are
+    /// filled in. Returns the body as written and the index of its last line.
+    fn parse_json_body(&self, start: usize) -> ParseResult<(String, usize)> {
+        let mut body = String::new();
+        let mut error = None;
+        for j in start..self.lines.len() {
+            if j > start && self.lines[j].trim().starts_with("@test") {
+                break;
+            }
+            if j > start {
+                body.push('\n');
+            }
+            body.push_str(&self.lines[j]);
+            match serde_json::from_str::<Value>(&mask_placeholders(&body)) {
+                Ok(_) => return Ok((body, j)),
+                Err(e) => error = Some(e),
+            }
+        }
+        Err(ParseError {
+            line: start + 1,
+            message: format!(
+                "Invalid JSON body: {}",
+                error.map(|e| e.to_string()).unwrap_or_default()
+            ),
+            context: self.lines[start].clone(),
+        })
+    }
+
+    fn read_body_file(&self, path: &str, i: usize) -> ParseResult<String> {
+        let error = |message: String| ParseError {
+            line: i + 1,
+            message,
+            context: self.lines[i].clone(),
+        };
+        if path.is_empty() {
+            return Err(error("@file needs a path".to_string()));
+        }
+        let resolved = match &self.base_dir {
+            Some(dir) => dir.join(path),
+            None => PathBuf::from(path),
+        };
+        std::fs::read_to_string(&resolved).map_err(|e| {
+            error(format!(
+                "Cannot read body file {}: {}",
+                resolved.display(),
+                e
+            ))
+        })
+    }
+}
+
+/// Stands a JSON number in for each `{{name}}`, so a templated body can be
+/// checked before its variables are known.
+fn mask_placeholders(text: &str) -> String {
+    let mut masked = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        masked.push_str(&rest[..start]);
+        masked.push('0');
+        rest = &rest[start + len + 4..];
+    }
+    masked.push_str(rest);
+    masked
+}
+
+/// A `Name: value` request header line. Names are HTTP tokens, so prose or
+/// URLs containing a colon are not mistaken for headers.
+fn parse_header_line(line: &str) -> Option<(&str, &str)> {
+    let (name, value) = line.split_once(':')?;
+    let is_token = !name.is_empty()
+        && !name.starts_with("assert_")
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c));
+    is_token.then(|| (name, value.trim()))
+}
+
+/// An `assert_json` expected value: `true`, `false`, `null`, a quoted
+/// string, an integer, or else the bare text as a string.
+pub(crate) fn parse_json_literal(value: &str) -> Value {
+    match value {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        "null" => Value::Null,
+        s if s.len() >= 2 && s.starts_with('"') && s.ends_with('"') => {
+            Value::String(s[1..s.len() - 1].to_string())
+        }
+        s => match s.parse::<i64>() {
+            Ok(n) => Value::Number(n.into()),
+            Err(_) => Value::String(s.to_string()),
+        },
+    }
+}
+
+/// `name: jsonpath $.a.b`, `name: header X-Token`, `name: status`,
+/// `name: duration` or `name: size`.
+fn parse_capture(spec: &str) -> Option<ExtractionRule> {
+    let (name, source) = spec.split_once(':')?;
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+    let source = source.trim();
+    let (kind, arg) = source
+        .split_once(char::is_whitespace)
+        .map(|(kind, arg)| (kind, arg.trim()))
+        .unwrap_or((source, ""));
+    match (kind, arg) {
+        ("jsonpath", path) if !path.is_empty() => {
+            let path = path.strip_prefix('$').unwrap_or(path);
+            Some(ExtractionRule::json_path(name, path))
+        }
+        ("header", header) if !header.is_empty() => Some(ExtractionRule::header(name, header)),
+        ("status", "") => Some(ExtractionRule::status(name)),
+        ("duration", "") => Some(ExtractionRule::duration(name)),
+        ("size", "") => Some(ExtractionRule::size(name)),
+        _ => None,
+    }
+}
+
+/// `Name=value` matches any value; `Name[n]=value` targets the nth one.
+fn header_value_assertion(name: &str, expected: &str) -> Assertion {
+    let indexed = name.strip_suffix(']').and_then(|rest| {
+        let (name, index) = rest.rsplit_once('[')?;
+        Some((name.trim(), index.trim().parse::<usize>().ok()?))
+    });
+
+    match indexed {
+        Some((name, index)) => Assertion::HeaderNth {
+            name: name.to_string(),
+            index,
+            expected: expected.to_string(),
+        },
+        None => Assertion::HeaderValue {
+            name: name.to_string(),
+            expected: expected.to_string(),
+        },
+    }
 }
 
 struct TestCaseBuilder {
     name: String,
     method: Option<String>,
     url: Option<String>,
-    headers: HashMap<String, String>,
+    headers: Headers,
     body: Option<String>,
     assertions: Vec<Assertion>,
     tags: Vec<String>,
     timeout: Duration,
+    variables: Vec<(String, String)>,
+    captures: Vec<ExtractionRule>,
 }
 
 impl TestCaseBuilder {
@@ -197,11 +461,13 @@ impl TestCaseBuilder {
             name,
             method: None,
             url: None,
-            headers: HashMap::new(),
+            headers: Headers::new(),
             body: None,
             assertions: Vec::new(),
             tags: Vec::new(),
             timeout: Duration::from_secs(30),
+            variables: Vec::new(),
+            captures: Vec::new(),
         }
     }
 
@@ -217,6 +483,14 @@ impl TestCaseBuilder {
         self.assertions.push(assertion);
     }
 
+    fn add_header(&mut self, name: &str, value: &str) {
+        self.headers.append(name, value);
+    }
+
+    fn set_body(&mut self, body: String) {
+        self.body = Some(body);
+    }
+
     fn add_tag(&mut self, tag: String) {
         self.tags.push(tag);
     }
@@ -245,6 +519,8 @@ impl TestCaseBuilder {
             assertions: self.assertions,
             tags: self.tags,
             timeout: self.timeout,
+            variables: self.variables,
+            captures: self.captures,
         })
     }
 }
@@ -330,6 +606,30 @@ assert_header: Content-Type=application/json
         assert_eq!(tests[0].assertions.len(), 1);
     }
 
+    #[test]
+    fn test_parse_indexed_header_assertion() {
+        let content = r#"
+@test Test
+GET https://example.com
+assert_header: Set-Cookie[1]=session=abc
+"#;
+
+        let parser = HmlParser::new(content);
+        let tests = parser.parse().unwrap();
+        match &tests[0].assertions[0] {
+            Assertion::HeaderNth {
+                name,
+                index,
+                expected,
+            } => {
+                assert_eq!(name, "Set-Cookie");
+                assert_eq!(*index, 1);
+                assert_eq!(expected, "session=abc");
+            }
+            other => panic!("unexpected assertion {:?}", other),
+        }
+    }
+
     #[test]
     fn test_parse_body_assertion() {
         let content = r#"
@@ -522,4 +822,161 @@ GET https://example.com
         let tests = parser.parse().unwrap();
         assert_eq!(tests.len(), 2);
     }
+
+    #[test]
+    fn test_parse_repeated_headers_keeps_every_value_in_order() {
+        let content = "@test Negotiate\nGET https://example.com\nAccept: text/html\nCookie: a=1\nAccept: application/json\nCookie: b=2\nassert_status: 200\n";
+
+        let tests = HmlParser::new(content).parse().unwrap();
+        let headers = &tests[0].request.headers;
+        assert_eq!(headers.get_all("Accept"), ["text/html", "application/json"]);
+        assert_eq!(headers.get_all("cookie"), ["a=1", "b=2"]);
+        let names: Vec<&str> = headers.iter().map(|(name, _)| name).collect();
+        assert_eq!(names, ["Accept", "Cookie", "Accept", "Cookie"]);
+    }
+
+    #[test]
+    fn test_parse_request_headers_and_json_body() {
+        let content = r#"
+@test CreateUser
+POST https://example.com/users
+Content-Type: application/json
+Authorization: Bearer abc:def
+{
+  "name": "ann",
+  "tags": ["a", "b"]
+}
+assert_status: 201
+"#;
+
+        let tests = HmlParser::new(content).parse().unwrap();
+        let request = &tests[0].request;
+        assert_eq!(
+            request.headers.get("Content-Type"),
+            Some("application/json")
+        );
+        assert_eq!(request.headers.get("Authorization"), Some("Bearer abc:def"));
+        assert_eq!(
+            request.body.as_deref(),
+            Some("{\n  \"name\": \"ann\",\n  \"tags\": [\"a\", \"b\"]\n}")
+        );
+        assert_eq!(tests[0].assertions.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_heredoc_body() {
+        let content = r#"
+@test Echo
+PUT https://example.com/notes/1
+Content-Type: text/plain
+<<EOF
+first line
+  assert_status: 500 is just text here
+
+EOF
+assert_status: 200
+"#;
+
+        let tests = HmlParser::new(content).parse().unwrap();
+        assert_eq!(
+            tests[0].request.body.as_deref(),
+            Some("first line\n  assert_status: 500 is just text here\n")
+        );
+        assert_eq!(tests[0].assertions.len(), 1);
+
+        let unterminated = "@test T\nPOST https://example.com\n<<END\ntext\n";
+        assert!(HmlParser::new(unterminated).parse().is_err());
+    }
+
+    #[test]
+    fn test_parse_file_body() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("payload.json"), "{\"id\": 7}").unwrap();
+        let content = "@test Upload\nPOST https://example.com\n@file payload.json\n";
+
+        let tests = HmlParser::new(content)
+            .with_base_dir(dir.path())
+            .parse()
+            .unwrap();
+        assert_eq!(tests[0].request.body.as_deref(), Some("{\"id\": 7}"));
+
+        let missing = "@test Upload\nPOST https://example.com\n@file missing.json\n";
+        let err = HmlParser::new(missing)
+            .with_base_dir(dir.path())
+            .parse()
+            .unwrap_err();
+        assert_eq!(err.line, 3);
+    }
+
+    #[test]
+    fn test_parse_request_section_errors() {
+        let header_after_assert = r#"
+@test T
+GET https://example.com
+assert_status: 200
+Accept: text/plain
+"#;
+        let err = HmlParser::new(header_after_assert).parse().unwrap_err();
+        assert_eq!(err.line, 5);
+
+        let two_bodies = "@test T\nPOST https://example.com\n{}\n[1]\n";
+        assert!(HmlParser::new(two_bodies).parse().is_err());
+
+        let bad_json = "@test T\nPOST https://example.com\n{\"a\": \nassert_status: 200\n";
+        let err = HmlParser::new(bad_json).parse().unwrap_err();
+        assert!(
+            err.message.starts_with("Invalid JSON body"),
+            "{}",
+            err.message
+        );
+    }
+
+    #[test]
+    fn test_parse_variables_and_captures() {
+        let content = r#"
+@var base = https://example.com
+@test Login
+@var user = ann
+POST {{base}}/login
+{"user": "{{user}}", "attempt": {{attempt}}}
+capture token: header X-Token
+capture id: jsonpath $.user.id
+capture code: status
+assert_json: user.id={{id}}
+
+@test Profile
+GET {{base}}/users/{{id}}
+Authorization: Bearer {{token}}
+"#;
+
+        let tests = HmlParser::new(content).parse().unwrap();
+        assert_eq!(
+            tests[0].variables,
+            vec![
+                ("base".to_string(), "https://example.com".to_string()),
+                ("user".to_string(), "ann".to_string()),
+            ]
+        );
+        assert_eq!(
+            tests[0].request.body.as_deref(),
+            Some(r#"{"user": "{{user}}", "attempt": {{attempt}}}"#)
+        );
+        assert_eq!(tests[0].captures.len(), 3);
+        match &tests[0].assertions[0] {
+            Assertion::JsonPath { expected, .. } => assert_eq!(expected, "{{id}}"),
+            other => panic!("unexpected assertion {:?}", other),
+        }
+        assert!(tests[1].variables.is_empty());
+        assert_eq!(tests[1].request.url, "{{base}}/users/{{id}}");
+        assert_eq!(
+            tests[1].request.headers.get("Authorization"),
+            Some("Bearer {{token}}")
+        );
+
+        for bad in ["capture id", "capture id: xpath //a", "capture : status"] {
+            let content = format!("@test T\nGET https://example.com\n{}\n", bad);
+            assert!(HmlParser::new(&content).parse().is_err(), "{}", bad);
+        }
+        assert!(HmlParser::new("@var = 1\n").parse().is_err());
+    }
 }
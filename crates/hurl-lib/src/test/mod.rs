@@ -1,10 +1,13 @@
 pub mod assertions;
 pub mod hml_parser;
+pub mod reporters;
 pub mod runner;
 
+use crate::http::chaining::ExtractionRule;
+use crate::http::headers::Headers;
 use assertions::{Assertion, AssertionResult};
 use chrono::Local;
-use std::collections::HashMap;
+use reporters::{HtmlReporter, Reporter};
 use std::time::Duration;
 
 #[derive(Debug, Clone)]
@@ -14,13 +17,17 @@ pub struct TestCase {
     pub assertions: Vec<Assertion>,
     pub tags: Vec<String>,
     pub timeout: Duration,
+    /// `@var` declarations, set before the request is sent.
+    pub variables: Vec<(String, String)>,
+    /// Values taken from the response for later tests to use.
+    pub captures: Vec<ExtractionRule>,
 }
 
 #[derive(Debug, Clone)]
 pub struct TestRequest {
     pub method: String,
     pub url: String,
-    pub headers: HashMap<String, String>,
+    pub headers: Headers,
     pub body: Option<String>,
 }
 
@@ -34,6 +41,9 @@ pub struct TestResult {
     pub assertion_results: Vec<AssertionResult>,
     pub error: Option<String>,
     pub timestamp: String,
+    /// Never run, e.g. because an earlier test failed under fail-fast.
+    /// A skipped test counts as neither passed nor failed.
+    pub skipped: bool,
 }
 
 impl TestResult {
@@ -47,6 +57,16 @@ impl TestResult {
             assertion_results: Vec::new(),
             error: None,
             timestamp: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            skipped: false,
+        }
+    }
+
+    /// A result for a test that was not run.
+    pub fn skipped(test_name: String) -> Self {
+        TestResult {
+            passed: false,
+            skipped: true,
+            ..TestResult::new(test_name)
         }
     }
 
@@ -72,7 +92,13 @@ impl TestResult {
     pub fn summary(&self) -> String {
         format!(
             "{}: {}/{} assertions passed in {:?}",
-            if self.passed { "✓ PASS" } else { "✗ FAIL" },
+            if self.skipped {
+                "- SKIP"
+            } else if self.passed {
+                "✓ PASS"
+            } else {
+                "✗ FAIL"
+            },
             self.assertions_passed,
             self.assertions_total,
             self.duration
@@ -140,14 +166,23 @@ impl TestReport {
     }
 
     pub fn failed_tests(&self) -> usize {
-        self.total_tests() - self.passed_tests()
+        self.run_tests() - self.passed_tests()
+    }
+
+    pub fn skipped_tests(&self) -> usize {
+        self.results.iter().filter(|r| r.skipped).count()
+    }
+
+    /// Tests that were run, i.e. all but the skipped ones.
+    pub fn run_tests(&self) -> usize {
+        self.total_tests() - self.skipped_tests()
     }
 
     pub fn success_rate(&self) -> f64 {
-        if self.total_tests() == 0 {
+        if self.run_tests() == 0 {
             100.0
         } else {
-            (self.passed_tests() as f64 / self.total_tests() as f64) * 100.0
+            (self.passed_tests() as f64 / self.run_tests() as f64) * 100.0
         }
     }
 
@@ -165,6 +200,7 @@ impl TestReport {
              Total Tests: {}\n\
              Passed: {}\n\
              Failed: {}\n\
+             Skipped: {}\n\
              Success Rate: {:.1}%\n\
              Total Assertions: {}\n\
              Passed Assertions: {}\n\
@@ -175,6 +211,7 @@ impl TestReport {
             self.total_tests(),
             self.passed_tests(),
             self.failed_tests(),
+            self.skipped_tests(),
             self.success_rate(),
             self.total_assertions(),
             self.passed_assertions(),
@@ -185,122 +222,7 @@ impl TestReport {
     }
 
     pub fn generate_html_report(&self) -> String {
-        let mut html = String::from(
-            r#"<!DOCTYPE html>
-<html>
-<head>
-    <meta charset="utf-8">
-    <title>Test Report</title>
-    <style>
-        body { font-family: Arial, sans-serif; margin: 20px; background: #f5f5f5; }
-        .header { background: #333; color: white; padding: 20px; border-radius: 5px; }
-        .summary { background: white; padding: 15px; margin: 15px 0; border-radius: 5px; }
-        .stat { display: inline-block; margin-right: 30px; }
-        .stat-label { color: #666; font-size: 0.9em; }
-        .stat-value { font-size: 1.5em; font-weight: bold; }
-        .test-result { background: white; padding: 15px; margin: 10px 0; border-radius: 5px; border-left: 5px solid #999; }
-        .test-result.pass { border-left-color: #28a745; }
-        .test-result.fail { border-left-color: #dc3545; }
-        .test-name { font-weight: bold; font-size: 1.1em; }
-        .test-status { margin: 10px 0; }
-        .pass { color: #28a745; }
-        .fail { color: #dc3545; }
-        .assertion { margin-left: 20px; padding: 10px; background: #f9f9f9; margin-top: 5px; border-radius: 3px; }
-        .assertion.pass { border-left: 3px solid #28a745; }
-        .assertion.fail { border-left: 3px solid #dc3545; }
-        table { width: 100%; border-collapse: collapse; }
-        th, td { padding: 10px; text-align: left; border-bottom: 1px solid #ddd; }
-        th { background: #f0f0f0; font-weight: bold; }
-    </style>
-</head>
-<body>
-"#,
-        );
-
-        html.push_str(&format!(
-            r#"<div class="header">
-    <h1>Test Report: {}</h1>
-    <p>Generated: {}</p>
-</div>"#,
-            self.suite_name,
-            Local::now().format("%Y-%m-%d %H:%M:%S")
-        ));
-
-        html.push_str(&format!(
-            r#"<div class="summary">
-    <h2>Summary</h2>
-    <div class="stat">
-        <div class="stat-label">Total Tests</div>
-        <div class="stat-value">{}</div>
-    </div>
-    <div class="stat">
-        <div class="stat-label">Passed</div>
-        <div class="stat-value pass">{}</div>
-    </div>
-    <div class="stat">
-        <div class="stat-label">Failed</div>
-        <div class="stat-value fail">{}</div>
-    </div>
-    <div class="stat">
-        <div class="stat-label">Success Rate</div>
-        <div class="stat-value">{:.1}%</div>
-    </div>
-</div>"#,
-            self.total_tests(),
-            self.passed_tests(),
-            self.failed_tests(),
-            self.success_rate()
-        ));
-
-        html.push_str("<h2>Test Results</h2>");
-
-        for result in &self.results {
-            let status_class = if result.passed { "pass" } else { "fail" };
-            let status_text = if result.passed { "PASS" } else { "FAIL" };
-
-            html.push_str(&format!(
-                r#"<div class="test-result {}">
-    <div class="test-name">{}</div>
-    <div class="test-status"><span class="{}">{}</span> - {:?}</div>
-    <div>Assertions: {}/{}</div>"#,
-                status_class,
-                result.test_name,
-                status_class,
-                status_text,
-                result.duration,
-                result.assertions_passed,
-                result.assertions_total
-            ));
-
-            if let Some(ref error) = result.error {
-                html.push_str(&format!("<div class=\"fail\">Error: {}</div>", error));
-            }
-
-            for assertion in &result.assertion_results {
-                let assert_class = if assertion.passed { "pass" } else { "fail" };
-                html.push_str(&format!(
-                    r#"<div class="assertion {}">
-        <strong>{}</strong>: {}
-        <div style="margin-top: 5px; color: #666;">Expected: {} | Actual: {}</div>
-    </div>"#,
-                    assert_class,
-                    assertion.assertion,
-                    if assertion.passed { "✓" } else { "✗" },
-                    assertion.expected,
-                    assertion.actual
-                ));
-            }
-
-            html.push_str("</div>");
-        }
-
-        html.push_str(
-            r#"
-</body>
-</html>"#,
-        );
-
-        html
+        HtmlReporter.render(self)
     }
 }
 
@@ -315,12 +237,14 @@ mod tests {
             request: TestRequest {
                 method: "GET".to_string(),
                 url: "https://example.com".to_string(),
-                headers: HashMap::new(),
+                headers: Headers::new(),
                 body: None,
             },
             assertions: Vec::new(),
             tags: vec!["smoke".to_string()],
             timeout: Duration::from_secs(30),
+            variables: vec![],
+            captures: vec![],
         };
 
         assert_eq!(test_case.name, "test_example");
@@ -384,12 +308,14 @@ mod tests {
             request: TestRequest {
                 method: "GET".to_string(),
                 url: "https://example.com".to_string(),
-                headers: HashMap::new(),
+                headers: Headers::new(),
                 body: None,
             },
             assertions: Vec::new(),
             tags: vec![],
             timeout: Duration::from_secs(30),
+            variables: vec![],
+            captures: vec![],
         };
 
         suite.add_test(test_case);
@@ -0,0 +1,618 @@
+//! Renderers that turn a [`TestReport`] into text, HTML, JUnit XML, TAP
+//! or JSON.
+
+use crate::test::{TestReport, TestResult};
+use chrono::Local;
+use serde_json::{json, Value};
+
+pub trait Reporter {
+    fn format(&self) -> ReportFormat;
+    fn render(&self, report: &TestReport) -> String;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Text,
+    Html,
+    Junit,
+    Tap,
+    Json,
+}
+
+impl ReportFormat {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" | "txt" => Some(ReportFormat::Text),
+            "html" => Some(ReportFormat::Html),
+            "junit" | "xml" => Some(ReportFormat::Junit),
+            "tap" => Some(ReportFormat::Tap),
+            "json" => Some(ReportFormat::Json),
+            _ => None,
+        }
+    }
+
+    pub fn reporter(self) -> Box<dyn Reporter + Send + Sync> {
+        match self {
+            ReportFormat::Text => Box::new(TextReporter),
+            ReportFormat::Html => Box::new(HtmlReporter),
+            ReportFormat::Junit => Box::new(JunitReporter),
+            ReportFormat::Tap => Box::new(TapReporter),
+            ReportFormat::Json => Box::new(JsonReporter),
+        }
+    }
+}
+
+/// [`TestReport::summary`] followed by one line per test and the details
+/// of each failure.
+pub struct TextReporter;
+
+impl Reporter for TextReporter {
+    fn format(&self) -> ReportFormat {
+        ReportFormat::Text
+    }
+
+    fn render(&self, report: &TestReport) -> String {
+        let mut text = report.summary();
+        text.push_str("\n\n");
+        for result in &report.results {
+            text.push_str(&format!(
+                "{} {} ({}ms)\n",
+                status(result),
+                result.test_name,
+                result.duration.as_millis()
+            ));
+            if let Some(error) = &result.error {
+                text.push_str(&format!("    Error: {}\n", error));
+            }
+            for assertion in result.assertion_results.iter().filter(|a| !a.passed) {
+                for line in assertion.format_failure().lines() {
+                    text.push_str(&format!("    {}\n", line));
+                }
+            }
+        }
+        text
+    }
+}
+
+pub struct HtmlReporter;
+
+impl Reporter for HtmlReporter {
+    fn format(&self) -> ReportFormat {
+        ReportFormat::Html
+    }
+
+    fn render(&self, report: &TestReport) -> String {
+        let mut html = String::from(
+            r#"<!DOCTYPE html>
+<html>
+<head>
+    <meta charset="utf-8">
+    <title>Test Report</title>
+    <style>
+        body { font-family: Arial, sans-serif; margin: 20px; background: #f5f5f5; }
+        .header { background: #333; color: white; padding: 20px; border-radius: 5px; }
+        .summary { background: white; padding: 15px; margin: 15px 0; border-radius: 5px; }
+        .stat { display: inline-block; margin-right: 30px; }
+        .stat-label { color: #666; font-size: 0.9em; }
+        .stat-value { font-size: 1.5em; font-weight: bold; }
+        .test-result { background: white; padding: 15px; margin: 10px 0; border-radius: 5px; border-left: 5px solid #999; }
+        .test-result.pass { border-left-color: #28a745; }
+        .test-result.fail { border-left-color: #dc3545; }
+        .test-result.skip { border-left-color: #999; }
+        .test-name { font-weight: bold; font-size: 1.1em; }
+        .test-status { margin: 10px 0; }
+        .pass { color: #28a745; }
+        .fail { color: #dc3545; }
+        .skip { color: #999; }
+        .assertion { margin-left: 20px; padding: 10px; background: #f9f9f9; margin-top: 5px; border-radius: 3px; }
+        .assertion.pass { border-left: 3px solid #28a745; }
+        .assertion.fail { border-left: 3px solid #dc3545; }
+        table { width: 100%; border-collapse: collapse; }
+        th, td { padding: 10px; text-align: left; border-bottom: 1px solid #ddd; }
+        th { background: #f0f0f0; font-weight: bold; }
+    </style>
+</head>
+<body>
+"#,
+        );
+
+        html.push_str(&format!(
+            r#"<div class="header">
+    <h1>Test Report: {}</h1>
+    <p>Generated: {}</p>
+</div>"#,
+            escape_xml(&report.suite_name),
+            Local::now().format("%Y-%m-%d %H:%M:%S")
+        ));
+
+        html.push_str(&format!(
+            r#"<div class="summary">
+    <h2>Summary</h2>
+    <div class="stat">
+        <div class="stat-label">Total Tests</div>
+        <div class="stat-value">{}</div>
+    </div>
+    <div class="stat">
+        <div class="stat-label">Passed</div>
+        <div class="stat-value pass">{}</div>
+    </div>
+    <div class="stat">
+        <div class="stat-label">Failed</div>
+        <div class="stat-value fail">{}</div>
+    </div>
+    <div class="stat">
+        <div class="stat-label">Skipped</div>
+        <div class="stat-value skip">{}</div>
+    </div>
+    <div class="stat">
+        <div class="stat-label">Success Rate</div>
+        <div class="stat-value">{:.1}%</div>
+    </div>
+</div>"#,
+            report.total_tests(),
+            report.passed_tests(),
+            report.failed_tests(),
+            report.skipped_tests(),
+            report.success_rate()
+        ));
+
+        html.push_str("<h2>Test Results</h2>");
+
+        for result in &report.results {
+            let status_text = status(result);
+            let status_class = status_text.to_ascii_lowercase();
+
+            html.push_str(&format!(
+                r#"<div class="test-result {}">
+    <div class="test-name">{}</div>
+    <div class="test-status"><span class="{}">{}</span> - {:?}</div>
+    <div>Assertions: {}/{}</div>"#,
+                status_class,
+                escape_xml(&result.test_name),
+                status_class,
+                status_text,
+                result.duration,
+                result.assertions_passed,
+                result.assertions_total
+            ));
+
+            if let Some(ref error) = result.error {
+                html.push_str(&format!(
+                    "<div class=\"fail\">Error: {}</div>",
+                    escape_xml(error)
+                ));
+            }
+
+            for assertion in &result.assertion_results {
+                let assert_class = if assertion.passed { "pass" } else { "fail" };
+                html.push_str(&format!(
+                    r#"<div class="assertion {}">
+        <strong>{}</strong>: {}
+        <div style="margin-top: 5px; color: #666;">Expected: {} | Actual: {}</div>
+    </div>"#,
+                    assert_class,
+                    escape_xml(&assertion.assertion),
+                    if assertion.passed { "✓" } else { "✗" },
+                    escape_xml(&assertion.expected),
+                    escape_xml(&assertion.actual)
+                ));
+            }
+
+            html.push_str("</div>");
+        }
+
+        html.push_str(
+            r#"
+</body>
+</html>"#,
+        );
+
+        html
+    }
+}
+
+/// JUnit XML as read by most CI servers: one `testsuite`, a `testcase` per
+/// test, `failure` for failed assertions, `error` when no response was
+/// checked and `skipped` for tests that were not run.
+pub struct JunitReporter;
+
+impl Reporter for JunitReporter {
+    fn format(&self) -> ReportFormat {
+        ReportFormat::Junit
+    }
+
+    fn render(&self, report: &TestReport) -> String {
+        let name = escape_xml(&report.suite_name);
+        let errors = report.results.iter().filter(|r| is_error(r)).count();
+        let failures = report.failed_tests() - errors;
+        let skipped = report.skipped_tests();
+        let time = report.total_duration.as_secs_f64();
+
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str(&format!(
+            "<testsuites name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{:.3}\">\n",
+            name,
+            report.total_tests(),
+            failures,
+            errors,
+            skipped,
+            time
+        ));
+        xml.push_str(&format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{:.3}\" timestamp=\"{}\">\n",
+            name,
+            report.total_tests(),
+            failures,
+            errors,
+            skipped,
+            time,
+            report.start_time.replace(' ', "T")
+        ));
+
+        for result in &report.results {
+            let open = format!(
+                "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\" assertions=\"{}\"",
+                escape_xml(&result.test_name),
+                name,
+                result.duration.as_secs_f64(),
+                result.assertions_total
+            );
+            if result.passed {
+                xml.push_str(&open);
+                xml.push_str("/>\n");
+                continue;
+            }
+            xml.push_str(&open);
+            xml.push_str(">\n");
+            if result.skipped {
+                xml.push_str("      <skipped/>\n");
+                xml.push_str("    </testcase>\n");
+                continue;
+            }
+            let failed: Vec<_> = result
+                .assertion_results
+                .iter()
+                .filter(|a| !a.passed)
+                .collect();
+            let details = failed
+                .iter()
+                .map(|a| a.format_failure())
+                .collect::<Vec<_>>()
+                .join("\n\n");
+            // An errored test is counted under `errors` only, so it gets a
+            // single `error` element that also carries any failed assertions.
+            if let Some(error) = &result.error {
+                if details.is_empty() {
+                    xml.push_str(&format!(
+                        "      <error message=\"{}\" type=\"error\"/>\n",
+                        escape_xml(error)
+                    ));
+                } else {
+                    xml.push_str(&format!(
+                        "      <error message=\"{}\" type=\"error\">{}</error>\n",
+                        escape_xml(error),
+                        escape_xml(&details)
+                    ));
+                }
+            } else if !failed.is_empty() {
+                xml.push_str(&format!(
+                    "      <failure message=\"{} of {} assertions failed\" type=\"assertion\">{}</failure>\n",
+                    failed.len(),
+                    result.assertions_total,
+                    escape_xml(&details)
+                ));
+            }
+            xml.push_str("    </testcase>\n");
+        }
+
+        xml.push_str("  </testsuite>\n</testsuites>\n");
+        xml
+    }
+}
+
+/// TAP version 13, with a YAML block of diagnostics under each failed test
+/// and a `# SKIP` directive on each test that was not run.
+pub struct TapReporter;
+
+impl Reporter for TapReporter {
+    fn format(&self) -> ReportFormat {
+        ReportFormat::Tap
+    }
+
+    fn render(&self, report: &TestReport) -> String {
+        // JSON strings are valid YAML double-quoted scalars.
+        let quote = |s: &str| Value::String(s.to_string()).to_string();
+
+        let mut tap = format!("TAP version 13\n1..{}\n", report.total_tests());
+        for (i, result) in report.results.iter().enumerate() {
+            if result.skipped {
+                tap.push_str(&format!(
+                    "ok {} - {} # SKIP not run\n",
+                    i + 1,
+                    result.test_name.replace('#', "\\#")
+                ));
+                continue;
+            }
+            let status = if result.passed { "ok" } else { "not ok" };
+            tap.push_str(&format!(
+                "{} {} - {}\n",
+                status,
+                i + 1,
+                result.test_name.replace('#', "\\#")
+            ));
+            if result.passed {
+                continue;
+            }
+            tap.push_str("  ---\n");
+            tap.push_str(&format!("  duration_ms: {}\n", result.duration.as_millis()));
+            if let Some(error) = &result.error {
+                tap.push_str(&format!("  error: {}\n", quote(error)));
+            }
+            let failed: Vec<_> = result
+                .assertion_results
+                .iter()
+                .filter(|a| !a.passed)
+                .collect();
+            if !failed.is_empty() {
+                tap.push_str("  failures:\n");
+                for assertion in failed {
+                    tap.push_str(&format!(
+                        "    - assertion: {}\n",
+                        quote(&assertion.assertion)
+                    ));
+                    tap.push_str(&format!("      message: {}\n", quote(&assertion.message)));
+                    tap.push_str(&format!("      expected: {}\n", quote(&assertion.expected)));
+                    tap.push_str(&format!("      actual: {}\n", quote(&assertion.actual)));
+                }
+            }
+            tap.push_str("  ...\n");
+        }
+        tap.push_str(&format!(
+            "# tests {}\n# pass {}\n# fail {}\n# skip {}\n",
+            report.total_tests(),
+            report.passed_tests(),
+            report.failed_tests(),
+            report.skipped_tests()
+        ));
+        tap
+    }
+}
+
+/// Version of the JSON layout; bumped only for incompatible changes.
+pub const JSON_SCHEMA_VERSION: u32 = 1;
+
+/// A JSON document with `schema_version`, `suite`, `started_at`,
+/// `finished_at`, `duration_ms`, a `summary` of counts and a `tests` array.
+/// Each test has `name`, `passed`, `skipped`, `duration_ms`, `timestamp`,
+/// `error` and `assertions`.
+pub struct JsonReporter;
+
+impl JsonReporter {
+    pub fn to_value(&self, report: &TestReport) -> Value {
+        json!({
+            "schema_version": JSON_SCHEMA_VERSION,
+            "suite": report.suite_name,
+            "started_at": report.start_time,
+            "finished_at": report.end_time,
+            "duration_ms": report.total_duration.as_millis() as u64,
+            "summary": {
+                "tests": report.total_tests(),
+                "passed": report.passed_tests(),
+                "failed": report.failed_tests(),
+                "skipped": report.skipped_tests(),
+                "assertions": report.total_assertions(),
+                "assertions_passed": report.passed_assertions(),
+                "success_rate": report.success_rate(),
+            },
+            "tests": report.results.iter().map(test_to_json).collect::<Vec<_>>(),
+        })
+    }
+}
+
+impl Reporter for JsonReporter {
+    fn format(&self) -> ReportFormat {
+        ReportFormat::Json
+    }
+
+    fn render(&self, report: &TestReport) -> String {
+        let mut json = serde_json::to_string_pretty(&self.to_value(report))
+            .unwrap_or_else(|_| "{}".to_string());
+        json.push('\n');
+        json
+    }
+}
+
+fn test_to_json(result: &TestResult) -> Value {
+    json!({
+        "name": result.test_name,
+        "passed": result.passed,
+        "skipped": result.skipped,
+        "duration_ms": result.duration.as_millis() as u64,
+        "timestamp": result.timestamp,
+        "error": result.error,
+        "assertions": result
+            .assertion_results
+            .iter()
+            .map(|a| json!({
+                "assertion": a.assertion,
+                "passed": a.passed,
+                "expected": a.expected,
+                "actual": a.actual,
+                "message": a.message,
+                "suggestion": a.suggestion,
+            }))
+            .collect::<Vec<_>>(),
+    })
+}
+
+/// `PASS`, `FAIL` or `SKIP`.
+fn status(result: &TestResult) -> &'static str {
+    if result.skipped {
+        "SKIP"
+    } else if result.passed {
+        "PASS"
+    } else {
+        "FAIL"
+    }
+}
+
+/// A failed test that has an error rather than, or as well as, failed
+/// assertions: the request could not be sent or a template was invalid.
+fn is_error(result: &TestResult) -> bool {
+    !result.passed && result.error.is_some()
+}
+
+/// Escapes text for XML and HTML content and attribute values, dropping
+/// control characters XML 1.0 does not allow.
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if (c as u32) < 0x20 => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::assertions::AssertionResult;
+    use std::time::Duration;
+
+    fn sample_report() -> TestReport {
+        let mut report = TestReport::new("api <smoke>".to_string());
+
+        let mut ok = TestResult::new("GetUser".to_string());
+        ok.add_assertion_result(AssertionResult::new(
+            true,
+            "status_code".into(),
+            "200".into(),
+            "200".into(),
+            "Status code matches".into(),
+        ));
+        ok.set_duration(Duration::from_millis(12));
+
+        let mut failed = TestResult::new("Create # user".to_string());
+        failed.add_assertion_result(AssertionResult::new(
+            false,
+            "body_contains".into(),
+            "\"id\"".into(),
+            "<empty>".into(),
+            "Body does not contain \"id\"".into(),
+        ));
+        failed.set_duration(Duration::from_millis(30));
+
+        let mut errored = TestResult::new("Down".to_string());
+        errored.add_assertion_result(AssertionResult::new(
+            false,
+            "status_code".into(),
+            "200".into(),
+            "500".into(),
+            "Status code mismatch".into(),
+        ));
+        errored.set_error("Capture failed: no such header".into());
+
+        report.add_result(ok);
+        report.add_result(failed);
+        report.add_result(errored);
+        report.add_result(TestResult::skipped("Cleanup".to_string()));
+        report.total_duration = Duration::from_millis(1500);
+        report.finalize();
+        report
+    }
+
+    #[test]
+    fn test_parse_report_format() {
+        assert_eq!(ReportFormat::parse("JUnit"), Some(ReportFormat::Junit));
+        assert_eq!(ReportFormat::parse("tap"), Some(ReportFormat::Tap));
+        assert_eq!(ReportFormat::parse("yaml"), None);
+        assert_eq!(
+            ReportFormat::parse("json").unwrap().reporter().format(),
+            ReportFormat::Json
+        );
+    }
+
+    #[test]
+    fn test_junit_report() {
+        let xml = JunitReporter.render(&sample_report());
+        assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n"));
+        assert!(xml.contains(
+            "<testsuites name=\"api &lt;smoke&gt;\" tests=\"4\" failures=\"1\" errors=\"1\" skipped=\"1\" time=\"1.500\">"
+        ));
+        assert!(xml.contains("skipped=\"1\" time=\"1.500\" timestamp="));
+        assert!(xml.contains(
+            "<testcase name=\"GetUser\" classname=\"api &lt;smoke&gt;\" time=\"0.012\" assertions=\"1\"/>"
+        ));
+        assert!(xml.contains(
+            "<failure message=\"1 of 1 assertions failed\" type=\"assertion\">FAILED: Body does not contain &quot;id&quot;\nExpected: &quot;id&quot;\nActual: &lt;empty&gt;"
+        ));
+        assert!(xml.contains(
+            "<error message=\"Capture failed: no such header\" type=\"error\">FAILED: Status code mismatch"
+        ));
+        assert_eq!(xml.matches("<failure ").count(), 1);
+        assert!(xml.contains(
+            "<testcase name=\"Cleanup\" classname=\"api &lt;smoke&gt;\" time=\"0.000\" assertions=\"0\">\n      <skipped/>\n    </testcase>"
+        ));
+        assert_eq!(xml.matches("<testcase ").count(), 4);
+        assert_eq!(xml.matches("</testcase>").count(), 3);
+    }
+
+    #[test]
+    fn test_tap_report() {
+        let tap = TapReporter.render(&sample_report());
+        let lines: Vec<&str> = tap.lines().collect();
+        assert_eq!(lines[0], "TAP version 13");
+        assert_eq!(lines[1], "1..4");
+        assert_eq!(lines[2], "ok 1 - GetUser");
+        assert_eq!(lines[3], "not ok 2 - Create \\# user");
+        assert!(tap.contains("      expected: \"\\\"id\\\"\"\n"));
+        assert!(tap.contains("  error: \"Capture failed: no such header\"\n"));
+        assert!(tap.contains("\nok 4 - Cleanup # SKIP not run\n"));
+        assert!(tap.ends_with("# tests 4\n# pass 1\n# fail 2\n# skip 1\n"));
+    }
+
+    #[test]
+    fn test_json_report_schema() {
+        let report = sample_report();
+        let value: Value = serde_json::from_str(&JsonReporter.render(&report)).unwrap();
+        assert_eq!(value["schema_version"], JSON_SCHEMA_VERSION);
+        assert_eq!(value["suite"], "api <smoke>");
+        assert_eq!(value["duration_ms"], 1500);
+        assert_eq!(value["summary"]["tests"], 4);
+        assert_eq!(value["summary"]["failed"], 2);
+        assert_eq!(value["summary"]["skipped"], 1);
+        assert_eq!(value["tests"][3]["skipped"], true);
+        assert_eq!(value["tests"][3]["passed"], false);
+        assert_eq!(value["tests"][0]["assertions"][0]["passed"], true);
+        assert_eq!(value["tests"][1]["assertions"][0]["actual"], "<empty>");
+        assert_eq!(value["tests"][2]["error"], "Capture failed: no such header");
+        assert_eq!(value["tests"][0]["error"], Value::Null);
+    }
+
+    #[test]
+    fn test_text_and_html_reports() {
+        let report = sample_report();
+        let text = TextReporter.render(&report);
+        assert!(text.starts_with("Test Suite: api <smoke>"));
+        assert!(text.contains("PASS GetUser (12ms)\n"));
+        assert!(text.contains("    Error: Capture failed: no such header\n"));
+        assert!(text.contains("SKIP Cleanup (0ms)\n"));
+        assert!(text.contains("Skipped: 1\n"));
+
+        let html = HtmlReporter.render(&report);
+        assert!(html.contains("Error: Capture failed: no such header"));
+        assert!(html.contains("<span class=\"skip\">SKIP</span>"));
+        assert!(report
+            .generate_html_report()
+            .contains("Test Report: api &lt;smoke&gt;"));
+        assert!(html.contains("Test Report: api &lt;smoke&gt;"));
+    }
+}
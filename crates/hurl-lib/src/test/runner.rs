@@ -1,15 +1,34 @@
+use crate::http::chaining::{Extractor, Variables};
 use crate::http::client::HttpClient;
 use crate::http::request::RequestBuilder;
 use crate::http::response::HttpResponse;
-use crate::test::assertions::assert_response;
+use crate::test::assertions::{assert_response, Assertion};
+use crate::test::hml_parser::parse_json_literal;
 use crate::test::{TestCase, TestReport, TestResult, TestSuite};
+use futures_util::stream::{self, StreamExt, TryStreamExt};
+use serde_json::Value;
 use std::collections::HashMap;
+use std::future::Future;
+use std::sync::OnceLock;
 use std::time::Instant;
-use tokio::task::JoinHandle;
+use tokio::runtime::{Handle, Runtime};
 
+/// How many tests `execute_parallel` runs at once unless
+/// `with_max_concurrent` says otherwise.
+pub const DEFAULT_MAX_CONCURRENT: usize = 10;
+
+/// Runs `TestCase`s through one shared `HttpClient`.
+///
+/// The `execute*` methods are async and meant for callers already on a
+/// runtime. The `run*` methods are a blocking facade over them for sync
+/// callers; they may also be called from inside a runtime.
 pub struct TestRunner {
     client: HttpClient,
     cache: TestResultCache,
+    variables: Variables,
+    max_concurrent: usize,
+    /// Drives the blocking facade; created on first use.
+    runtime: OnceLock<Runtime>,
 }
 
 pub struct TestResultCache {
@@ -23,11 +42,11 @@ impl TestResultCache {
         }
     }
 
-    fn insert(&mut self, key: String, result: TestResult) {
+    pub fn insert(&mut self, key: String, result: TestResult) {
         self.results.insert(key, result);
     }
 
-    fn get(&self, key: &str) -> Option<&TestResult> {
+    pub fn get(&self, key: &str) -> Option<&TestResult> {
         self.results.get(key)
     }
 
@@ -38,16 +57,66 @@ impl TestResultCache {
 
 impl TestRunner {
     pub fn new() -> crate::Result<Self> {
-        Ok(TestRunner {
-            client: HttpClient::new()?,
+        Ok(TestRunner::with_client(HttpClient::new()?))
+    }
+
+    /// Runs tests through `client`, sharing its configuration, cookies
+    /// and rate limiter.
+    pub fn with_client(client: HttpClient) -> Self {
+        TestRunner {
+            client,
             cache: TestResultCache::new(),
-        })
+            variables: Variables::new(),
+            max_concurrent: DEFAULT_MAX_CONCURRENT,
+            runtime: OnceLock::new(),
+        }
     }
 
-    pub fn run_test(&self, test: &TestCase) -> crate::Result<TestResult> {
+    /// Variables every test starts with, e.g. from `--var`.
+    pub fn with_variables(mut self, variables: Variables) -> Self {
+        self.variables = variables;
+        self
+    }
+
+    /// Caps how many tests `execute_parallel` has in flight. Values below
+    /// one are treated as one.
+    pub fn with_max_concurrent(mut self, max_concurrent: usize) -> Self {
+        self.max_concurrent = max_concurrent;
+        self
+    }
+
+    pub fn variables(&self) -> &Variables {
+        &self.variables
+    }
+
+    pub fn max_concurrent(&self) -> usize {
+        self.max_concurrent
+    }
+
+    pub async fn execute(&self, test: &TestCase) -> crate::Result<TestResult> {
+        let mut variables = self.variables.clone();
+        self.execute_with(test, &mut variables).await
+    }
+
+    /// Runs `test` with `variables` filled into its `{{name}}` placeholders,
+    /// then adds its `@var`s and captures to them for the tests after it.
+    pub async fn execute_with(
+        &self,
+        test: &TestCase,
+        variables: &mut Variables,
+    ) -> crate::Result<TestResult> {
         let start = Instant::now();
         let mut result = TestResult::new(test.name.clone());
 
+        let test = match render_test(test, variables) {
+            Ok(test) => test,
+            Err(e) => {
+                result.set_error(format!("Template error: {}", e));
+                result.set_duration(start.elapsed());
+                return Ok(result);
+            }
+        };
+
         let mut request_builder = match test.request.method.as_str() {
             "GET" => RequestBuilder::get(&test.request.url),
             "POST" => RequestBuilder::post(&test.request.url),
@@ -58,22 +127,39 @@ impl TestRunner {
             "OPTIONS" => RequestBuilder::options(&test.request.url),
             _ => {
                 result.set_error(format!("Unknown HTTP method: {}", test.request.method));
+                result.set_duration(start.elapsed());
                 return Ok(result);
             }
         };
 
         for (key, value) in &test.request.headers {
-            request_builder = request_builder.header(key, value);
+            request_builder = request_builder.append_header(key, value);
+        }
+
+        if let Some(body) = &test.request.body {
+            if !request_builder.has_header("Content-Type")
+                && matches!(
+                    serde_json::from_str(body),
+                    Ok(serde_json::Value::Object(_) | serde_json::Value::Array(_))
+                )
+            {
+                request_builder = request_builder.header("Content-Type", "application/json");
+            }
+            request_builder = request_builder.text_body(body);
         }
 
         request_builder = request_builder.set_timeout(test.timeout);
 
-        match self.execute_request(request_builder) {
+        match self.execute_request(request_builder).await {
             Ok(response) => {
                 for assertion in &test.assertions {
                     let assertion_result = assert_response(&response, assertion);
                     result.add_assertion_result(assertion_result);
                 }
+                match Extractor::apply_extractions(&response, &test.captures) {
+                    Ok(captured) => variables.merge(captured),
+                    Err(e) => result.set_error(format!("Capture failed: {}", e)),
+                }
             }
             Err(e) => {
                 result.set_error(format!("Request failed: {}", e));
@@ -85,11 +171,14 @@ impl TestRunner {
         Ok(result)
     }
 
-    pub fn run_suite(&mut self, suite: &TestSuite) -> crate::Result<TestReport> {
+    /// Runs the suite's tests one after another, carrying captured
+    /// variables from each test to the next.
+    pub async fn execute_suite(&self, suite: &TestSuite) -> crate::Result<TestReport> {
         let mut report = TestReport::new(suite.name.clone());
 
+        let mut variables = self.variables.clone();
         for test in &suite.test_cases {
-            let result = self.run_test(test)?;
+            let result = self.execute_with(test, &mut variables).await?;
             report.add_result(result);
         }
 
@@ -97,48 +186,46 @@ impl TestRunner {
         Ok(report)
     }
 
-    pub fn run_tests_parallel(&self, tests: &[TestCase]) -> crate::Result<Vec<TestResult>> {
-        let runtime = tokio::runtime::Runtime::new()?;
-        let results = runtime.block_on(self.run_parallel_async(tests))?;
-        Ok(results)
+    /// Runs independent tests with at most `max_concurrent` in flight.
+    /// Each starts from the runner's variables, and results are returned
+    /// in the order of `tests`.
+    pub async fn execute_parallel(&self, tests: &[TestCase]) -> crate::Result<Vec<TestResult>> {
+        let runs: Vec<_> = tests.iter().map(|test| self.execute(test)).collect();
+        stream::iter(runs)
+            .buffered(self.max_concurrent.max(1))
+            .try_collect()
+            .await
     }
 
-    async fn run_parallel_async(&self, tests: &[TestCase]) -> crate::Result<Vec<TestResult>> {
-        let mut handles: Vec<JoinHandle<crate::Result<TestResult>>> = Vec::new();
+    pub fn run_test(&self, test: &TestCase) -> crate::Result<TestResult> {
+        self.block_on(self.execute(test))?
+    }
 
-        for test in tests {
-            let test_clone = test.clone();
-            let handle = tokio::spawn(async move {
-                let runner = TestRunner::new()?;
-                runner.run_test(&test_clone)
-            });
-            handles.push(handle);
-        }
+    pub fn run_test_with(
+        &self,
+        test: &TestCase,
+        variables: &mut Variables,
+    ) -> crate::Result<TestResult> {
+        self.block_on(self.execute_with(test, variables))?
+    }
 
-        let mut results = Vec::new();
-        for handle in handles {
-            match handle.await {
-                Ok(Ok(result)) => results.push(result),
-                Ok(Err(e)) => {
-                    let mut result = TestResult::new("unknown".to_string());
-                    result.set_error(format!("Parallel execution error: {}", e));
-                    results.push(result);
-                }
-                Err(e) => {
-                    let mut result = TestResult::new("unknown".to_string());
-                    result.set_error(format!("Join error: {}", e));
-                    results.push(result);
-                }
-            }
-        }
+    pub fn run_suite(&mut self, suite: &TestSuite) -> crate::Result<TestReport> {
+        self.block_on(self.execute_suite(suite))?
+    }
 
-        Ok(results)
+    pub fn run_tests_parallel(&self, tests: &[TestCase]) -> crate::Result<Vec<TestResult>> {
+        self.block_on(self.execute_parallel(tests))?
     }
 
     pub fn filter_by_tag(&self, tests: &[TestCase], tag: &str) -> Vec<TestCase> {
+        self.filter_by_tags(tests, &[tag.to_string()])
+    }
+
+    /// Tests carrying at least one of `tags`, in their original order.
+    pub fn filter_by_tags(&self, tests: &[TestCase], tags: &[String]) -> Vec<TestCase> {
         tests
             .iter()
-            .filter(|t| t.tags.contains(&tag.to_string()))
+            .filter(|t| tags.iter().any(|tag| t.tags.contains(tag)))
             .cloned()
             .collect()
     }
@@ -155,15 +242,94 @@ impl TestRunner {
         self.cache.clear();
     }
 
-    fn execute_request(&self, builder: RequestBuilder) -> crate::Result<HttpResponse> {
-        let runtime = tokio::runtime::Runtime::new()?;
-        runtime.block_on(async {
-            let start = std::time::Instant::now();
-            let response = self.client.execute(builder).await?;
-            let duration = start.elapsed();
-            Ok(response.with_duration(duration))
+    async fn execute_request(&self, builder: RequestBuilder) -> crate::Result<HttpResponse> {
+        let start = Instant::now();
+        let response = self.client.execute(builder).await?;
+        Ok(response.with_duration(start.elapsed()))
+    }
+
+    /// Drives `future` on the runner's own runtime. Called from inside
+    /// another runtime, it blocks on a scoped thread instead, as a thread
+    /// that is already running a runtime cannot enter a second one.
+    fn block_on<F>(&self, future: F) -> crate::Result<F::Output>
+    where
+        F: Future + Send,
+        F::Output: Send,
+    {
+        let run = || Ok(self.runtime()?.block_on(future));
+        if Handle::try_current().is_err() {
+            return run();
+        }
+        std::thread::scope(|scope| match scope.spawn(run).join() {
+            Ok(output) => output,
+            Err(panic) => std::panic::resume_unwind(panic),
         })
     }
+
+    /// A multi-threaded runtime, so connections pooled while it drove a
+    /// request keep being serviced between blocking calls.
+    fn runtime(&self) -> crate::Result<&Runtime> {
+        if let Some(runtime) = self.runtime.get() {
+            return Ok(runtime);
+        }
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()?;
+        Ok(self.runtime.get_or_init(|| runtime))
+    }
+}
+
+impl Drop for TestRunner {
+    fn drop(&mut self) {
+        // Dropping a runtime blocks, which panics inside an async context.
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+/// Sets `test`'s `@var`s in `variables`, then returns a copy of the test
+/// with every `{{name}}` in its request and assertions replaced.
+fn render_test(test: &TestCase, variables: &mut Variables) -> crate::Result<TestCase> {
+    for (name, value) in &test.variables {
+        let value = variables.render(value)?;
+        variables.set(name, Value::String(value));
+    }
+
+    let mut test = test.clone();
+    let request = &mut test.request;
+    request.url = variables.render(&request.url)?;
+    for value in request.headers.values_mut() {
+        *value = variables.render(value)?;
+    }
+    if let Some(body) = &request.body {
+        request.body = Some(variables.render(body)?);
+    }
+    for assertion in &mut test.assertions {
+        render_assertion(assertion, variables)?;
+    }
+    Ok(test)
+}
+
+fn render_assertion(assertion: &mut Assertion, variables: &Variables) -> crate::Result<()> {
+    match assertion {
+        Assertion::HeaderValue { expected, .. } | Assertion::HeaderNth { expected, .. } => {
+            *expected = variables.render(expected)?;
+        }
+        Assertion::BodyContains(text) | Assertion::BodyRegex(text) => {
+            *text = variables.render(text)?;
+        }
+        Assertion::JsonPath { expected, .. } => {
+            if let Value::String(template) = expected {
+                if template.contains("{{") {
+                    *expected = parse_json_literal(&variables.render(template)?);
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(())
 }
 
 impl Default for TestRunner {
@@ -175,7 +341,7 @@ impl Default for TestRunner {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::collections::HashMap;
+    use crate::http::headers::Headers;
 
     #[test]
     fn test_runner_creation() {
@@ -210,12 +376,14 @@ mod tests {
             request: crate::test::TestRequest {
                 method: "GET".to_string(),
                 url: "https://example.com".to_string(),
-                headers: HashMap::new(),
+                headers: Headers::new(),
                 body: None,
             },
             assertions: vec![],
             tags: vec!["smoke".to_string(), "api".to_string()],
             timeout: std::time::Duration::from_secs(30),
+            variables: vec![],
+            captures: vec![],
         };
 
         let test2 = TestCase {
@@ -223,12 +391,14 @@ mod tests {
             request: crate::test::TestRequest {
                 method: "GET".to_string(),
                 url: "https://example.com".to_string(),
-                headers: HashMap::new(),
+                headers: Headers::new(),
                 body: None,
             },
             assertions: vec![],
             tags: vec!["integration".to_string()],
             timeout: std::time::Duration::from_secs(30),
+            variables: vec![],
+            captures: vec![],
         };
 
         tests.push(test1);
@@ -239,6 +409,21 @@ mod tests {
         assert_eq!(filtered[0].name, "test1");
     }
 
+    #[test]
+    fn test_filter_by_tags_matches_any_tag_in_order() {
+        use crate::test::hml_parser::HmlParser;
+
+        let content = "@test a\n@tag: smoke\nGET https://example.com\n\n\
+                       @test b\nGET https://example.com\n\n\
+                       @test c\n@tag: slow\nGET https://example.com\n";
+        let tests = HmlParser::new(content).parse().unwrap();
+        let runner = TestRunner::new().unwrap();
+
+        let filtered = runner.filter_by_tags(&tests, &["slow".into(), "smoke".into()]);
+        let names: Vec<&str> = filtered.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["a", "c"]);
+    }
+
     #[test]
     fn test_filter_by_name() {
         let runner = TestRunner::new().unwrap();
@@ -248,24 +433,28 @@ mod tests {
                 request: crate::test::TestRequest {
                     method: "GET".to_string(),
                     url: "https://example.com".to_string(),
-                    headers: HashMap::new(),
+                    headers: Headers::new(),
                     body: None,
                 },
                 assertions: vec![],
                 tags: vec![],
                 timeout: std::time::Duration::from_secs(30),
+                variables: vec![],
+                captures: vec![],
             },
             TestCase {
                 name: "test_post_user".to_string(),
                 request: crate::test::TestRequest {
                     method: "POST".to_string(),
                     url: "https://example.com".to_string(),
-                    headers: HashMap::new(),
+                    headers: Headers::new(),
                     body: None,
                 },
                 assertions: vec![],
                 tags: vec![],
                 timeout: std::time::Duration::from_secs(30),
+                variables: vec![],
+                captures: vec![],
             },
         ];
 
@@ -287,24 +476,28 @@ mod tests {
                 request: crate::test::TestRequest {
                     method: "GET".to_string(),
                     url: "https://example.com".to_string(),
-                    headers: HashMap::new(),
+                    headers: Headers::new(),
                     body: None,
                 },
                 assertions: vec![],
                 tags: vec!["smoke".to_string()],
                 timeout: std::time::Duration::from_secs(30),
+                variables: vec![],
+                captures: vec![],
             },
             TestCase {
                 name: "test2".to_string(),
                 request: crate::test::TestRequest {
                     method: "GET".to_string(),
                     url: "https://example.com".to_string(),
-                    headers: HashMap::new(),
+                    headers: Headers::new(),
                     body: None,
                 },
                 assertions: vec![],
                 tags: vec!["smoke".to_string()],
                 timeout: std::time::Duration::from_secs(30),
+                variables: vec![],
+                captures: vec![],
             },
         ];
 
@@ -320,12 +513,14 @@ mod tests {
             request: crate::test::TestRequest {
                 method: "GET".to_string(),
                 url: "https://example.com".to_string(),
-                headers: HashMap::new(),
+                headers: Headers::new(),
                 body: None,
             },
             assertions: vec![],
             tags: vec!["smoke".to_string()],
             timeout: std::time::Duration::from_secs(30),
+            variables: vec![],
+            captures: vec![],
         }];
 
         let filtered = runner.filter_by_tag(&tests, "integration");
@@ -340,12 +535,14 @@ mod tests {
             request: crate::test::TestRequest {
                 method: "GET".to_string(),
                 url: "https://example.com".to_string(),
-                headers: HashMap::new(),
+                headers: Headers::new(),
                 body: None,
             },
             assertions: vec![],
             tags: vec![],
             timeout: std::time::Duration::from_secs(30),
+            variables: vec![],
+            captures: vec![],
         }];
 
         let filtered = runner.filter_by_name(&tests, "delete");
@@ -361,24 +558,28 @@ mod tests {
                 request: crate::test::TestRequest {
                     method: "GET".to_string(),
                     url: "https://example.com".to_string(),
-                    headers: HashMap::new(),
+                    headers: Headers::new(),
                     body: None,
                 },
                 assertions: vec![],
                 tags: vec![],
                 timeout: std::time::Duration::from_secs(30),
+                variables: vec![],
+                captures: vec![],
             },
             TestCase {
                 name: "test_users_post".to_string(),
                 request: crate::test::TestRequest {
                     method: "POST".to_string(),
                     url: "https://example.com".to_string(),
-                    headers: HashMap::new(),
+                    headers: Headers::new(),
                     body: None,
                 },
                 assertions: vec![],
                 tags: vec![],
                 timeout: std::time::Duration::from_secs(30),
+                variables: vec![],
+                captures: vec![],
             },
         ];
 
@@ -391,4 +592,159 @@ mod tests {
         let mut runner = TestRunner::new().unwrap();
         runner.clear_cache();
     }
+
+    #[test]
+    fn test_run_test_sends_headers_and_body() {
+        use crate::http::test_server::{MockResponse, TestServer};
+        use crate::test::hml_parser::HmlParser;
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let server = rt.block_on(TestServer::start(|_, _| MockResponse::new(201)));
+        let content = format!(
+            "@test CreateUser\nPOST {}\nAuthorization: Bearer t0k\n{{\"name\": \"ann\"}}\nassert_status: 201\n",
+            server.url("/users")
+        );
+        let tests = HmlParser::new(&content).parse().unwrap();
+
+        let result = TestRunner::new().unwrap().run_test(&tests[0]).unwrap();
+        assert!(result.passed, "{:?}", result);
+
+        let requests = server.requests();
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].header("authorization"), Some("Bearer t0k"));
+        assert_eq!(requests[0].header("content-type"), Some("application/json"));
+        assert_eq!(requests[0].body, br#"{"name": "ann"}"#);
+    }
+
+    #[test]
+    fn test_run_suite_carries_captures_between_tests() {
+        use crate::http::test_server::{MockResponse, TestServer};
+        use crate::test::hml_parser::HmlParser;
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let server = rt.block_on(TestServer::start(|req, _| {
+            if req.path == "/login" {
+                MockResponse::ok(r#"{"user": {"id": 42, "name": "ann"}}"#).header("X-Token", "t0k")
+            } else {
+                MockResponse::ok(format!(r#"{{"id": 42, "path": "{}"}}"#, req.path))
+            }
+        }));
+        let content = r#"
+@test Login
+POST {{base}}/login
+{"user": "{{user}}"}
+capture token: header X-Token
+capture id: jsonpath $.user.id
+assert_json: user.name="{{user}}"
+
+@test Profile
+GET {{base}}/users/{{id}}
+Authorization: Bearer {{token}}
+assert_json: id={{id}}
+assert_body: /users/{{id}}
+
+@test Missing
+GET {{base}}/{{nope}}
+"#;
+        let mut suite = TestSuite::new("captures");
+        suite.add_tests(HmlParser::new(content).parse().unwrap());
+
+        let mut variables = Variables::new();
+        variables.set("base", Value::String(server.url("")));
+        variables.set("user", Value::String("ann".into()));
+        let mut runner = TestRunner::new().unwrap().with_variables(variables);
+        let report = runner.run_suite(&suite).unwrap();
+
+        assert!(report.results[0].passed, "{:?}", report.results[0]);
+        assert!(report.results[1].passed, "{:?}", report.results[1]);
+        let error = report.results[2].error.as_deref().unwrap();
+        assert!(error.contains("undefined variable: nope"), "{}", error);
+
+        let requests = server.requests();
+        assert_eq!(requests[0].body, br#"{"user": "ann"}"#);
+        assert_eq!(requests[1].path, "/users/42");
+        assert_eq!(requests[1].header("authorization"), Some("Bearer t0k"));
+        assert_eq!(requests.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_method_is_an_error_with_a_duration() {
+        use crate::test::TestRequest;
+
+        // The .hml parser rejects unknown methods, but a TestCase can be
+        // built directly.
+        let test = TestCase {
+            name: "Brew".into(),
+            request: TestRequest {
+                method: "BREW".into(),
+                url: "http://127.0.0.1:9/pot".into(),
+                headers: Headers::new(),
+                body: None,
+            },
+            assertions: vec![],
+            tags: vec![],
+            timeout: std::time::Duration::from_secs(1),
+            variables: vec![],
+            captures: vec![],
+        };
+        let result = TestRunner::new().unwrap().execute(&test).await.unwrap();
+        assert!(!result.passed);
+        assert_eq!(result.error.as_deref(), Some("Unknown HTTP method: BREW"));
+        assert!(result.duration > std::time::Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_execute_parallel_is_bounded_and_ordered() {
+        use crate::http::test_server::{MockResponse, TestServer};
+        use crate::test::hml_parser::HmlParser;
+        use std::time::Duration;
+
+        let server = TestServer::start(|req, n| {
+            // Later requests answer sooner, so completion order differs
+            // from test order.
+            MockResponse::ok(req.path.clone()).delay(Duration::from_millis(300 - 40 * n as u64))
+        })
+        .await;
+        let content: String = (0..5)
+            .map(|i| {
+                format!(
+                    "@test t{i}\nGET {}\nassert_body: /{i}\n\n",
+                    server.url(&format!("/{i}"))
+                )
+            })
+            .collect();
+        let tests = HmlParser::new(&content).parse().unwrap();
+        let runner = TestRunner::new().unwrap().with_max_concurrent(2);
+
+        let running = tokio::spawn(async move { runner.execute_parallel(&tests).await });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(server.request_count(), 2);
+
+        let results = running.await.unwrap().unwrap();
+        let names: Vec<&str> = results.iter().map(|r| r.test_name.as_str()).collect();
+        assert_eq!(names, ["t0", "t1", "t2", "t3", "t4"]);
+        assert!(results.iter().all(|r| r.passed), "{:?}", results);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_blocking_api_inside_runtime() {
+        use crate::http::test_server::{MockResponse, TestServer};
+        use crate::test::hml_parser::HmlParser;
+
+        let server = TestServer::start(|_, _| MockResponse::ok("pong")).await;
+        let content = format!(
+            "@test Ping\nGET {}\nassert_body: pong\n\n@test Again\nGET {}\nassert_status: 200\n",
+            server.url("/ping"),
+            server.url("/ping")
+        );
+        let mut suite = TestSuite::new("blocking");
+        suite.add_tests(HmlParser::new(&content).parse().unwrap());
+
+        let mut runner = TestRunner::new().unwrap();
+        let result = runner.run_test(&suite.test_cases[0]).unwrap();
+        assert!(result.passed, "{:?}", result);
+        let report = runner.run_suite(&suite).unwrap();
+        assert_eq!(report.passed_tests(), 2);
+        assert_eq!(server.request_count(), 3);
+    }
 }
@@ -22,6 +22,27 @@ pub fn format_duration(millis: u128) -> String {
     }
 }
 
+/// Parses an HTTP date (RFC 1123, RFC 850 or asctime, plus the dashed
+/// variants found in cookie `Expires`). Returns a Unix timestamp.
+pub fn parse_http_date(value: &str) -> Option<i64> {
+    if let Ok(at) = chrono::DateTime::parse_from_rfc2822(value) {
+        return Some(at.timestamp());
+    }
+    let value = value
+        .trim()
+        .trim_end_matches(" GMT")
+        .trim_end_matches(" UTC");
+    [
+        "%a, %d-%b-%Y %H:%M:%S",
+        "%A, %d-%b-%y %H:%M:%S",
+        "%a, %d %b %Y %H:%M:%S",
+        "%a %b %e %H:%M:%S %Y",
+    ]
+    .iter()
+    .find_map(|format| chrono::NaiveDateTime::parse_from_str(value, format).ok())
+    .map(|at| at.and_utc().timestamp())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;